@@ -1,180 +1,51 @@
-use std::{time};
-use std::sync::{Arc};
-use std::sync::atomic::{AtomicUsize, Ordering};
-use rand::{random_bool, random_range, rng, Rng};
-
-#[derive(Debug, Copy, Clone)]
-pub struct Particle {
-    x: f32,
-    y: f32,
-    id: usize
-}
-impl Particle {
-    pub fn new(x_param:f32, y_param:f32, id_param: usize) -> Particle {
-        Particle {
-            x: x_param,
-            y: y_param,
-            id:id_param
-        }
-    }
-    pub fn collide(&self, other: &Particle) -> bool {
-        let x = other.x - self.x;
-        let y = other.y - self.y;
-        return x * x + y * y <= 0.25 * 0.25;
-    }
+use clap::error::ErrorKind;
+use clap::{CommandFactory, FromArgMatches, Parser};
+use particle_system::{compare_counters, Cli, CounterKind, Dimension, ParticleSystem, Precision, Scalar, SimulationConfig, ThreeD, TwoD};
+
+#[derive(Debug, Parser)]
+#[command(about = "Colliding particle simulation counting collisions in a shared counter")]
+struct Args {
+    /// Run the same seeded workload with every counter backend & report throughput & correctness
+    #[arg(long)]
+    compare_counters: bool,
+    #[command(flatten)]
+    cli: Cli
 }
 
-pub struct ParticleSystem {
-    particles: Vec<Particle>,
-    collision_counter: Arc<AtomicUsize>
-}
-impl ParticleSystem {
-    pub fn new()-> ParticleSystem {
-        ParticleSystem {
-            particles: Vec::new(),
-            collision_counter: Arc::new(AtomicUsize::new(0))
-        }
-    }
-    pub fn move_particles_loop(&mut self) {
-        // Loop and measure time. Without print statements, roughly 6,000,000 loops equates to 10 seconds (Ryzen 5 7600x).
-        // With print statements, the loop count drastically decreases to around 2000.
-        let num_iterations = 20000;
-        // let num_iterations = 6000000;
-        let thread_count = 12;
-        let particles_per_thread = self.particles.len() / thread_count;
-        println!("Moving {} particles {} times across {} threads...", self.particles.len(), num_iterations, thread_count);
-        let start_time = time::Instant::now();
-
-        // Initialise threads
-        let mut pool = scoped_threadpool::Pool::new(thread_count as u32);
-        pool.scoped(|scope| {
-            let mut i = 0usize;
-            for chunk in self.particles.chunks_mut(particles_per_thread) {
-                scope.execute(move || thread_main(chunk, num_iterations, i));
-                i += 1;
-            }
-        });
-
-        let duration = time::Instant::now().duration_since(start_time);
-        println!("Took {} ms to move {} particles {} times", duration.as_millis(), self.particles.len(), num_iterations);
-    }
-    pub fn collide_particles(&mut self) {
-        let list_len = self.particles.len();
-        let thread_count = 12;
-        let particles_per_thread = list_len / thread_count;
-        let mut collision_pool = scoped_threadpool::Pool::new(thread_count as u32);
-
-        println!("Checking collisions...");
-        let start_time = time::Instant::now();
-
-        collision_pool.scoped(|scope| {
-            let mut thread_id = 0usize;
-
-            for i in 0..thread_count {
-                let clone = self.particles.clone();
-                let counter_clone = Arc::clone(&self.collision_counter);
-                scope.execute(move || thread_collide(&clone, &counter_clone, particles_per_thread, thread_id));
-                thread_id += 1;
-            }
-        });
-
-        let duration = time::Instant::now().duration_since(start_time);
-        println!("Took {} ms to check collisions. Detected {} collisions", duration.as_millis(), self.collision_counter.load(Ordering::Relaxed));
+fn main() {
+    // Read run settings from the command line; this version counts into one relaxed AtomicUsize unless told otherwise
+    let mut command = Args::command();
+    let args = Args::from_arg_matches(&command.get_matches_mut()).unwrap_or_else(|error| error.exit());
+    let config = args.cli.apply(SimulationConfig { counter: CounterKind::AtomicRelaxed, ..SimulationConfig::default() });
+    config.validate().unwrap_or_else(|error| command.error(ErrorKind::ValueValidation, error).exit());
+    match (args.compare_counters, config.precision, config.is_3d()) {
+        (true, Precision::F32, false) => compare::<f32, TwoD>(&config),
+        (true, Precision::F32, true) => compare::<f32, ThreeD>(&config),
+        (true, Precision::F64, false) => compare::<f64, TwoD>(&config),
+        (true, Precision::F64, true) => compare::<f64, ThreeD>(&config),
+        (false, Precision::F32, false) => run(ParticleSystem::<f32, TwoD>::with_config(config)),
+        (false, Precision::F32, true) => run(ParticleSystem::<f32, ThreeD>::with_config(config)),
+        (false, Precision::F64, false) => run(ParticleSystem::<f64, TwoD>::with_config(config)),
+        (false, Precision::F64, true) => run(ParticleSystem::<f64, ThreeD>::with_config(config))
     }
 }
 
-const PARTICLE_COUNT:usize = 100;
-const PARTICLE_BOUNDS:(i32, i32) = (10, 10);
-const PARTICLE_BOUNDS_HALF:(f32, f32) = (PARTICLE_BOUNDS.0 as f32 * 0.5, PARTICLE_BOUNDS.1 as f32 * 0.5);
-
-fn main() {
-    // Create particle system object
-    let mut particle_system = ParticleSystem::new();
-
+fn run<T: Scalar, D: Dimension>(mut particle_system: ParticleSystem<T, D>) {
     // Create particles & add to system
-    println!("Creating {} particles...", PARTICLE_COUNT);
-    for i in 0..PARTICLE_COUNT {
-        // Generate random positions within bounds
-        let x = random_range(-PARTICLE_BOUNDS_HALF.0..PARTICLE_BOUNDS_HALF.0);
-        let y = random_range(-PARTICLE_BOUNDS_HALF.1..PARTICLE_BOUNDS_HALF.1);
-
-        // Create instance with generated position
-        let particle = Particle::new(x, y, i);
-
-        // Announce position
-        // println!("Created particle {} with position ({}, {})", i, particle.x, particle.y);
+    particle_system.spawn_particles();
 
-        // Add instance to system
-        particle_system.particles.push(particle);
-    }
-
-    // Run loop
+    // Run loop, then check collisions. The total is accumulated in the system's collision counter.
     particle_system.move_particles_loop();
     particle_system.collide_particles();
+    println!("Atomic collision counter: {}", particle_system.collision_count());
 }
-fn thread_main(chunk: &mut [Particle], iteration_count: i32, thread_index: usize) {
-    let chunk_size = chunk.len();
-    let mut rng = rng();
-    for j in 0..iteration_count {
-        // println!("Thread {} moving particles...", thread_index);
-        for i in 0..chunk_size as usize {
-            // Generate vector to add and decide whether or not it should be negative (50% chance)
-            let mut xy = (rng.random::<f32>(), rng.random::<f32>());
-            let negative = (random_bool(0.5), random_bool(0.5));
-            if (negative.0) {
-                xy.0 = -xy.0;
-            }
-            if (negative.1) {
-                xy.1 = -xy.1;
-            }
-
-            // Apply vector to particle
-            chunk[i].x += xy.0;
-            chunk[i].y += xy.1;
 
-            // Restrict particle to within declared boundaries
-            if (chunk[i].x < -PARTICLE_BOUNDS_HALF.0){
-                chunk[i].x = -PARTICLE_BOUNDS_HALF.0;
-            }
-            else if (chunk[i].x > PARTICLE_BOUNDS_HALF.0) {
-                chunk[i].x = PARTICLE_BOUNDS_HALF.0;
-            }
-
-            if (chunk[i].y < -PARTICLE_BOUNDS_HALF.1){
-                chunk[i].y = -PARTICLE_BOUNDS_HALF.1;
-            }
-            else if (chunk[i].y > PARTICLE_BOUNDS_HALF.1) {
-                chunk[i].y = PARTICLE_BOUNDS_HALF.1;
-            }
-
-            // println!("Particle {} moved. New position: ({}, {})", i + (chunk_size * thread_index), chunk[i].x, chunk[i].y);
-        }
+fn compare<T: Scalar, D: Dimension>(config: &SimulationConfig) {
+    // Each run prints its own progress, so the table comes once they are all done
+    let reports = compare_counters::<T, D>(config);
+    println!("{:<16} {:>12} {:>10} {:>16}  result", "counter", "collisions", "ms", "collisions/s");
+    for report in reports {
+        let result = if report.correct() { "ok".to_string() } else { format!("expected {}", report.expected) };
+        println!("{:<16} {:>12} {:>10} {:>16.0}  {}", report.name, report.collisions, report.duration.as_millis(), report.throughput(), result);
     }
 }
-fn thread_collide(list: &Vec<Particle>, collision_count: &AtomicUsize, particles_per_thread: usize, thread_id: usize) {
-    let mut local_collision_count = 0;
-    let start_time = time::Instant::now();
-    let list_size = list.len();
-
-    let mut clone = list.clone();
-    let chunk = clone.chunks_mut(particles_per_thread).nth(thread_id).unwrap();
-
-    for i in 0..particles_per_thread - 1 {
-        let i_id = chunk[i].id;
-            for j in i_id + 1..list_size {
-                // Skip if comparing to self
-                if (i_id == j) {
-                    continue;
-                }
-
-                if (chunk[i].collide(&list[j])) {
-                    local_collision_count += 1;
-                    collision_count.fetch_add(1, Ordering::Relaxed);
-                    println!("Collision found between particles {} ({}, {}) and {} ({}, {})", i_id, chunk[i].x, chunk[i].y, j, list[j].x, list[j].y);
-                }
-        }
-    }
-
-    let duration = time::Instant::now().duration_since(start_time);
-    println!("Thread {} spent {} ms on collision checking, and detected {} total collisions", thread_id, duration.as_millis(), local_collision_count);
-}
\ No newline at end of file
@@ -0,0 +1,88 @@
+mod common;
+
+use common::system;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use rand::Rng;
+use particle_system::{broad_phase_for, max_contact_distance, thread_collide, BroadPhase, BroadPhaseKind, EventBuffer, Particle, ParticleRng, ShardedCounter, SimulationConfig, SweepAndPrune, TwoD};
+
+// One single-threaded collision pass, so the comparison shows the algorithmic speedup alone
+fn pass(broad_phase: &mut dyn BroadPhase, particles: &[Particle], config: &SimulationConfig) -> usize {
+    let contact_distance = max_contact_distance(particles);
+    broad_phase.update(particles.into(), contact_distance, &config.domain());
+    let counter = ShardedCounter::default();
+    broad_phase.tasks(particles.len(), 1).into_iter().map(|task| thread_collide(particles.into(), broad_phase, &counter, config, task, contact_distance, &mut EventBuffer::default())).sum()
+}
+
+// A fresh broad phase every iteration, so building the structure is included in the time
+fn bench_kinds(group: &mut criterion::BenchmarkGroup<criterion::measurement::WallTime>, kinds: &[BroadPhaseKind], particles: &[Particle], config: &SimulationConfig) {
+    for &kind in kinds {
+        let config = SimulationConfig { broad_phase: kind, ..config.clone() };
+        let name = broad_phase_for::<f32, TwoD>(&config).name();
+        group.bench_with_input(BenchmarkId::new(name, particles.len()), &config, |b, config| {
+            b.iter(|| pass(&mut *broad_phase_for(config), particles, config))
+        });
+    }
+}
+
+fn broad_phase(c: &mut Criterion) {
+    let mut group = c.benchmark_group("broad_phase");
+    group.sample_size(10);
+
+    for particle_count in [1_000, 4_000, 16_000, 100_000] {
+        let particle_system = system(particle_count);
+        // Brute force at 100k is ~5e9 pair tests per sample; leave it out
+        let kinds: &[BroadPhaseKind] = if particle_count <= 16_000 {
+            &[BroadPhaseKind::BruteForce, BroadPhaseKind::Grid, BroadPhaseKind::Quadtree, BroadPhaseKind::SweepAndPrune]
+        } else {
+            &[BroadPhaseKind::Grid, BroadPhaseKind::Quadtree, BroadPhaseKind::SweepAndPrune]
+        };
+        bench_kinds(&mut group, kinds, &particle_system.particles, &particle_system.config);
+    }
+    group.finish();
+}
+
+// Sweep-and-prune is built for repeated steps: compare re-sorting from scratch with repairing
+// the previous order after one movement step
+fn sweep_and_prune(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sweep_and_prune");
+    group.sample_size(10);
+
+    for particle_count in [4_000, 16_000, 100_000] {
+        let mut particle_system = system(particle_count);
+        let before = SweepAndPrune::build(&particle_system.particles);
+        particle_system.config.num_iterations = 1;
+        particle_system.move_particles_loop();
+        let particles = &particle_system.particles;
+        let config = &particle_system.config;
+
+        group.bench_function(BenchmarkId::new("rebuild", particle_count), |b| {
+            b.iter(|| pass(&mut SweepAndPrune::build::<[Particle]>(&[]), particles, config))
+        });
+        group.bench_function(BenchmarkId::new("insertion_sort_update", particle_count), |b| {
+            b.iter_batched_ref(|| before.clone(), |sweep_and_prune| pass(sweep_and_prune, particles, config), BatchSize::LargeInput)
+        });
+    }
+    group.finish();
+}
+
+// Most particles packed into a few small clumps inside a large, mostly empty domain: the grid's
+// cells get capped by its memory limit and fill up, while the quadtree only refines the clumps
+fn clustered(c: &mut Criterion) {
+    let mut group = c.benchmark_group("clustered");
+    group.sample_size(10);
+
+    for particle_count in [4_000, 16_000, 64_000] {
+        let side = (particle_count as f32).sqrt() * 4.0;
+        let config = SimulationConfig { particle_count, bounds: (side, side, 0.0), log_collisions: false, ..SimulationConfig::default() };
+        let particles: Vec<Particle> = (0..particle_count).map(|i| {
+            let mut rng = ParticleRng::for_spawn(600086, i);
+            let centre = ((i % 4) as f32 * side * 0.2 - side * 0.3, (i % 3) as f32 * side * 0.25 - side * 0.25);
+            Particle::new(centre.0 + rng.random_range(-2.0..2.0), centre.1 + rng.random_range(-2.0..2.0), i)
+        }).collect();
+        bench_kinds(&mut group, &[BroadPhaseKind::Grid, BroadPhaseKind::Quadtree], &particles, &config);
+    }
+    group.finish();
+}
+
+criterion_group!(benches, broad_phase, clustered, sweep_and_prune);
+criterion_main!(benches);
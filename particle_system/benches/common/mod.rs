@@ -0,0 +1,18 @@
+use particle_system::{ParticleSystem, Scalar, SimulationConfig};
+
+// The seeded system every benchmark runs on. Constant density (about one particle per unit area)
+// so the number of real collisions per particle stays the same as the system grows; only the
+// cost of finding them changes.
+pub fn system<T: Scalar>(particle_count: usize) -> ParticleSystem<T> {
+    let side = (particle_count as f32).sqrt();
+    let config = SimulationConfig {
+        particle_count,
+        bounds: (side, side, 0.0),
+        log_collisions: false,
+        seed: Some(600086),
+        ..SimulationConfig::default()
+    };
+    let mut particle_system = ParticleSystem::with_config(config);
+    particle_system.spawn_particles();
+    particle_system
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use common::system;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use particle_system::{broad_phase_for, max_contact_distance, motion_models_for, thread_collide, thread_main, BroadPhaseKind, EventBuffer, Scalar, ShardedCounter, TwoD};
+
+// One single-threaded movement step of every particle
+fn bench_move<T: Scalar>(group: &mut criterion::BenchmarkGroup<criterion::measurement::WallTime>, name: &str, particle_count: usize) {
+    let particle_system = system::<T>(particle_count);
+    let config = &particle_system.config;
+    let motion_models = motion_models_for::<T, TwoD>(&config.species);
+    group.bench_function(BenchmarkId::new(name, particle_count), |b| {
+        let mut particles = particle_system.particles.clone();
+        let mut step = 0;
+        b.iter(|| {
+            thread_main(&mut particles, config, &motion_models, 600086, step..step + 1, 0);
+            step += 1;
+        })
+    });
+}
+
+// One single-threaded grid collision pass, including the grid build
+fn bench_collide<T: Scalar>(group: &mut criterion::BenchmarkGroup<criterion::measurement::WallTime>, name: &str, particle_count: usize) {
+    let mut particle_system = system::<T>(particle_count);
+    particle_system.config.broad_phase = BroadPhaseKind::Grid;
+    let (particles, config) = (&particle_system.particles, &particle_system.config);
+    group.bench_function(BenchmarkId::new(name, particle_count), |b| {
+        b.iter(|| {
+            let mut broad_phase = broad_phase_for::<T, TwoD>(config);
+            let contact_distance = max_contact_distance(particles);
+            broad_phase.update(particles.into(), contact_distance, &config.domain());
+            let counter = ShardedCounter::default();
+            thread_collide(particles.into(), &*broad_phase, &counter, config, 0..particles.len(), contact_distance, &mut EventBuffer::default())
+        })
+    });
+}
+
+fn precision(c: &mut Criterion) {
+    let mut group = c.benchmark_group("precision_move");
+    group.sample_size(20);
+    for particle_count in [10_000, 100_000] {
+        bench_move::<f32>(&mut group, "f32", particle_count);
+        bench_move::<f64>(&mut group, "f64", particle_count);
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("precision_collide");
+    group.sample_size(10);
+    for particle_count in [10_000, 100_000] {
+        bench_collide::<f32>(&mut group, "f32", particle_count);
+        bench_collide::<f64>(&mut group, "f64", particle_count);
+    }
+    group.finish();
+}
+
+criterion_group!(benches, precision);
+criterion_main!(benches);
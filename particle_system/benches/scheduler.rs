@@ -0,0 +1,44 @@
+mod common;
+
+use std::thread;
+use common::system;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use particle_system::{broad_phase_for, collision_worker, max_contact_distance, BroadPhaseKind, EventBuffer, SchedulerKind, ShardedCounter, SimulationConfig, TaskQueues};
+
+// One collision pass on `threads` threads, with the tasks shared out by each scheduler. The pass
+// takes as long as its busiest thread, so any imbalance shows up as time
+fn scheduler(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scheduler");
+    group.sample_size(10);
+
+    let particle_system = system::<f32>(8_000);
+    let particles = &particle_system.particles;
+    let contact_distance = max_contact_distance(particles);
+    let threads = 8;
+    for broad_phase_kind in [BroadPhaseKind::BruteForce, BroadPhaseKind::Grid] {
+        let config = SimulationConfig { broad_phase: broad_phase_kind, ..particle_system.config.clone() };
+        let mut broad_phase = broad_phase_for(&config);
+        broad_phase.update(particles.into(), contact_distance, &config.domain());
+        let broad_phase = &*broad_phase;
+        for (name, kind) in [("static", SchedulerKind::Static), ("work_stealing", SchedulerKind::WorkStealing)] {
+            group.bench_function(BenchmarkId::new(format!("{}/{}", broad_phase.name(), name), threads), |b| {
+                b.iter(|| {
+                    let tasks = broad_phase.tasks(particles.len(), TaskQueues::task_count(threads, kind));
+                    let queues = TaskQueues::new(tasks, threads, kind);
+                    let counter = ShardedCounter::default();
+                    thread::scope(|scope| {
+                        for worker in 0..threads {
+                            let (queues, counter, config) = (&queues, &counter, &config);
+                            scope.spawn(move || collision_worker(particles.into(), broad_phase, counter, config, queues, contact_distance, &mut EventBuffer::new(worker, 0, false)));
+                        }
+                    });
+                    queues.stats()
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, scheduler);
+criterion_main!(benches);
@@ -0,0 +1,46 @@
+mod common;
+
+use common::system;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use particle_system::{broad_phase_for, max_contact_distance, thread_collide, BroadPhaseKind, EventBuffer, ParticleColumns, ParticleSlice, ShardedCounter, SimdLevel, SimulationConfig, TwoD};
+
+// One single-threaded collision pass over struct of arrays storage with each kernel the CPU has,
+// including the broad phase build
+fn bench_levels(group: &mut criterion::BenchmarkGroup<criterion::measurement::WallTime>, kind: BroadPhaseKind, particle_count: usize) {
+    let particle_system = system::<f32>(particle_count);
+    let config = SimulationConfig { broad_phase: kind, ..particle_system.config.clone() };
+    let mut columns = ParticleColumns::from_particles(&particle_system.particles);
+    let contact_distance = max_contact_distance(&particle_system.particles);
+    let name = broad_phase_for::<f32, TwoD>(&config).name();
+    for level in [SimdLevel::Scalar, SimdLevel::Sse2, SimdLevel::Avx2, SimdLevel::Avx512] {
+        if level.supported() != level {
+            continue;
+        }
+        columns.simd = level;
+        let slice = ParticleSlice::Columns(&columns);
+        group.bench_function(BenchmarkId::new(format!("{}/{:?}", name, level), particle_count), |b| {
+            b.iter(|| {
+                let mut broad_phase = broad_phase_for(&config);
+                broad_phase.update(slice, contact_distance, &config.domain());
+                let counter = ShardedCounter::default();
+                thread_collide(slice, &*broad_phase, &counter, &config, 0..slice.len(), contact_distance, &mut EventBuffer::default())
+            })
+        });
+    }
+}
+
+fn simd(c: &mut Criterion) {
+    let mut group = c.benchmark_group("simd_collide");
+    group.sample_size(10);
+    // Brute force at 100k is ~5e9 pair tests per sample; leave it out
+    for particle_count in [10_000, 20_000] {
+        bench_levels(&mut group, BroadPhaseKind::BruteForce, particle_count);
+    }
+    for particle_count in [10_000, 100_000] {
+        bench_levels(&mut group, BroadPhaseKind::Grid, particle_count);
+    }
+    group.finish();
+}
+
+criterion_group!(benches, simd);
+criterion_main!(benches);
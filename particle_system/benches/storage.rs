@@ -0,0 +1,74 @@
+mod common;
+
+use common::system;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use particle_system::{broad_phase_for, max_contact_distance, motion_models_for, thread_collide, thread_main, thread_main_columns, BroadPhaseKind, EventBuffer, ParticleColumns, ParticleSlice, ShardedCounter, SimulationConfig, TwoD};
+
+// One single-threaded collision pass over the same particles in each layout, including the broad phase build
+fn bench_collide(group: &mut criterion::BenchmarkGroup<criterion::measurement::WallTime>, kind: BroadPhaseKind, particle_count: usize) {
+    let particle_system = system::<f32>(particle_count);
+    let config = SimulationConfig { broad_phase: kind, ..particle_system.config.clone() };
+    let particles = &particle_system.particles;
+    let columns = ParticleColumns::from_particles(particles);
+    let contact_distance = max_contact_distance(particles);
+    let name = broad_phase_for::<f32, TwoD>(&config).name();
+    for (layout, slice) in [("structs", ParticleSlice::Structs(particles)), ("columns", ParticleSlice::Columns(&columns))] {
+        group.bench_function(BenchmarkId::new(format!("{}/{}", name, layout), particle_count), |b| {
+            b.iter(|| {
+                let mut broad_phase = broad_phase_for(&config);
+                broad_phase.update(slice, contact_distance, &config.domain());
+                let counter = ShardedCounter::default();
+                thread_collide(slice, &*broad_phase, &counter, &config, 0..slice.len(), contact_distance, &mut EventBuffer::default())
+            })
+        });
+    }
+}
+
+// One single-threaded movement step of every particle in each layout
+fn bench_move(group: &mut criterion::BenchmarkGroup<criterion::measurement::WallTime>, particle_count: usize) {
+    let particle_system = system::<f32>(particle_count);
+    let config = &particle_system.config;
+    let motion_models = motion_models_for(&config.species);
+    group.bench_function(BenchmarkId::new("structs", particle_count), |b| {
+        let mut particles = particle_system.particles.clone();
+        let mut step = 0;
+        b.iter(|| {
+            thread_main(&mut particles, config, &motion_models, 600086, step..step + 1, 0);
+            step += 1;
+        })
+    });
+    group.bench_function(BenchmarkId::new("columns", particle_count), |b| {
+        let mut columns = ParticleColumns::from_particles(&particle_system.particles);
+        let mut step = 0;
+        b.iter(|| {
+            let len = columns.len();
+            for chunk in columns.chunks_mut(len) {
+                thread_main_columns(chunk, config, &motion_models, 600086, step..step + 1, 0);
+            }
+            step += 1;
+        })
+    });
+}
+
+fn storage(c: &mut Criterion) {
+    let mut group = c.benchmark_group("storage_collide");
+    group.sample_size(10);
+    // Brute force at 100k is ~5e9 pair tests per sample; leave it out
+    bench_collide(&mut group, BroadPhaseKind::BruteForce, 10_000);
+    bench_collide(&mut group, BroadPhaseKind::BruteForce, 20_000);
+    for particle_count in [10_000, 100_000] {
+        bench_collide(&mut group, BroadPhaseKind::Grid, particle_count);
+        bench_collide(&mut group, BroadPhaseKind::SweepAndPrune, particle_count);
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("storage_move");
+    group.sample_size(20);
+    for particle_count in [10_000, 100_000] {
+        bench_move(&mut group, particle_count);
+    }
+    group.finish();
+}
+
+criterion_group!(benches, storage);
+criterion_main!(benches);
@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+use crate::{ColumnsMut, Dimension, Particle, Scalar};
+
+// What happens to a particle that moves past a wall, chosen per axis
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "snake_case")]
+pub enum Boundary {
+    // Stopped on the wall (the original lab behaviour; particles pile up on the edges)
+    #[default]
+    Clamp,
+    // Mirrored back inside, with that velocity component reversed
+    Reflect,
+    // Wrapped round to the opposite wall (toroidal); collisions use minimum-image distances
+    Periodic,
+    // Removed from the system & counted (see ParticleSystem::absorbed_count)
+    Absorb
+}
+
+// The box particles live in, centred on the origin, and how each axis treats its walls. A depth
+// (half.2) of zero is a flat, 2D domain: TwoD particles have no z at all, a ThreeD one is kept at
+// z = 0, & the z boundary is never used.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Domain<T: Scalar = f32> {
+    pub half: (T, T, T),
+    pub boundaries: (Boundary, Boundary, Boundary)
+}
+
+impl Boundary {
+    // Bring one coordinate (& its velocity component) back inside [-half, half]. Absorbing walls
+    // leave the particle where it is; Domain::is_absorbed picks it up afterwards.
+    fn apply<T: Scalar>(self, position: &mut T, velocity: &mut T, half: T) {
+        if (-half..=half).contains(position) {
+            return;
+        }
+        match self {
+            Boundary::Clamp => *position = position.clamp(-half, half),
+            Boundary::Reflect => {
+                // Closed form of bouncing between the walls, so even a jump of several domain
+                // widths lands in the right place. An odd number of bounces reverses the velocity.
+                let two = T::from_f32(2.0);
+                let width = two * half;
+                let bounces = ((*position + half) / width).floor();
+                let t = (*position + half).rem_euclid(two * width);
+                *position = if t <= width { t - half } else { two * width - t - half };
+                if bounces.rem_euclid(two) == T::ONE {
+                    *velocity = -*velocity;
+                }
+            }
+            Boundary::Periodic => *position = (*position + half).rem_euclid(T::from_f32(2.0) * half) - half,
+            Boundary::Absorb => {}
+        }
+    }
+}
+
+impl<T: Scalar> Domain<T> {
+    pub fn is_3d(&self) -> bool {
+        self.half.2 > T::ZERO
+    }
+    pub fn dimensions(&self) -> usize {
+        if self.is_3d() { 3 } else { 2 }
+    }
+    // z never counts as periodic in a flat domain
+    pub fn periodic(&self) -> (bool, bool, bool) {
+        (
+            self.boundaries.0 == Boundary::Periodic,
+            self.boundaries.1 == Boundary::Periodic,
+            self.is_3d() && self.boundaries.2 == Boundary::Periodic
+        )
+    }
+    pub fn any_periodic(&self) -> bool {
+        let periodic = self.periodic();
+        periodic.0 || periodic.1 || periodic.2
+    }
+    pub fn absorbs(&self) -> bool {
+        self.boundaries.0 == Boundary::Absorb || self.boundaries.1 == Boundary::Absorb || (self.is_3d() && self.boundaries.2 == Boundary::Absorb)
+    }
+
+    // Apply each axis' boundary after a move
+    pub fn apply<D: Dimension>(&self, particle: &mut Particle<T, D>) {
+        self.boundaries.0.apply(&mut particle.x, &mut particle.vx, self.half.0);
+        self.boundaries.1.apply(&mut particle.y, &mut particle.vy, self.half.1);
+        if let (Some(z), Some(vz)) = (D::depth_mut(&mut particle.z), D::depth_mut(&mut particle.vz)) {
+            if self.is_3d() {
+                self.boundaries.2.apply(z, vz, self.half.2);
+            } else {
+                // A 3D particle in a flat domain: whatever moved it in z is dropped
+                (*z, *vz) = (T::ZERO, T::ZERO);
+            }
+        }
+    }
+
+    // As apply, for every particle in a chunk of ParticleColumns: one whole column at a time
+    pub fn apply_columns<D: Dimension>(&self, chunk: &mut ColumnsMut<'_, T, D>) {
+        for (x, vx) in chunk.x.iter_mut().zip(chunk.vx.iter_mut()) {
+            self.boundaries.0.apply(x, vx, self.half.0);
+        }
+        for (y, vy) in chunk.y.iter_mut().zip(chunk.vy.iter_mut()) {
+            self.boundaries.1.apply(y, vy, self.half.1);
+        }
+        if let (Some(z), Some(vz)) = (D::depths_mut(chunk.z), D::depths_mut(chunk.vz)) {
+            if self.is_3d() {
+                for (z, vz) in z.iter_mut().zip(vz.iter_mut()) {
+                    self.boundaries.2.apply(z, vz, self.half.2);
+                }
+            } else {
+                z.fill(T::ZERO);
+                vz.fill(T::ZERO);
+            }
+        }
+    }
+
+    // Left through an absorbing wall, so due to be removed
+    pub fn is_absorbed<D: Dimension>(&self, particle: &Particle<T, D>) -> bool {
+        (self.boundaries.0 == Boundary::Absorb && !(-self.half.0..=self.half.0).contains(&particle.x))
+            || (self.boundaries.1 == Boundary::Absorb && !(-self.half.1..=self.half.1).contains(&particle.y))
+            || (self.is_3d() && self.boundaries.2 == Boundary::Absorb && !(-self.half.2..=self.half.2).contains(&particle.z()))
+    }
+
+    // Vector from `a` to `b`. On periodic axes this is the minimum image: the shortest way round,
+    // possibly across the seam.
+    pub fn separation<D: Dimension>(&self, a: &Particle<T, D>, b: &Particle<T, D>) -> (T, T, T) {
+        self.separation_between(a.position(), b.position())
+    }
+    // As separation, between two positions
+    pub fn separation_between(&self, a: (T, T, T), b: (T, T, T)) -> (T, T, T) {
+        let (mut x, mut y, mut z) = (b.0 - a.0, b.1 - a.1, b.2 - a.2);
+        let periodic = self.periodic();
+        if periodic.0 {
+            let width = T::from_f32(2.0) * self.half.0;
+            x -= width * (x / width).round();
+        }
+        if periodic.1 {
+            let height = T::from_f32(2.0) * self.half.1;
+            y -= height * (y / height).round();
+        }
+        if periodic.2 {
+            let depth = T::from_f32(2.0) * self.half.2;
+            z -= depth * (z / depth).round();
+        }
+        (x, y, z)
+    }
+}
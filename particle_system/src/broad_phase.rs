@@ -0,0 +1,200 @@
+use std::ops::Range;
+use crate::partition::{chunk_ranges, partition_pairs};
+use crate::soa::per_layout;
+use crate::{Aabb, BroadPhaseKind, Dimension, Domain, Particle, ParticleSlice, ParticleStore, Quadtree, Scalar, SimulationConfig, SweepAndPrune, TwoD, UniformGrid};
+
+// A strategy for finding candidate pairs before the exact Particle::collide test.
+//
+// A collision pass calls `update` once, serially, with the current positions, then splits the
+// work into `tasks` and hands one task to each collision thread. Across all tasks every pair
+// (i, j) must be produced at most once, with i < j, and every colliding pair must be produced.
+// `contact_distance` is the largest distance at which any two particles can collide, i.e. twice
+// the largest radius present (see max_contact_distance); structures size themselves from it.
+// On periodic axes of `domain`, pairs across the seam must be produced too, and the exact test
+// is Particle::collide_in with minimum-image distances.
+// tests/broad_phase_conformance.rs checks exactly that against brute force.
+// Generic over the particle precision & dimension, like ParticleSystem. Particles come in any
+// layout (see ParticleSlice); implementations match on it once per call with per_layout!.
+pub trait BroadPhase<T: Scalar = f32, D: Dimension = TwoD>: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    // Bring any internal structure up to date with the current positions
+    fn update(&mut self, particles: ParticleSlice<'_, T, D>, contact_distance: T, domain: &Domain<T>);
+
+    // Split the pass into at most `thread_count` independent tasks
+    fn tasks(&self, particle_count: usize, thread_count: usize) -> Vec<Range<usize>>;
+
+    // Call `f(i, j)` for every candidate pair belonging to `task`
+    fn for_each_candidate(&self, particles: ParticleSlice<'_, T, D>, task: Range<usize>, contact_distance: T, domain: &Domain<T>, f: &mut dyn FnMut(usize, usize));
+
+    // Test every candidate pair of `task`, calling `on_collision` for each colliding pair, and
+    // return the number of collisions. Implementations override this with a direct loop so the
+    // hot path doesn't pay for a dynamic call per candidate.
+    fn collide_task(&self, particles: ParticleSlice<'_, T, D>, task: Range<usize>, contact_distance: T, domain: &Domain<T>, on_collision: &mut dyn FnMut(usize, usize)) -> usize {
+        let mut collisions = 0;
+        self.for_each_candidate(particles, task, contact_distance, domain, &mut |i, j| {
+            if per_layout!(particles, p => p.collide_in(i, j, domain)) {
+                on_collision(i, j);
+                collisions += 1;
+            }
+        });
+        collisions
+    }
+}
+
+// Build the broad phase selected by `config.broad_phase`. Structures start empty & are sized by
+// the first `update`, from the particles actually present. The quadtree & sweep and prune only
+// prune on x & y, so they are refused in 3D, as SimulationConfig::validate does.
+pub fn broad_phase_for<T: Scalar, D: Dimension>(config: &SimulationConfig) -> Box<dyn BroadPhase<T, D>> {
+    let none: &[Particle<T, D>] = &[];
+    let flat_only = matches!(config.broad_phase, BroadPhaseKind::Quadtree | BroadPhaseKind::SweepAndPrune);
+    assert!(!(flat_only && (D::AXES == 3 || config.is_3d())), "{:?} can't be used in a 3D domain", config.broad_phase);
+    match config.broad_phase {
+        BroadPhaseKind::BruteForce => Box::new(BruteForce),
+        BroadPhaseKind::Grid => Box::new(UniformGrid::build(none, T::ZERO, &config.domain())),
+        BroadPhaseKind::Quadtree => Box::new(Quadtree::build(none, Aabb::of_domain(&config.domain()))),
+        BroadPhaseKind::SweepAndPrune => Box::new(SweepAndPrune::build(none))
+    }
+}
+
+// Every pair, O(n^2). Tasks are row ranges split by pair count (see partition.rs).
+#[derive(Debug, Default, Copy, Clone)]
+pub struct BruteForce;
+
+impl<T: Scalar, D: Dimension> BroadPhase<T, D> for BruteForce {
+    fn name(&self) -> &'static str {
+        "brute_force"
+    }
+    fn update(&mut self, _particles: ParticleSlice<'_, T, D>, _contact_distance: T, _domain: &Domain<T>) {}
+    fn tasks(&self, particle_count: usize, thread_count: usize) -> Vec<Range<usize>> {
+        partition_pairs(particle_count, thread_count)
+    }
+    fn for_each_candidate(&self, particles: ParticleSlice<'_, T, D>, rows: Range<usize>, _contact_distance: T, _domain: &Domain<T>, f: &mut dyn FnMut(usize, usize)) {
+        for i in rows {
+            for j in i + 1..particles.len() {
+                f(i, j);
+            }
+        }
+    }
+    fn collide_task(&self, particles: ParticleSlice<'_, T, D>, rows: Range<usize>, _contact_distance: T, domain: &Domain<T>, on_collision: &mut dyn FnMut(usize, usize)) -> usize {
+        per_layout!(particles, p => BruteForce::collide_rows(p, rows, domain, on_collision))
+    }
+}
+
+impl BruteForce {
+    fn collide_rows<T: Scalar, P: ParticleStore<T> + ?Sized>(particles: &P, rows: Range<usize>, domain: &Domain<T>, on_collision: &mut dyn FnMut(usize, usize)) -> usize {
+        let mut collisions = 0;
+        for i in rows {
+            particles.for_each_contact(i, i + 1..particles.len(), domain, |j| {
+                on_collision(i, j);
+                collisions += 1;
+            });
+        }
+        collisions
+    }
+}
+
+// Grid tasks are particle ranges; every particle has a similar number of neighbours
+impl<T: Scalar, D: Dimension> BroadPhase<T, D> for UniformGrid<T> {
+    fn name(&self) -> &'static str {
+        "grid"
+    }
+    fn update(&mut self, particles: ParticleSlice<'_, T, D>, contact_distance: T, domain: &Domain<T>) {
+        *self = per_layout!(particles, p => UniformGrid::build(p, contact_distance, domain));
+    }
+    fn tasks(&self, particle_count: usize, thread_count: usize) -> Vec<Range<usize>> {
+        chunk_ranges(particle_count, thread_count)
+    }
+    fn for_each_candidate(&self, _particles: ParticleSlice<'_, T, D>, rows: Range<usize>, _contact_distance: T, _domain: &Domain<T>, f: &mut dyn FnMut(usize, usize)) {
+        for i in rows {
+            for j in self.candidates(i) {
+                f(i, j);
+            }
+        }
+    }
+    fn collide_task(&self, particles: ParticleSlice<'_, T, D>, rows: Range<usize>, _contact_distance: T, domain: &Domain<T>, on_collision: &mut dyn FnMut(usize, usize)) -> usize {
+        per_layout!(particles, p => {
+            let mut collisions = 0;
+            for i in rows {
+                for run in self.neighbour_runs(i) {
+                    p.for_each_later_contact(i, run, domain, |j| {
+                        on_collision(i, j);
+                        collisions += 1;
+                    });
+                }
+            }
+            collisions
+        })
+    }
+}
+
+impl<T: Scalar, D: Dimension> BroadPhase<T, D> for Quadtree<T> {
+    fn name(&self) -> &'static str {
+        "quadtree"
+    }
+    fn update(&mut self, particles: ParticleSlice<'_, T, D>, _contact_distance: T, _domain: &Domain<T>) {
+        per_layout!(particles, p => Quadtree::update(self, p));
+    }
+    fn tasks(&self, particle_count: usize, thread_count: usize) -> Vec<Range<usize>> {
+        chunk_ranges(particle_count, thread_count)
+    }
+    fn for_each_candidate(&self, particles: ParticleSlice<'_, T, D>, rows: Range<usize>, contact_distance: T, domain: &Domain<T>, f: &mut dyn FnMut(usize, usize)) {
+        per_layout!(particles, p => {
+            for i in rows {
+                Quadtree::for_each_candidate(self, i, p, contact_distance, domain, |j| f(i, j));
+            }
+        })
+    }
+    fn collide_task(&self, particles: ParticleSlice<'_, T, D>, rows: Range<usize>, contact_distance: T, domain: &Domain<T>, on_collision: &mut dyn FnMut(usize, usize)) -> usize {
+        per_layout!(particles, p => {
+            let mut collisions = 0;
+            for i in rows {
+                Quadtree::for_each_candidate(self, i, p, contact_distance, domain, |j| {
+                    if p.collide_in(i, j, domain) {
+                        on_collision(i, j);
+                        collisions += 1;
+                    }
+                });
+            }
+            collisions
+        })
+    }
+}
+
+// The sweep is serial and happens in `update`; tasks are ranges of the resulting pair list.
+// Intervals come from each particle's own radius; `contact_distance` only decides which particles
+// need ghost intervals when x is periodic.
+impl<T: Scalar, D: Dimension> BroadPhase<T, D> for SweepAndPrune<T> {
+    fn name(&self) -> &'static str {
+        "sweep_and_prune"
+    }
+    fn update(&mut self, particles: ParticleSlice<'_, T, D>, contact_distance: T, domain: &Domain<T>) {
+        if domain.periodic().0 {
+            *self = per_layout!(particles, p => SweepAndPrune::build_periodic(p, domain.half.0, contact_distance));
+        } else {
+            per_layout!(particles, p => SweepAndPrune::update(self, p));
+        }
+        self.sweep();
+    }
+    fn tasks(&self, _particle_count: usize, thread_count: usize) -> Vec<Range<usize>> {
+        chunk_ranges(self.pairs().len(), thread_count)
+    }
+    fn for_each_candidate(&self, _particles: ParticleSlice<'_, T, D>, range: Range<usize>, _contact_distance: T, _domain: &Domain<T>, f: &mut dyn FnMut(usize, usize)) {
+        for &(i, j) in &self.pairs()[range] {
+            f(i as usize, j as usize);
+        }
+    }
+    fn collide_task(&self, particles: ParticleSlice<'_, T, D>, range: Range<usize>, _contact_distance: T, domain: &Domain<T>, on_collision: &mut dyn FnMut(usize, usize)) -> usize {
+        per_layout!(particles, p => {
+            let mut collisions = 0;
+            for &(i, j) in &self.pairs()[range] {
+                let (i, j) = (i as usize, j as usize);
+                if p.collide_in(i, j, domain) {
+                    on_collision(i, j);
+                    collisions += 1;
+                }
+            }
+            collisions
+        })
+    }
+}
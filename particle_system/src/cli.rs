@@ -0,0 +1,157 @@
+use clap::error::ErrorKind;
+use clap::{CommandFactory, FromArgMatches, Parser};
+use crate::dynamics::DEFAULT_DT;
+use crate::{Boundary, BroadPhaseKind, CounterKind, Dynamics, Integrator, Precision, RadiusDistribution, SchedulerKind, SimdLevel, SimulationConfig, StorageLayout};
+
+// The binaries' shared command line, only built with the `cli` feature so the library itself
+// doesn't need clap. Every option is optional; apply fills in the rest from a default config.
+
+impl SimulationConfig {
+    // Parse the process arguments, using `defaults` for anything not given on the command line.
+    // Values that can't be run are reported as a usage error & the process exits
+    pub fn from_args(defaults: SimulationConfig) -> SimulationConfig {
+        // Parsed through `command` so errors are reported under the binary's own name
+        let mut command = Cli::command();
+        let cli = Cli::from_arg_matches(&command.get_matches_mut()).unwrap_or_else(|error| error.exit());
+        let config = cli.apply(defaults);
+        config.validate().unwrap_or_else(|error| command.error(ErrorKind::ValueValidation, error).exit());
+        config
+    }
+}
+
+#[derive(Debug, Parser)]
+#[command(about = "Multi-threaded colliding particle simulation")]
+pub struct Cli {
+    /// Number of particles to create
+    #[arg(long, short = 'n')]
+    pub particles: Option<usize>,
+    /// Width of the simulation domain (centred on the origin)
+    #[arg(long)]
+    pub bounds_x: Option<f32>,
+    /// Height of the simulation domain (centred on the origin)
+    #[arg(long)]
+    pub bounds_y: Option<f32>,
+    /// Depth of the simulation domain; anything above 0 makes the simulation 3D
+    #[arg(long)]
+    pub bounds_z: Option<f32>,
+    /// Boundary on every axis
+    #[arg(long, value_enum)]
+    pub boundary: Option<Boundary>,
+    /// Boundary on the x axis (overrides --boundary)
+    #[arg(long, value_enum)]
+    pub boundary_x: Option<Boundary>,
+    /// Boundary on the y axis (overrides --boundary)
+    #[arg(long, value_enum)]
+    pub boundary_y: Option<Boundary>,
+    /// Boundary on the z axis (overrides --boundary)
+    #[arg(long, value_enum)]
+    pub boundary_z: Option<Boundary>,
+    /// Particle radius; two particles collide when closer than the sum of their radii
+    #[arg(long, short = 'r')]
+    pub radius: Option<f32>,
+    /// Draw radii uniformly between --radius and this, instead of one fixed size
+    #[arg(long, requires = "radius")]
+    pub radius_max: Option<f32>,
+    /// Maximum initial particle speed (random direction)
+    #[arg(long)]
+    pub initial_speed: Option<f32>,
+    /// Smallest particle mass
+    #[arg(long)]
+    pub mass_min: Option<f32>,
+    /// Largest particle mass
+    #[arg(long)]
+    pub mass_max: Option<f32>,
+    /// Integrate velocity & force with this scheme instead of random walking
+    #[arg(long, value_enum)]
+    pub integrator: Option<Integrator>,
+    /// Time step for --integrator
+    #[arg(long, requires = "integrator")]
+    pub dt: Option<f32>,
+    /// Resolve collisions with elastic impulses
+    #[arg(long)]
+    pub collision_response: Option<bool>,
+    /// Number of movement iterations to run
+    #[arg(long, short = 'i')]
+    pub iterations: Option<usize>,
+    /// Total number of worker threads
+    #[arg(long, short = 't', value_parser = clap::value_parser!(u32).range(1..))]
+    pub threads: Option<u32>,
+    /// Threads reserved for movement when moving & colliding together
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub movement_threads: Option<u32>,
+    /// Steps movement may run ahead of collision checking when pipelined
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub pipeline_depth: Option<u32>,
+    /// Print every detected collision
+    #[arg(long)]
+    pub log_collisions: Option<bool>,
+    /// Keep a record of every collision (ids, positions, step & thread)
+    #[arg(long)]
+    pub record_collisions: Option<bool>,
+    /// How candidate collision pairs are found
+    #[arg(long, value_enum)]
+    pub broad_phase: Option<BroadPhaseKind>,
+    /// Float precision of particle positions & velocities
+    #[arg(long, value_enum)]
+    pub precision: Option<Precision>,
+    /// Memory layout of the particles while running
+    #[arg(long, value_enum)]
+    pub storage: Option<StorageLayout>,
+    /// Widest SIMD collision kernel to use (capped at what the CPU supports). The kernels only run
+    /// over struct-of-arrays storage in f32 without periodic boundaries, so any level but scalar
+    /// selects --storage struct-of-arrays unless another layout is given
+    #[arg(long, value_enum)]
+    pub simd: Option<SimdLevel>,
+    /// How collision threads count collisions
+    #[arg(long, value_enum)]
+    pub counter: Option<CounterKind>,
+    /// How collision tasks are shared between threads
+    #[arg(long, value_enum)]
+    pub scheduler: Option<SchedulerKind>,
+    /// Seed for the random number generators
+    #[arg(long, short = 's')]
+    pub seed: Option<u64>
+}
+impl Cli {
+    pub fn apply(self, defaults: SimulationConfig) -> SimulationConfig {
+        SimulationConfig {
+            particle_count: self.particles.unwrap_or(defaults.particle_count),
+            bounds: (
+                self.bounds_x.unwrap_or(defaults.bounds.0),
+                self.bounds_y.unwrap_or(defaults.bounds.1),
+                self.bounds_z.unwrap_or(defaults.bounds.2)
+            ),
+            boundaries: (
+                self.boundary_x.or(self.boundary).unwrap_or(defaults.boundaries.0),
+                self.boundary_y.or(self.boundary).unwrap_or(defaults.boundaries.1),
+                self.boundary_z.or(self.boundary).unwrap_or(defaults.boundaries.2)
+            ),
+            particle_radius: match (self.radius, self.radius_max) {
+                (Some(min), Some(max)) => RadiusDistribution::Uniform { min, max },
+                (Some(radius), None) => RadiusDistribution::Fixed(radius),
+                _ => defaults.particle_radius
+            },
+            initial_speed: self.initial_speed.unwrap_or(defaults.initial_speed),
+            mass_range: (self.mass_min.unwrap_or(defaults.mass_range.0), self.mass_max.unwrap_or(defaults.mass_range.1)),
+            species: defaults.species,
+            dynamics: match self.integrator {
+                Some(integrator) => Dynamics::Integrated { integrator, dt: self.dt.unwrap_or(DEFAULT_DT), force: defaults.dynamics.force() },
+                None => defaults.dynamics
+            },
+            collision_response: self.collision_response.unwrap_or(defaults.collision_response),
+            num_iterations: self.iterations.unwrap_or(defaults.num_iterations),
+            thread_count: self.threads.map_or(defaults.thread_count, |t| t as usize),
+            movement_thread_count: self.movement_threads.map_or(defaults.movement_thread_count, |t| t as usize),
+            pipeline_depth: self.pipeline_depth.map_or(defaults.pipeline_depth, |depth| depth as usize),
+            log_collisions: self.log_collisions.unwrap_or(defaults.log_collisions),
+            record_collisions: self.record_collisions.unwrap_or(defaults.record_collisions),
+            broad_phase: self.broad_phase.unwrap_or(defaults.broad_phase),
+            precision: self.precision.unwrap_or(defaults.precision),
+            storage: self.storage.or(StorageLayout::implied_by(self.simd)).unwrap_or(defaults.storage),
+            simd: self.simd.unwrap_or(defaults.simd),
+            counter: self.counter.unwrap_or(defaults.counter),
+            scheduler: self.scheduler.unwrap_or(defaults.scheduler),
+            seed: self.seed.or(defaults.seed)
+        }
+    }
+}
@@ -0,0 +1,306 @@
+use rand::Rng;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use thiserror::Error;
+use crate::particle::DEFAULT_RADIUS;
+use crate::rng::standard_normal;
+use crate::{Boundary, Domain, Dynamics, Precision, Scalar, SimdLevel, Species};
+
+// Every tunable value of a run. Previously these were literals spread across main and the
+// ParticleSystem methods; now a single value is passed into the ParticleSystem.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulationConfig {
+    pub particle_count: usize,
+    // Width, height & depth of the domain. A depth of 0 is a flat, 2D domain (the original lab setup)
+    pub bounds: (f32, f32, f32),
+    // What happens at the walls, per axis (x, y, z)
+    pub boundaries: (Boundary, Boundary, Boundary),
+    // Spawned particles draw their radius from this; two collide when closer than the sum of their radii
+    pub particle_radius: RadiusDistribution,
+    // Spawned particles get a random direction & a speed up to this (0 keeps them stationary)
+    pub initial_speed: f32,
+    // Spawned particles get a mass drawn uniformly from min..=max
+    pub mass_range: (f32, f32),
+    // Kinds of particle & how each moves; a single species gives every particle the same motion model
+    pub species: Vec<Species>,
+    // Random walk (the default), or velocity & force integrated with a fixed time step
+    pub dynamics: Dynamics,
+    // Bounce colliding particles off each other (elastic impulses), instead of only counting them
+    pub collision_response: bool,
+    pub num_iterations: usize,
+    pub thread_count: usize,
+    // Only used by move_and_collide_particles; the remaining threads check collisions
+    pub movement_thread_count: usize,
+    // Most finished steps pipelined_move_and_collide_particles lets movement get ahead of collision checking by
+    pub pipeline_depth: usize,
+    pub log_collisions: bool,
+    // Keep a CollisionEvent for every collision found (see ParticleSystem::collision_events)
+    pub record_collisions: bool,
+    pub broad_phase: BroadPhaseKind,
+    // Float type particles are stored & moved in. Only read by whoever picks the ParticleSystem's
+    // type (the binaries & Scenario::run); the config itself is always f32
+    pub precision: Precision,
+    // How the ParticleSystem lays particles out in memory while it runs
+    pub storage: StorageLayout,
+    // Widest contact test kernel to use with StructOfArrays storage (see simd.rs). Defaults to the
+    // best the CPU has; results are the same at every level. Other layouts, f64 & domains with a
+    // periodic axis always use the scalar loops
+    pub simd: SimdLevel,
+    // How collision threads count what they find (see CollisionCounter). Totals are the same with all of them
+    pub counter: CounterKind,
+    // How each collision pass' tasks are shared out between the collision threads (scheduler.rs)
+    pub scheduler: SchedulerKind,
+    // Fixed seed for reproducible runs; None draws fresh entropy every run
+    pub seed: Option<u64>
+}
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        SimulationConfig {
+            particle_count: 100,
+            bounds: (10.0, 10.0, 0.0),
+            boundaries: (Boundary::Clamp, Boundary::Clamp, Boundary::Clamp),
+            particle_radius: RadiusDistribution::Fixed(DEFAULT_RADIUS),
+            initial_speed: 0.0,
+            mass_range: (1.0, 1.0),
+            species: vec![Species::default()],
+            dynamics: Dynamics::RandomWalk,
+            collision_response: false,
+            num_iterations: 20000,
+            thread_count: 12,
+            movement_thread_count: 2,
+            pipeline_depth: 2,
+            log_collisions: true,
+            record_collisions: false,
+            broad_phase: BroadPhaseKind::BruteForce,
+            precision: Precision::F32,
+            storage: StorageLayout::ArrayOfStructs,
+            simd: SimdLevel::detect(),
+            counter: CounterKind::Sharded,
+            scheduler: SchedulerKind::WorkStealing,
+            seed: None
+        }
+    }
+}
+// How candidate pairs are found before the exact Particle::collide test
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "snake_case")]
+pub enum BroadPhaseKind {
+    // Test every pair, O(n^2)
+    #[default]
+    BruteForce,
+    // Only test pairs in neighbouring cells of a uniform grid (grid.rs)
+    Grid,
+    // Only test pairs found by a region query on an adaptive quadtree (quadtree.rs). 2D only
+    Quadtree,
+    // Only test pairs whose x intervals overlap in a sorted endpoint list (sweep_and_prune.rs). 2D only
+    SweepAndPrune
+}
+
+// Memory layout used while a run is in progress. Either way `ParticleSystem::particles` holds the
+// result afterwards; only the speed differs.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "snake_case")]
+pub enum StorageLayout {
+    // A Vec of whole Particles, worked on in place
+    #[default]
+    ArrayOfStructs,
+    // One contiguous column per field (soa.rs), so collision checks only stream the coordinates & radii
+    StructOfArrays,
+    // Particles as ArrayOfStructs, with their positions published to atomics behind per-particle
+    // sequence counters (seqlock.rs). Collision threads read those while movement threads write,
+    // so move_and_collide_particles overlaps the two with no copy in between (checking every pair
+    // while it does, see ParticleSystem::double_buffered_pass)
+    SharedAtomic
+}
+
+impl StorageLayout {
+    // The layout to use when only a SIMD level was asked for. The vector kernels only run over
+    // StructOfArrays columns, so asking for one (anything but scalar) without a layout picks that
+    pub fn implied_by(simd: Option<SimdLevel>) -> Option<StorageLayout> {
+        simd.filter(|&level| level != SimdLevel::Scalar).map(|_| StorageLayout::StructOfArrays)
+    }
+}
+
+// Which CollisionCounter backend the collision threads count into (counter.rs)
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "snake_case")]
+pub enum CounterKind {
+    // One Mutex<usize>, locked for every collision
+    Mutex,
+    // One AtomicUsize, added to for every collision with Relaxed, AcqRel or SeqCst ordering
+    AtomicRelaxed,
+    AtomicAcqRel,
+    AtomicSeqCst,
+    // A cache-padded shard per thread, added to for every collision & summed after each pass
+    #[default]
+    Sharded,
+    // A count local to each thread, added to the total once when its task is done
+    PerThread
+}
+
+impl CounterKind {
+    // Every backend, in the order compare_counters runs them
+    pub const ALL: [CounterKind; 6] = [CounterKind::Mutex, CounterKind::AtomicRelaxed, CounterKind::AtomicAcqRel, CounterKind::AtomicSeqCst, CounterKind::Sharded, CounterKind::PerThread];
+}
+
+// How a collision pass is split between its threads (scheduler.rs)
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "snake_case")]
+pub enum SchedulerKind {
+    // One task per thread, as the broad phase splits the pass, & each thread runs only its own
+    Static,
+    // TASKS_PER_THREAD smaller tasks per thread; a thread that runs out of its own takes from the
+    // back of another's queue, so none sits idle while there is work left
+    #[default]
+    WorkStealing
+}
+
+// How particle sizes are drawn at spawn. In a scenario file a plain number is a fixed radius, and
+// a table picks the distribution by its fields, e.g. `radius = { min = 0.05, max = 0.2 }`.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RadiusDistribution {
+    // Every particle the same size
+    Fixed(f32),
+    // Uniform between min & max (inclusive)
+    Uniform { min: f32, max: f32 },
+    // Log-normal: ln(radius) is normal with mean ln(median) & standard deviation sigma. The usual
+    // model for polydisperse powders & colloids; always positive, with a long tail of large particles
+    LogNormal { median: f32, sigma: f32 },
+    // A two-size mixture, with `large_fraction` of the particles (on average) being the large ones
+    Bidisperse { small: f32, large: f32, large_fraction: f32 }
+}
+
+impl RadiusDistribution {
+    pub fn sample(&self, rng: &mut impl Rng) -> f32 {
+        match *self {
+            RadiusDistribution::Fixed(radius) => radius,
+            RadiusDistribution::Uniform { min, max } => if max > min { rng.random_range(min..=max) } else { min },
+            RadiusDistribution::LogNormal { median, sigma } => {
+                median * (sigma * standard_normal(rng)).exp()
+            }
+            RadiusDistribution::Bidisperse { small, large, large_fraction } => {
+                if rng.random_bool(large_fraction.clamp(0.0, 1.0) as f64) { large } else { small }
+            }
+        }
+    }
+    // Describes the first problem, if any, for error messages
+    pub fn check(&self) -> Result<(), String> {
+        let positive = |value: f32| value.is_finite() && value > 0.0;
+        match *self {
+            RadiusDistribution::Fixed(radius) if !positive(radius) => Err(format!("must be a positive number, got {}", radius)),
+            RadiusDistribution::Uniform { min, max } if !(positive(min) && positive(max) && min <= max) => {
+                Err(format!("min & max must be positive with min <= max, got {}..{}", min, max))
+            }
+            RadiusDistribution::LogNormal { median, sigma } if !(positive(median) && sigma.is_finite() && sigma >= 0.0) => {
+                Err(format!("median must be positive & sigma non-negative, got median {} sigma {}", median, sigma))
+            }
+            RadiusDistribution::Bidisperse { small, large, large_fraction } if !(positive(small) && positive(large) && (0.0..=1.0).contains(&large_fraction)) => {
+                Err(format!("sizes must be positive & large_fraction within 0..1, got {}, {} & {}", small, large, large_fraction))
+            }
+            _ => Ok(())
+        }
+    }
+}
+
+impl SimulationConfig {
+    pub fn bounds_half(&self) -> (f32, f32, f32) {
+        (self.bounds.0 * 0.5, self.bounds.1 * 0.5, self.bounds.2 * 0.5)
+    }
+    pub fn is_3d(&self) -> bool {
+        self.bounds.2 > 0.0
+    }
+    pub fn domain<T: Scalar>(&self) -> Domain<T> {
+        let half = self.bounds_half();
+        Domain { half: (T::from_f32(half.0), T::from_f32(half.1), T::from_f32(half.2)), boundaries: self.boundaries }
+    }
+    // Always leave at least one thread for collisions, even if movement asked for them all
+    pub fn collision_thread_count(&self) -> usize {
+        self.thread_count.saturating_sub(self.movement_thread_count).max(1)
+    }
+
+    // The first value a run can't be started with, if any. Both the command line & scenario files
+    // are checked with this before anything is spawned
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        fn invalid(field: &'static str, reason: impl Into<String>) -> Result<(), ConfigError> {
+            Err(ConfigError { field, reason: reason.into() })
+        }
+        if self.particle_count == 0 {
+            return invalid("particle_count", "must be at least 1");
+        }
+        let (x, y, z) = self.bounds;
+        if !(x.is_finite() && x > 0.0) {
+            return invalid("bounds.x", format!("must be a positive number, got {}", x));
+        }
+        if !(y.is_finite() && y > 0.0) {
+            return invalid("bounds.y", format!("must be a positive number, got {}", y));
+        }
+        if !(z.is_finite() && z >= 0.0) {
+            return invalid("bounds.z", format!("must be a non-negative number, got {}", z));
+        }
+        // Both only prune on x (& y), so with depth they'd test close to every pair
+        if z > 0.0 && matches!(self.broad_phase, BroadPhaseKind::Quadtree | BroadPhaseKind::SweepAndPrune) {
+            return invalid("broad_phase", format!("{:?} doesn't look at z, so can't be used in a 3D domain (use grid or brute force)", self.broad_phase));
+        }
+        if let Err(reason) = self.particle_radius.check() {
+            return invalid("particle_radius", reason);
+        }
+        if !(self.initial_speed.is_finite() && self.initial_speed >= 0.0) {
+            return invalid("initial_speed", format!("must be a non-negative number, got {}", self.initial_speed));
+        }
+        let (mass_min, mass_max) = self.mass_range;
+        if !(mass_min.is_finite() && mass_max.is_finite() && mass_min > 0.0 && mass_min <= mass_max) {
+            return invalid("mass_range", format!("must be (min, max) with 0 < min <= max, got ({}, {})", mass_min, mass_max));
+        }
+        if self.species.is_empty() {
+            return invalid("species", "must have at least one entry");
+        }
+        for species in &self.species {
+            if !(species.weight.is_finite() && species.weight > 0.0) {
+                return invalid("species.weight", format!("must be a positive number, got {}", species.weight));
+            }
+            if let Err(reason) = species.movement.check() {
+                return invalid("species.movement", reason);
+            }
+        }
+        if let Err(reason) = self.dynamics.check() {
+            return invalid("dynamics", reason);
+        }
+        if self.num_iterations == 0 {
+            return invalid("num_iterations", "must be at least 1");
+        }
+        if self.thread_count == 0 {
+            return invalid("thread_count", "must be at least 1");
+        }
+        if self.movement_thread_count == 0 {
+            return invalid("movement_thread_count", "must be at least 1");
+        }
+        if self.pipeline_depth == 0 {
+            return invalid("pipeline_depth", "must be at least 1");
+        }
+        Ok(())
+    }
+}
+
+// A SimulationConfig value that can't be run, named by its field
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("invalid value for `{field}`: {reason}")]
+pub struct ConfigError {
+    pub field: &'static str,
+    pub reason: String
+}
+
+// Reads a vector from a scenario file as [x, y] or [x, y, z]; the 2D form leaves z at its default.
+// Used with #[serde(deserialize_with)] so scenarios written before 3D support still load.
+pub(crate) fn xy_or_xyz<'de, D: Deserializer<'de>, T: Deserialize<'de> + Default>(deserializer: D) -> Result<(T, T, T), D::Error> {
+    let components = Vec::<T>::deserialize(deserializer)?;
+    let count = components.len();
+    let mut components = components.into_iter();
+    match (components.next(), components.next(), components.next(), components.next()) {
+        (Some(x), Some(y), z, None) => Ok((x, y, z.unwrap_or_default())),
+        _ => Err(de::Error::invalid_length(count, &"2 or 3 components"))
+    }
+}
@@ -0,0 +1,195 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{self, Duration};
+use crate::{CounterKind, Dimension, ParticleSystem, Scalar, SimulationConfig};
+
+// Where the collision threads count what they find, and how they synchronise doing it. Each task
+// calls count once per collision & task_done once at the end; end_pass runs once all the pass'
+// tasks have finished. The backends are there to compare the synchronisation costs (see
+// compare_counters); every one of them gives the same total.
+pub trait CollisionCounter: Send + Sync {
+    // As used on the command line & in reports
+    fn name(&self) -> &'static str;
+    // One collision, found by a task running on the collision thread with this id (0 up to the
+    // number of collision threads; every task a thread runs uses the same id)
+    fn count(&self, thread_id: usize);
+    // That task has finished, having found `collisions` in all
+    fn task_done(&self, _thread_id: usize, _collisions: usize) {}
+    // Every task of the pass has finished; fold anything held back into the total
+    fn end_pass(&self) {}
+    // Collisions in every finished pass
+    fn total(&self) -> usize;
+}
+
+// The counter selected by `kind`
+pub fn counter_for(kind: CounterKind) -> Arc<dyn CollisionCounter> {
+    match kind {
+        CounterKind::Mutex => Arc::new(MutexCounter::default()),
+        CounterKind::AtomicRelaxed => Arc::new(AtomicCounter::new(Ordering::Relaxed)),
+        CounterKind::AtomicAcqRel => Arc::new(AtomicCounter::new(Ordering::AcqRel)),
+        CounterKind::AtomicSeqCst => Arc::new(AtomicCounter::new(Ordering::SeqCst)),
+        CounterKind::Sharded => Arc::new(ShardedCounter::default()),
+        CounterKind::PerThread => Arc::new(PerThreadCounter::default())
+    }
+}
+
+// One total behind a lock, taken for every collision
+#[derive(Debug, Default)]
+pub struct MutexCounter {
+    total: Mutex<usize>
+}
+
+impl CollisionCounter for MutexCounter {
+    fn name(&self) -> &'static str {
+        "mutex"
+    }
+    fn count(&self, _thread_id: usize) {
+        *self.total.lock().unwrap() += 1;
+    }
+    fn total(&self) -> usize {
+        *self.total.lock().unwrap()
+    }
+}
+
+// One AtomicUsize, added to for every collision with the given ordering (the lab's Q2 counter).
+// Relaxed is enough for a count; the others show what stronger orderings cost
+#[derive(Debug)]
+pub struct AtomicCounter {
+    total: AtomicUsize,
+    ordering: Ordering
+}
+
+impl AtomicCounter {
+    pub fn new(ordering: Ordering) -> AtomicCounter {
+        AtomicCounter { total: AtomicUsize::new(0), ordering }
+    }
+}
+
+impl CollisionCounter for AtomicCounter {
+    fn name(&self) -> &'static str {
+        match self.ordering {
+            Ordering::Relaxed => "atomic-relaxed",
+            Ordering::AcqRel => "atomic-acq-rel",
+            Ordering::SeqCst => "atomic-seq-cst",
+            Ordering::Acquire => "atomic-acquire",
+            Ordering::Release => "atomic-release",
+            _ => "atomic"
+        }
+    }
+    fn count(&self, _thread_id: usize) {
+        self.total.fetch_add(1, self.ordering);
+    }
+    // Read once the pass' threads have been joined, which already orders it after every add
+    fn total(&self) -> usize {
+        self.total.load(Ordering::Relaxed)
+    }
+}
+
+// Shards to count into. A collision thread uses shard thread_id % SHARDS, so with more than this
+// many threads some share a shard, which is still correct, just not contention-free
+pub const SHARDS: usize = 64;
+
+// One thread's running count, on a 128 byte line of its own: x86 fetches 64 byte lines in pairs,
+// so anything closer would still bounce between cores
+#[repr(align(128))]
+#[derive(Debug, Default)]
+struct Shard(AtomicUsize);
+
+// Rather than every collision going to one AtomicUsize (& its cache line going round every
+// core), each collision thread counts into its own shard (thread_id % SHARDS, so shared beyond 64
+// threads), and end_pass adds the shards to the total once the pass that filled them is over.
+// The default backend
+#[derive(Debug)]
+pub struct ShardedCounter {
+    shards: Vec<Shard>,
+    // Every pass reduced so far
+    total: AtomicUsize
+}
+
+impl Default for ShardedCounter {
+    fn default() -> Self {
+        ShardedCounter { shards: (0..SHARDS).map(|_| Shard::default()).collect(), total: AtomicUsize::new(0) }
+    }
+}
+
+impl CollisionCounter for ShardedCounter {
+    fn name(&self) -> &'static str {
+        "sharded"
+    }
+    fn count(&self, thread_id: usize) {
+        self.shards[thread_id % SHARDS].0.fetch_add(1, Ordering::Relaxed);
+    }
+    // Empty every shard into the total
+    fn end_pass(&self) {
+        let pass: usize = self.shards.iter().map(|shard| shard.0.swap(0, Ordering::Relaxed)).sum();
+        self.total.fetch_add(pass, Ordering::Relaxed);
+    }
+    fn total(&self) -> usize {
+        self.total.load(Ordering::Relaxed)
+    }
+}
+
+// Nothing shared per collision at all: each task's own count (kept on its stack by thread_collide)
+// is added to the total once, when the task is done
+#[derive(Debug, Default)]
+pub struct PerThreadCounter {
+    total: AtomicUsize
+}
+
+impl CollisionCounter for PerThreadCounter {
+    fn name(&self) -> &'static str {
+        "per-thread"
+    }
+    fn count(&self, _thread_id: usize) {}
+    fn task_done(&self, _thread_id: usize, collisions: usize) {
+        self.total.fetch_add(collisions, Ordering::Relaxed);
+    }
+    fn total(&self) -> usize {
+        self.total.load(Ordering::Relaxed)
+    }
+}
+
+// How one backend did in compare_counters
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CounterReport {
+    pub kind: CounterKind,
+    // The counter's own name()
+    pub name: &'static str,
+    // What the counter added up to...
+    pub collisions: usize,
+    // ...and what it should have: the number of collision events a recorded run of the same workload found
+    pub expected: usize,
+    // Moving & colliding, start to finish
+    pub duration: Duration
+}
+
+impl CounterReport {
+    pub fn correct(&self) -> bool {
+        self.collisions == self.expected
+    }
+    // Collisions counted per second of run time
+    pub fn throughput(&self) -> f64 {
+        self.collisions as f64 / self.duration.as_secs_f64().max(f64::MIN_POSITIVE)
+    }
+}
+
+// Run the same seeded move_and_collide_particles workload once with every counter backend, plus
+// once recording every event to know the right answer. Without a seed in `config` one is drawn
+// here, so every backend still gets the same particles. Collisions aren't logged, since printing
+// them would take far longer than counting them.
+pub fn compare_counters<T: Scalar, D: Dimension>(config: &SimulationConfig) -> Vec<CounterReport> {
+    let seed = config.seed.unwrap_or_else(rand::random);
+    let run = |kind, record_collisions| {
+        let mut particle_system = ParticleSystem::<T, D>::with_config(SimulationConfig { counter: kind, record_collisions, log_collisions: false, seed: Some(seed), ..config.clone() });
+        particle_system.spawn_particles();
+        let start_time = time::Instant::now();
+        particle_system.move_and_collide_particles();
+        let duration = start_time.elapsed();
+        (particle_system.collision_counter.name(), particle_system.collision_count(), particle_system.collision_events().len(), duration)
+    };
+    let (_, _, expected, _) = run(CounterKind::default(), true);
+    CounterKind::ALL.into_iter().map(|kind| {
+        let (name, collisions, _, duration) = run(kind, false);
+        CounterReport { kind, name, collisions, expected, duration }
+    }).collect()
+}
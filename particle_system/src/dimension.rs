@@ -0,0 +1,90 @@
+use std::fmt::Debug;
+use crate::Scalar;
+
+// How many axes particles move & collide in, as a type: TwoD or ThreeD. Particle, ParticleSystem
+// and everything that handles particles take one (TwoD unless asked otherwise), so a flat run
+// doesn't carry a z it never uses, and a flat particle can't be given one.
+//
+// The only difference between the two is the depth axis. A particle keeps its z & vz as
+// Depth<T>: T in 3D, but Flat (nothing at all) in 2D. Generic code reads them with `depth`, which
+// is a constant zero in 2D, & writes them through `depth_mut`, which has nothing to write to.
+pub trait Dimension: Copy + Default + Debug + PartialEq + Send + Sync + 'static {
+    // 2 or 3
+    const AXES: usize;
+
+    type Depth<T: Scalar>: Copy + Default + Debug + PartialEq + Send + Sync;
+
+    // The value of a z or vz; always zero in 2D
+    fn depth<T: Scalar>(depth: Self::Depth<T>) -> T;
+    // A z or vz from a value, which is dropped in 2D
+    fn to_depth<T: Scalar>(value: T) -> Self::Depth<T>;
+    fn depth_mut<T: Scalar>(depth: &mut Self::Depth<T>) -> Option<&mut T>;
+    // A whole column of them (see ParticleColumns), or None in 2D
+    fn depths<T: Scalar>(column: &[Self::Depth<T>]) -> Option<&[T]>;
+    fn depths_mut<T: Scalar>(column: &mut [Self::Depth<T>]) -> Option<&mut [T]>;
+
+    // Dot product of two vectors, counting z only in 3D
+    fn dot<T: Scalar>(a: (T, T, T), b: (T, T, T)) -> T;
+}
+
+// A flat domain: particles move in x & y only
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct TwoD;
+
+// A box with depth: particles move in x, y & z, and collide as spheres
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct ThreeD;
+
+// The z (or vz) of a particle in 2D. Takes no space, and there is nothing to read but zero
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Flat;
+
+impl Dimension for TwoD {
+    const AXES: usize = 2;
+
+    type Depth<T: Scalar> = Flat;
+
+    fn depth<T: Scalar>(_depth: Flat) -> T {
+        T::ZERO
+    }
+    fn to_depth<T: Scalar>(_value: T) -> Flat {
+        Flat
+    }
+    fn depth_mut<T: Scalar>(_depth: &mut Flat) -> Option<&mut T> {
+        None
+    }
+    fn depths<T: Scalar>(_column: &[Flat]) -> Option<&[T]> {
+        None
+    }
+    fn depths_mut<T: Scalar>(_column: &mut [Flat]) -> Option<&mut [T]> {
+        None
+    }
+    fn dot<T: Scalar>(a: (T, T, T), b: (T, T, T)) -> T {
+        a.0 * b.0 + a.1 * b.1
+    }
+}
+
+impl Dimension for ThreeD {
+    const AXES: usize = 3;
+
+    type Depth<T: Scalar> = T;
+
+    fn depth<T: Scalar>(depth: T) -> T {
+        depth
+    }
+    fn to_depth<T: Scalar>(value: T) -> T {
+        value
+    }
+    fn depth_mut<T: Scalar>(depth: &mut T) -> Option<&mut T> {
+        Some(depth)
+    }
+    fn depths<T: Scalar>(column: &[T]) -> Option<&[T]> {
+        Some(column)
+    }
+    fn depths_mut<T: Scalar>(column: &mut [T]) -> Option<&mut [T]> {
+        Some(column)
+    }
+    fn dot<T: Scalar>(a: (T, T, T), b: (T, T, T)) -> T {
+        a.0 * b.0 + a.1 * b.1 + a.2 * b.2
+    }
+}
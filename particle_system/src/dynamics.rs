@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+use crate::config::xy_or_xyz;
+use crate::{Dimension, Particle, Scalar};
+
+// How thread_main advances a particle each step.
+//
+// The original lab model is a random walk: the species' motion model adds a random offset, and
+// the particle's velocity (if any) carries it one unit per step. The integrated mode instead
+// treats velocity & force properly, advancing time by `dt` per step with the chosen integrator.
+// It is deterministic & ignores the motion models.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Dynamics {
+    #[default]
+    RandomWalk,
+    Integrated {
+        integrator: Integrator,
+        dt: f32,
+        #[serde(default)]
+        force: ForceField
+    }
+}
+
+// Time step used when an integrator is picked on the command line without --dt
+pub const DEFAULT_DT: f32 = 0.01;
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "snake_case")]
+pub enum Integrator {
+    // First order; energy grows without bound in oscillating systems
+    ExplicitEuler,
+    // First order but symplectic: energy oscillates around the true value instead of drifting
+    SemiImplicitEuler,
+    // Second order & symplectic; exact for constant forces
+    #[default]
+    VelocityVerlet
+}
+
+// External force on every particle, depending only on its position (and mass). Vectors are
+// written [x, y] or [x, y, z] in scenario files.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum ForceField {
+    #[default]
+    None,
+    // Uniform acceleration, e.g. (0, -9.81, 0)
+    Gravity {
+        #[serde(deserialize_with = "xy_or_xyz")]
+        acceleration: (f32, f32, f32)
+    },
+    // A spring pulling every particle towards `centre`: F = -stiffness (x - centre)
+    Harmonic {
+        stiffness: f32,
+        #[serde(deserialize_with = "xy_or_xyz")]
+        centre: (f32, f32, f32)
+    }
+}
+
+// The field's parameters are f32 like the rest of the config, and converted to the particle's precision
+fn vector<T: Scalar>(v: (f32, f32, f32)) -> (T, T, T) {
+    (T::from_f32(v.0), T::from_f32(v.1), T::from_f32(v.2))
+}
+
+impl ForceField {
+    pub fn acceleration<T: Scalar, D: Dimension>(&self, particle: &Particle<T, D>) -> (T, T, T) {
+        match *self {
+            ForceField::None => (T::ZERO, T::ZERO, T::ZERO),
+            ForceField::Gravity { acceleration } => vector(acceleration),
+            ForceField::Harmonic { stiffness, centre } => {
+                let centre: (T, T, T) = vector(centre);
+                let k = T::from_f32(stiffness) / particle.mass;
+                (-k * (particle.x - centre.0), -k * (particle.y - centre.1), -k * (particle.z() - centre.2))
+            }
+        }
+    }
+    // Potential energy of one particle, with zero at the origin / centre
+    pub fn potential_energy<T: Scalar, D: Dimension>(&self, particle: &Particle<T, D>) -> T {
+        match *self {
+            ForceField::None => T::ZERO,
+            ForceField::Gravity { acceleration } => {
+                let acceleration: (T, T, T) = vector(acceleration);
+                -particle.mass * (acceleration.0 * particle.x + acceleration.1 * particle.y + acceleration.2 * particle.z())
+            }
+            ForceField::Harmonic { stiffness, centre } => {
+                let centre: (T, T, T) = vector(centre);
+                let (x, y, z) = (particle.x - centre.0, particle.y - centre.1, particle.z() - centre.2);
+                T::from_f32(0.5) * T::from_f32(stiffness) * (x * x + y * y + z * z)
+            }
+        }
+    }
+}
+
+impl Integrator {
+    // Advance one particle by `dt`. In 2D the z components of the force are dropped
+    pub fn step<T: Scalar, D: Dimension>(&self, particle: &mut Particle<T, D>, force: &ForceField, dt: f32) {
+        let dt = T::from_f32(dt);
+        let half = T::from_f32(0.5);
+        match self {
+            Integrator::ExplicitEuler => {
+                let a = force.acceleration(particle);
+                let v = particle.velocity();
+                particle.translate((v.0 * dt, v.1 * dt, v.2 * dt));
+                particle.accelerate((a.0 * dt, a.1 * dt, a.2 * dt));
+            }
+            Integrator::SemiImplicitEuler => {
+                let a = force.acceleration(particle);
+                particle.accelerate((a.0 * dt, a.1 * dt, a.2 * dt));
+                let v = particle.velocity();
+                particle.translate((v.0 * dt, v.1 * dt, v.2 * dt));
+            }
+            Integrator::VelocityVerlet => {
+                // The force only depends on position, so recomputing a(t) costs less than
+                // storing it on every particle
+                let a = force.acceleration(particle);
+                let v = particle.velocity();
+                particle.translate(((v.0 + half * a.0 * dt) * dt, (v.1 + half * a.1 * dt) * dt, (v.2 + half * a.2 * dt) * dt));
+                let a_next = force.acceleration(particle);
+                particle.accelerate((half * (a.0 + a_next.0) * dt, half * (a.1 + a_next.1) * dt, half * (a.2 + a_next.2) * dt));
+            }
+        }
+    }
+}
+
+impl Dynamics {
+    // Describes the first problem, if any, for error messages
+    pub fn check(&self) -> Result<(), String> {
+        match *self {
+            Dynamics::Integrated { dt, .. } if !(dt.is_finite() && dt > 0.0) => Err(format!("dt must be a positive number, got {}", dt)),
+            Dynamics::Integrated { force: ForceField::Harmonic { stiffness, .. }, .. } if !(stiffness.is_finite() && stiffness >= 0.0) => {
+                Err(format!("stiffness must be a non-negative number, got {}", stiffness))
+            }
+            _ => Ok(())
+        }
+    }
+    pub fn force(&self) -> ForceField {
+        match *self {
+            Dynamics::RandomWalk => ForceField::None,
+            Dynamics::Integrated { force, .. } => force
+        }
+    }
+}
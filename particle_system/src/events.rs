@@ -0,0 +1,60 @@
+use crate::{Dimension, Particle, Scalar};
+
+// One detected collision. `a` is always the lower particle id.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CollisionEvent<T: Scalar = f32> {
+    // Movement steps taken before the collision pass that found it
+    pub step: usize,
+    // Collision thread that found it
+    pub thread_id: usize,
+    pub a: usize,
+    pub b: usize,
+    pub a_position: (T, T, T),
+    pub b_position: (T, T, T)
+}
+
+impl<T: Scalar> CollisionEvent<T> {
+    pub fn new<D: Dimension>(step: usize, thread_id: usize, a: &Particle<T, D>, b: &Particle<T, D>) -> CollisionEvent<T> {
+        let (a, b) = if a.id <= b.id { (a, b) } else { (b, a) };
+        CollisionEvent {
+            step,
+            thread_id,
+            a: a.id,
+            b: b.id,
+            a_position: a.position(),
+            b_position: b.position()
+        }
+    }
+}
+
+// Owned by exactly one collision thread for one pass, so recording needs no locks or atomics.
+// The system merges every thread's buffer once the pass has finished.
+#[derive(Debug, Default)]
+pub struct EventBuffer<T: Scalar = f32> {
+    pub thread_id: usize,
+    pub step: usize,
+    // When false, nothing is recorded (the full history can get large over long runs)
+    pub recording: bool,
+    pub events: Vec<CollisionEvent<T>>
+}
+
+impl<T: Scalar> EventBuffer<T> {
+    pub fn new(thread_id: usize, step: usize, recording: bool) -> EventBuffer<T> {
+        EventBuffer { thread_id, step, recording, events: Vec::new() }
+    }
+    pub fn record<D: Dimension>(&mut self, a: &Particle<T, D>, b: &Particle<T, D>) {
+        if self.recording {
+            self.events.push(CollisionEvent::new(self.step, self.thread_id, a, b));
+        }
+    }
+}
+
+// Merge one pass worth of buffers into `history`. Within a step the events are ordered by
+// particle ids, so the history doesn't depend on how the pass was split between threads.
+pub fn merge_event_buffers<T: Scalar>(buffers: Vec<EventBuffer<T>>, history: &mut Vec<CollisionEvent<T>>) {
+    let start = history.len();
+    for buffer in buffers {
+        history.extend(buffer.events);
+    }
+    history[start..].sort_unstable_by_key(|event| (event.step, event.a, event.b));
+}
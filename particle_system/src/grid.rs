@@ -0,0 +1,167 @@
+use crate::{Domain, ParticleStore, Scalar};
+
+// Uniform grid broad phase. Particles are bucketed into cells at least as wide as the largest
+// contact distance (twice the largest radius), so any colliding pair sits in the same or an
+// adjacent cell and only those 9 cells (27 in 3D) need checking, instead of the whole list. A flat
+// domain is a single layer of cells in z.
+//
+// On a periodic axis the grid spans the whole domain in cells that tile it exactly, and the
+// neighbours of the first & last cells wrap round to each other.
+//
+// The cells are stored CSR-style: `cell_start[c]..cell_start[c + 1]` indexes `sorted` to give the
+// particles in cell c. Building is a counting sort, O(n) with no per-cell allocations.
+pub struct UniformGrid<T: Scalar = f32> {
+    origin: (T, T, T),
+    cell_size: (T, T, T),
+    dims: (usize, usize, usize),
+    periodic: (bool, bool, bool),
+    cell_of: Vec<usize>,
+    cell_start: Vec<usize>,
+    sorted: Vec<usize>
+}
+
+// Never allocate more than this many cells per particle; sparse systems get coarser cells instead
+const MAX_CELLS_PER_PARTICLE: usize = 4;
+
+// The cells next to (and including) cell `c` along one axis, as up to two runs of consecutive
+// cells (first, last); only the first `count` are used. Stored rows are contiguous, so each run
+// is a single slice of `sorted`.
+fn neighbour_runs(c: usize, dims: usize, periodic: bool) -> ([(usize, usize); 2], usize) {
+    if periodic && dims <= 3 {
+        // Every cell is a neighbour; list each once
+        return ([(0, dims - 1), (0, 0)], 1);
+    }
+    if periodic && c == 0 {
+        return ([(dims - 1, dims - 1), (0, 1)], 2);
+    }
+    if periodic && c == dims - 1 {
+        return ([(dims - 2, dims - 1), (0, 0)], 2);
+    }
+    ([(c.saturating_sub(1), (c + 1).min(dims - 1)), (0, 0)], 1)
+}
+
+impl<T: Scalar> UniformGrid<T> {
+    pub fn build<P: ParticleStore<T> + ?Sized>(particles: &P, contact_distance: T, domain: &Domain<T>) -> UniformGrid<T> {
+        // Slightly wider than the contact distance, so rounding in the cell lookup can never push
+        // a pair exactly touching into non-adjacent cells
+        let mut cell_size = (contact_distance * T::from_f32(1.001)).max(T::MIN_POSITIVE);
+
+        let mut min = (T::INFINITY, T::INFINITY, T::INFINITY);
+        let mut max = (T::NEG_INFINITY, T::NEG_INFINITY, T::NEG_INFINITY);
+        for i in 0..particles.len() {
+            let (x, y, z) = particles.position(i);
+            min = (min.0.min(x), min.1.min(y), min.2.min(z));
+            max = (max.0.max(x), max.1.max(y), max.2.max(z));
+        }
+        if particles.is_empty() {
+            min = (T::ZERO, T::ZERO, T::ZERO);
+            max = (T::ZERO, T::ZERO, T::ZERO);
+        }
+        let periodic = domain.periodic();
+        if periodic.0 {
+            (min.0, max.0) = (-domain.half.0, domain.half.0);
+        }
+        if periodic.1 {
+            (min.1, max.1) = (-domain.half.1, domain.half.1);
+        }
+        if periodic.2 {
+            (min.2, max.2) = (-domain.half.2, domain.half.2);
+        }
+
+        let max_cells = (particles.len() * MAX_CELLS_PER_PARTICLE).max(1);
+        let mut dims = UniformGrid::dims_for(min, max, cell_size, periodic, max_cells);
+        while dims.0.saturating_mul(dims.1).saturating_mul(dims.2) > max_cells {
+            cell_size *= T::from_f32(2.0);
+            dims = UniformGrid::dims_for(min, max, cell_size, periodic, max_cells);
+        }
+        // Periodic axes stretch their cells to tile the domain exactly
+        let stretched = |periodic: bool, extent: T, dims: usize| if periodic { extent / T::from_usize(dims) } else { cell_size };
+        let cell_size = (
+            stretched(periodic.0, max.0 - min.0, dims.0),
+            stretched(periodic.1, max.1 - min.1, dims.1),
+            stretched(periodic.2, max.2 - min.2, dims.2)
+        );
+
+        let mut grid = UniformGrid {
+            origin: min,
+            cell_size,
+            dims,
+            periodic,
+            cell_of: Vec::with_capacity(particles.len()),
+            cell_start: vec![0; dims.0 * dims.1 * dims.2 + 1],
+            sorted: vec![0; particles.len()]
+        };
+
+        // Count particles per cell, prefix-sum into start offsets, then scatter
+        for i in 0..particles.len() {
+            let cell = grid.cell_index(grid.cell_coords(particles.position(i)));
+            grid.cell_of.push(cell);
+            grid.cell_start[cell + 1] += 1;
+        }
+        for c in 0..grid.cell_start.len() - 1 {
+            grid.cell_start[c + 1] += grid.cell_start[c];
+        }
+        let mut next = grid.cell_start.clone();
+        for (i, &cell) in grid.cell_of.iter().enumerate() {
+            grid.sorted[next[cell]] = i;
+            next[cell] += 1;
+        }
+        grid
+    }
+
+    // Cells per axis at `cell_size`. A tiny or zero contact distance makes extent / cell_size huge
+    // (or infinite, which to_usize saturates to usize::MAX), so counts are capped one past
+    // `max_cells`: still too many, so build keeps doubling the cell size, but never overflowing
+    fn dims_for(min: (T, T, T), max: (T, T, T), cell_size: T, periodic: (bool, bool, bool), max_cells: usize) -> (usize, usize, usize) {
+        let axis = |extent: T, periodic: bool| {
+            let cells = (extent / cell_size).to_usize().min(max_cells + 1);
+            if periodic {
+                cells.max(1)
+            } else {
+                cells + 1
+            }
+        };
+        (axis(max.0 - min.0, periodic.0), axis(max.1 - min.1, periodic.1), axis(max.2 - min.2, periodic.2))
+    }
+
+    fn cell_coords(&self, (x, y, z): (T, T, T)) -> (usize, usize, usize) {
+        let cx = ((x - self.origin.0) / self.cell_size.0).to_usize();
+        let cy = ((y - self.origin.1) / self.cell_size.1).to_usize();
+        let cz = ((z - self.origin.2) / self.cell_size.2).to_usize();
+        (cx.min(self.dims.0 - 1), cy.min(self.dims.1 - 1), cz.min(self.dims.2 - 1))
+    }
+
+    fn cell_index(&self, (cx, cy, cz): (usize, usize, usize)) -> usize {
+        (cz * self.dims.1 + cy) * self.dims.0 + cx
+    }
+
+    pub fn cell_size(&self) -> (T, T, T) {
+        self.cell_size
+    }
+
+    // Indices of the particles sharing a cell with, or in a cell next to, particle `i`
+    // (including `i` itself)
+    pub fn neighbours(&self, i: usize) -> impl Iterator<Item = usize> + '_ {
+        self.neighbour_runs(i).flat_map(|run| run.iter().copied())
+    }
+
+    // The same, as runs of consecutive cells: one slice of particle indices per run
+    pub fn neighbour_runs(&self, i: usize) -> impl Iterator<Item = &[usize]> + '_ {
+        let cell = self.cell_of[i];
+        let (cx, cy, cz) = (cell % self.dims.0, cell / self.dims.0 % self.dims.1, cell / (self.dims.0 * self.dims.1));
+        let (x_runs, x_count) = neighbour_runs(cx, self.dims.0, self.periodic.0);
+        let (y_runs, y_count) = neighbour_runs(cy, self.dims.1, self.periodic.1);
+        let (z_runs, z_count) = neighbour_runs(cz, self.dims.2, self.periodic.2);
+        let rows = z_runs.into_iter().take(z_count).flat_map(|(z0, z1)| z0..=z1).flat_map(move |z| {
+            y_runs.into_iter().take(y_count).flat_map(|(y0, y1)| y0..=y1).map(move |y| (z * self.dims.1 + y) * self.dims.0)
+        });
+        rows.flat_map(move |row| {
+            x_runs.into_iter().take(x_count).map(move |(x0, x1)| &self.sorted[self.cell_start[row + x0]..self.cell_start[row + x1 + 1]])
+        })
+    }
+
+    // Candidate partners for particle `i`, restricted to j > i so every pair comes out once
+    pub fn candidates(&self, i: usize) -> impl Iterator<Item = usize> + '_ {
+        self.neighbours(i).filter(move |&j| j > i)
+    }
+}
@@ -0,0 +1,52 @@
+// Shared particle simulation used by the three lab binaries (colliding_particles,
+// colliding_particles_atomic & colliding_particles_simultaneous).
+mod boundary;
+mod broad_phase;
+#[cfg(feature = "cli")]
+mod cli;
+mod config;
+mod counter;
+mod dimension;
+mod dynamics;
+mod events;
+mod grid;
+mod motion;
+mod particle;
+pub mod partition;
+mod pipeline;
+mod quadtree;
+mod rng;
+mod scalar;
+mod scenario;
+mod scheduler;
+mod seqlock;
+mod simd;
+mod soa;
+mod sweep_and_prune;
+mod system;
+mod threads;
+
+pub use boundary::{Boundary, Domain};
+pub use broad_phase::{broad_phase_for, BroadPhase, BruteForce};
+#[cfg(feature = "cli")]
+pub use cli::Cli;
+pub use config::{BroadPhaseKind, ConfigError, CounterKind, RadiusDistribution, SchedulerKind, SimulationConfig, StorageLayout};
+pub use counter::{compare_counters, counter_for, AtomicCounter, CollisionCounter, CounterReport, MutexCounter, PerThreadCounter, ShardedCounter, SHARDS};
+pub use dimension::{Dimension, Flat, ThreeD, TwoD};
+pub use dynamics::{Dynamics, ForceField, Integrator, DEFAULT_DT};
+pub use events::{merge_event_buffers, CollisionEvent, EventBuffer};
+pub use grid::UniformGrid;
+pub use motion::{motion_model_for, motion_models_for, Brownian, ConstantDrift, LevyFlight, MotionModel, MovementModel, RandomWalk, Species, VectorFieldDrift};
+pub use particle::{max_contact_distance, resolve_elastic_collision, resolve_elastic_collision_in, Particle, DEFAULT_RADIUS};
+pub use pipeline::PipelineStats;
+pub use quadtree::{Aabb, Quadtree};
+pub use rng::ParticleRng;
+pub use scalar::{Precision, Scalar};
+pub use scenario::{BoundsSettings, OutputSink, ParticleSettings, RunSummary, Scenario, ScenarioError, Strategy, ThreadSettings};
+pub use scheduler::{SchedulerStats, TaskQueues, WorkerStats, TASKS_PER_THREAD};
+pub use seqlock::AtomicPositions;
+pub use simd::SimdLevel;
+pub use soa::{ColumnsMut, ParticleColumns, ParticleSlice, ParticleStore};
+pub use sweep_and_prune::SweepAndPrune;
+pub use system::ParticleSystem;
+pub use threads::{collision_worker, thread_collide, thread_main, thread_main_columns, thread_main_shared};
@@ -0,0 +1,200 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use crate::config::xy_or_xyz;
+use crate::rng::{random_direction, standard_normal};
+use crate::{Dimension, Particle, ParticleRng, Scalar, TwoD};
+
+// How a particle moves on its own during one step, on top of its velocity.
+//
+// thread_main calls `displacement` once per particle per step, from whichever movement thread
+// owns the particle's chunk, with that particle's (seed, id, step) stream. A model must only use
+// that stream (never shared state) so results stay the same for any number of threads.
+// In 2D (D::AXES == 2) the z component is dropped, and models shouldn't spend draws on it.
+// Models are generic over the particle precision; draws stay f32 so both precisions take the same steps.
+pub trait MotionModel<T: Scalar = f32, D: Dimension = TwoD>: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn displacement(&self, particle: &Particle<T, D>, rng: &mut ParticleRng) -> (T, T, T);
+}
+
+// A motion model as it appears in a config or scenario file. A vector field can't be written down
+// there; use ParticleSystem::set_motion_model with a VectorFieldDrift instead.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum MovementModel {
+    // Uniform +-[0, 1) step on each axis, the original lab model
+    #[default]
+    RandomWalk,
+    // Gaussian steps with variance 2 * diffusion per axis per step
+    Brownian { diffusion: f32 },
+    // Heavy-tailed step lengths (see LevyFlight)
+    Levy { alpha: f32, min_step: f32, max_step: f32 },
+    // The same displacement every step, [x, y] or [x, y, z]
+    Drift {
+        #[serde(deserialize_with = "xy_or_xyz")]
+        velocity: (f32, f32, f32)
+    }
+}
+
+impl MovementModel {
+    // Describes the first problem, if any, for error messages
+    pub fn check(&self) -> Result<(), String> {
+        match *self {
+            MovementModel::Brownian { diffusion } if !(diffusion.is_finite() && diffusion >= 0.0) => {
+                Err(format!("diffusion must be a non-negative number, got {}", diffusion))
+            }
+            MovementModel::Levy { alpha, min_step, max_step } if !(alpha > 0.0 && alpha <= 2.0 && min_step > 0.0 && min_step <= max_step && max_step.is_finite()) => {
+                Err(format!("needs 0 < alpha <= 2 and 0 < min_step <= max_step, got alpha {} steps {}..{}", alpha, min_step, max_step))
+            }
+            MovementModel::Drift { velocity } if !(velocity.0.is_finite() && velocity.1.is_finite() && velocity.2.is_finite()) => {
+                Err(format!("velocity must be finite, got ({}, {}, {})", velocity.0, velocity.1, velocity.2))
+            }
+            _ => Ok(())
+        }
+    }
+}
+
+// One kind of particle. Spawned particles pick a species with probability proportional to its weight.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Species {
+    #[serde(default = "unit_weight")]
+    pub weight: f32,
+    #[serde(default)]
+    pub movement: MovementModel
+}
+
+fn unit_weight() -> f32 {
+    1.0
+}
+
+impl Default for Species {
+    fn default() -> Self {
+        Species { weight: 1.0, movement: MovementModel::RandomWalk }
+    }
+}
+
+// Build the motion model described by `movement`
+pub fn motion_model_for<T: Scalar, D: Dimension>(movement: &MovementModel) -> Box<dyn MotionModel<T, D>> {
+    match *movement {
+        MovementModel::RandomWalk => Box::new(RandomWalk),
+        MovementModel::Brownian { diffusion } => Box::new(Brownian { diffusion }),
+        MovementModel::Levy { alpha, min_step, max_step } => Box::new(LevyFlight { alpha, min_step, max_step }),
+        MovementModel::Drift { velocity } => Box::new(ConstantDrift { velocity })
+    }
+}
+
+// One model per species in `species`, indexed by Particle::species
+pub fn motion_models_for<T: Scalar, D: Dimension>(species: &[Species]) -> Vec<Box<dyn MotionModel<T, D>>> {
+    species.iter().map(|species| motion_model_for(&species.movement)).collect()
+}
+
+// Uniform +-[0, 1) on each axis: a magnitude, then a coin flip for the sign
+#[derive(Debug, Default, Copy, Clone)]
+pub struct RandomWalk;
+
+impl<T: Scalar, D: Dimension> MotionModel<T, D> for RandomWalk {
+    fn name(&self) -> &'static str {
+        "random_walk"
+    }
+    fn displacement(&self, _particle: &Particle<T, D>, rng: &mut ParticleRng) -> (T, T, T) {
+        // Generate vector to add and decide whether or not it should be negative (50% chance)
+        let mut xy = (rng.random::<f32>(), rng.random::<f32>());
+        let negative = (rng.random_bool(0.5), rng.random_bool(0.5));
+        if negative.0 {
+            xy.0 = -xy.0;
+        }
+        if negative.1 {
+            xy.1 = -xy.1;
+        }
+        // z is drawn last, so 2D walks are the same as they always were
+        let mut z = 0.0;
+        if D::AXES == 3 {
+            z = rng.random::<f32>();
+            if rng.random_bool(0.5) {
+                z = -z;
+            }
+        }
+        (T::from_f32(xy.0), T::from_f32(xy.1), T::from_f32(z))
+    }
+}
+
+// Brownian motion with diffusion coefficient D: each axis moves by N(0, 2D) per unit step, so the
+// mean squared displacement grows as 4Dt in 2D & 6Dt in 3D
+#[derive(Debug, Copy, Clone)]
+pub struct Brownian {
+    pub diffusion: f32
+}
+
+impl<T: Scalar, D: Dimension> MotionModel<T, D> for Brownian {
+    fn name(&self) -> &'static str {
+        "brownian"
+    }
+    fn displacement(&self, _particle: &Particle<T, D>, rng: &mut ParticleRng) -> (T, T, T) {
+        let sigma = T::from_f32(2.0 * self.diffusion).sqrt();
+        let mut normal = || T::from_f32(standard_normal(rng));
+        let xy = (sigma * normal(), sigma * normal());
+        let z = if D::AXES == 3 { sigma * normal() } else { T::ZERO };
+        (xy.0, xy.1, z)
+    }
+}
+
+// Lévy flight: a uniformly random direction (in the plane or on the sphere), and a step length from a
+// truncated power law: P(l > x) = (min_step / x)^alpha between min_step & max_step, with any longer draw
+// cut to max_step (the domain is bounded anyway). Smaller alpha means more long jumps. The untruncated
+// tail would have infinite variance for alpha <= 2, but with the cap every step is at most max_step,
+// so the variance is finite and grows with max_step.
+#[derive(Debug, Copy, Clone)]
+pub struct LevyFlight {
+    pub alpha: f32,
+    pub min_step: f32,
+    pub max_step: f32
+}
+
+impl<T: Scalar, D: Dimension> MotionModel<T, D> for LevyFlight {
+    fn name(&self) -> &'static str {
+        "levy"
+    }
+    fn displacement(&self, _particle: &Particle<T, D>, rng: &mut ParticleRng) -> (T, T, T) {
+        let u = T::from_f32(1.0 - rng.random::<f32>());
+        let length = (T::from_f32(self.min_step) * u.powf(T::from_f32(-1.0 / self.alpha))).min(T::from_f32(self.max_step));
+        let direction = random_direction(rng, D::AXES);
+        (length * T::from_f32(direction.0), length * T::from_f32(direction.1), length * T::from_f32(direction.2))
+    }
+}
+
+// Every particle moves by the same vector each step; no randomness
+#[derive(Debug, Copy, Clone)]
+pub struct ConstantDrift {
+    pub velocity: (f32, f32, f32)
+}
+
+impl<T: Scalar, D: Dimension> MotionModel<T, D> for ConstantDrift {
+    fn name(&self) -> &'static str {
+        "drift"
+    }
+    fn displacement(&self, _particle: &Particle<T, D>, _rng: &mut ParticleRng) -> (T, T, T) {
+        (T::from_f32(self.velocity.0), T::from_f32(self.velocity.1), T::from_f32(self.velocity.2))
+    }
+}
+
+// Drift given by a user-supplied field: a particle at (x, y, z) moves by field(x, y, z) each step
+// (z is always 0 in 2D). The field works in the particles' own precision.
+pub struct VectorFieldDrift<F> {
+    pub field: F
+}
+
+impl<F> VectorFieldDrift<F> {
+    pub fn new(field: F) -> VectorFieldDrift<F> {
+        VectorFieldDrift { field }
+    }
+}
+
+impl<T: Scalar, D: Dimension, F: Fn(T, T, T) -> (T, T, T) + Send + Sync> MotionModel<T, D> for VectorFieldDrift<F> {
+    fn name(&self) -> &'static str {
+        "vector_field"
+    }
+    fn displacement(&self, particle: &Particle<T, D>, _rng: &mut ParticleRng) -> (T, T, T) {
+        (self.field)(particle.x, particle.y, particle.z())
+    }
+}
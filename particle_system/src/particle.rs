@@ -0,0 +1,159 @@
+use crate::{Dimension, Domain, Flat, Scalar, ThreeD, TwoD};
+
+// Generic over the precision of its position, velocity, mass & radius (see Scalar) and the axes
+// it moves in (see Dimension); f32 in 2D unless asked otherwise
+#[derive(Debug, Copy, Clone)]
+pub struct Particle<T: Scalar = f32, D: Dimension = TwoD> {
+    pub x: T,
+    pub y: T,
+    // Nothing at all in 2D (Flat); z() reads it in code written for either dimension
+    pub z: D::Depth<T>,
+    pub id: usize,
+    pub vx: T,
+    pub vy: T,
+    pub vz: D::Depth<T>,
+    pub mass: T,
+    pub radius: T,
+    // Index into the system's species (and so its motion model)
+    pub species: usize
+}
+
+// Radius of a particle made with Particle::new. Two of them touch at 0.25, the contact distance
+// every particle used before sizes were per particle.
+pub const DEFAULT_RADIUS: f32 = 0.125;
+impl<T: Scalar> Particle<T> {
+    // A stationary particle of unit mass & the default radius
+    pub fn new(x_param:T, y_param:T, id_param: usize) -> Particle<T> {
+        Particle {
+            x: x_param,
+            y: y_param,
+            z: Flat,
+            id:id_param,
+            vx: T::ZERO,
+            vy: T::ZERO,
+            vz: Flat,
+            mass: T::ONE,
+            radius: T::from_f32(DEFAULT_RADIUS),
+            species: 0
+        }
+    }
+}
+impl<T: Scalar> Particle<T, ThreeD> {
+    // As Particle::new, for a 3D domain
+    pub fn new_3d(x: T, y: T, z: T, id: usize) -> Particle<T, ThreeD> {
+        Particle::at((x, y, z), id)
+    }
+    pub fn with_velocity_3d(self, vx: T, vy: T, vz: T) -> Particle<T, ThreeD> {
+        Particle { vx, vy, vz, ..self }
+    }
+}
+impl<T: Scalar, D: Dimension> Particle<T, D> {
+    // As Particle::new or new_3d, for code written for either dimension; z is dropped in 2D
+    pub fn at((x, y, z): (T, T, T), id: usize) -> Particle<T, D> {
+        Particle {
+            x,
+            y,
+            z: D::to_depth(z),
+            id,
+            vx: T::ZERO,
+            vy: T::ZERO,
+            vz: D::to_depth(T::ZERO),
+            mass: T::ONE,
+            radius: T::from_f32(DEFAULT_RADIUS),
+            species: 0
+        }
+    }
+    pub fn with_velocity(self, vx: T, vy: T) -> Particle<T, D> {
+        Particle { vx, vy, ..self }
+    }
+    pub fn with_mass(self, mass: T) -> Particle<T, D> {
+        Particle { mass, ..self }
+    }
+    pub fn with_radius(self, radius: T) -> Particle<T, D> {
+        Particle { radius, ..self }
+    }
+    pub fn with_species(self, species: usize) -> Particle<T, D> {
+        Particle { species, ..self }
+    }
+    // Always 0 in 2D
+    pub fn z(&self) -> T {
+        D::depth(self.z)
+    }
+    pub fn vz(&self) -> T {
+        D::depth(self.vz)
+    }
+    pub fn position(&self) -> (T, T, T) {
+        (self.x, self.y, self.z())
+    }
+    pub fn velocity(&self) -> (T, T, T) {
+        (self.vx, self.vy, self.vz())
+    }
+    // Move by `offset` / change the velocity by `change`. The z component is dropped in 2D
+    pub(crate) fn translate(&mut self, offset: (T, T, T)) {
+        self.x += offset.0;
+        self.y += offset.1;
+        if let Some(z) = D::depth_mut(&mut self.z) {
+            *z += offset.2;
+        }
+    }
+    pub(crate) fn accelerate(&mut self, change: (T, T, T)) {
+        self.vx += change.0;
+        self.vy += change.1;
+        if let Some(vz) = D::depth_mut(&mut self.vz) {
+            *vz += change.2;
+        }
+    }
+    // Touching or overlapping: centres no further apart than the sum of the two radii (circles in
+    // 2D, spheres in 3D)
+    pub fn collide(&self, other: &Particle<T, D>) -> bool {
+        self.collide_in(other, &Domain::default())
+    }
+    // As collide, measuring the distance within `domain`, i.e. round the seam on periodic axes
+    pub fn collide_in(&self, other: &Particle<T, D>, domain: &Domain<T>) -> bool {
+        let separation = domain.separation(self, other);
+        let contact = self.radius + other.radius;
+        D::dot(separation, separation) <= contact * contact
+    }
+    pub fn momentum(&self) -> (T, T, T) {
+        (self.mass * self.vx, self.mass * self.vy, self.mass * self.vz())
+    }
+    pub fn kinetic_energy(&self) -> T {
+        T::from_f32(0.5) * self.mass * D::dot(self.velocity(), self.velocity())
+    }
+}
+
+// Elastic collision response between two touching particles. An impulse along the line between
+// their centres exchanges the normal components of momentum, which conserves both total
+// momentum & kinetic energy. Returns false (and changes nothing) if the particles are already
+// separating, or sit exactly on top of each other so there is no line between them.
+pub fn resolve_elastic_collision<T: Scalar, D: Dimension>(a: &mut Particle<T, D>, b: &mut Particle<T, D>) -> bool {
+    resolve_elastic_collision_in(a, b, &Domain::default())
+}
+
+// As resolve_elastic_collision, with the normal taken within `domain` (across the seam if periodic)
+pub fn resolve_elastic_collision_in<T: Scalar, D: Dimension>(a: &mut Particle<T, D>, b: &mut Particle<T, D>, domain: &Domain<T>) -> bool {
+    let normal = domain.separation(a, b);
+    let distance = D::dot(normal, normal).sqrt();
+    if distance == T::ZERO {
+        return false;
+    }
+    let normal = (normal.0 / distance, normal.1 / distance, normal.2 / distance);
+
+    // Speed at which a is approaching b along the normal
+    let approach = D::dot((a.vx - b.vx, a.vy - b.vy, a.vz() - b.vz()), normal);
+    if approach <= T::ZERO {
+        return false;
+    }
+
+    let impulse = T::from_f32(2.0) * approach / (T::ONE / a.mass + T::ONE / b.mass);
+    let (a_share, b_share) = (impulse / a.mass, impulse / b.mass);
+    a.accelerate((-(a_share * normal.0), -(a_share * normal.1), -(a_share * normal.2)));
+    b.accelerate((b_share * normal.0, b_share * normal.1, b_share * normal.2));
+    true
+}
+
+// The furthest apart two of these particles can be & still collide: twice the largest radius.
+// Broad phases size their cells / query regions from this.
+pub fn max_contact_distance<T: Scalar, D: Dimension>(particles: &[Particle<T, D>]) -> T {
+    T::from_f32(2.0) * particles.iter().fold(T::ZERO, |max, particle| max.max(particle.radius))
+}
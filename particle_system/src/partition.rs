@@ -0,0 +1,60 @@
+use std::ops::Range;
+
+// Splitting work between threads.
+//
+// Collision checking covers every unordered pair (i, j) with i < j. Row i owns the pairs
+// (i, i+1) .. (i, n-1), so giving each thread a contiguous range of rows, with the ranges
+// tiling 0..n, tests every pair exactly once. Rows get shorter as i grows, so the ranges are
+// cut by pair count rather than particle count to keep the threads evenly loaded.
+
+// Chunk length that splits `len` items into at most `parts` chunks. Never zero, so it is
+// always safe to pass to chunks()/chunks_mut(), even when there are more threads than particles.
+pub fn chunk_len(len: usize, parts: usize) -> usize {
+    len.div_ceil(parts.max(1)).max(1)
+}
+
+// Split 0..len into at most `parts` contiguous, non-empty ranges of (nearly) equal length
+pub fn chunk_ranges(len: usize, parts: usize) -> Vec<Range<usize>> {
+    let step = chunk_len(len, parts);
+    (0..len).step_by(step).map(|start| start..(start + step).min(len)).collect()
+}
+
+// Number of unordered pairs among `particle_count` particles
+pub fn pair_count(particle_count: usize) -> usize {
+    particle_count * particle_count.saturating_sub(1) / 2
+}
+
+// Number of pairs owned by row `row`
+pub fn row_pair_count(row: usize, particle_count: usize) -> usize {
+    particle_count.saturating_sub(row + 1)
+}
+
+// Split the rows 0..particle_count into at most `parts` contiguous, non-empty ranges holding
+// roughly the same number of pairs each. The ranges tile 0..particle_count in order.
+pub fn partition_pairs(particle_count: usize, parts: usize) -> Vec<Range<usize>> {
+    let parts = parts.max(1);
+    let total = pair_count(particle_count);
+    let mut ranges = Vec::with_capacity(parts.min(particle_count));
+    let mut start = 0;
+    let mut covered = 0;
+
+    for row in 0..particle_count {
+        covered += row_pair_count(row, particle_count);
+        // Close the current range once it reaches its share of the total. The last range
+        // always runs to the end, picking up any rows left over.
+        let target = total * (ranges.len() + 1) / parts;
+        if ranges.len() + 1 < parts && covered >= target && row + 1 < particle_count {
+            ranges.push(start..row + 1);
+            start = row + 1;
+        }
+    }
+    if start < particle_count {
+        ranges.push(start..particle_count);
+    }
+    ranges
+}
+
+// Every pair owned by `rows`, in the order thread_collide tests them
+pub fn pairs_in_rows(rows: Range<usize>, particle_count: usize) -> impl Iterator<Item = (usize, usize)> {
+    rows.flat_map(move |i| (i + 1..particle_count).map(move |j| (i, j)))
+}
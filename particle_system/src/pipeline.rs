@@ -0,0 +1,45 @@
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::{Dimension, Particle, ParticleColumns, ParticleSlice, Scalar};
+
+// How long each side of ParticleSystem::pipelined_move_and_collide_particles spent waiting on the
+// other. Movement stalls when the channel is full (collision checking is the bottleneck);
+// collision stalls when it is empty (movement is).
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PipelineStats {
+    // Time the movement stage spent blocked sending a finished step
+    pub movement_stall: Duration,
+    // Time the collision stage spent waiting for the next step
+    pub collision_stall: Duration,
+    // Steps passed from movement to collision
+    pub snapshots: usize
+}
+
+impl PipelineStats {
+    // The stage that held the other one up for longer
+    pub fn bottleneck(&self) -> &'static str {
+        if self.movement_stall > self.collision_stall { "collision" } else { "movement" }
+    }
+}
+
+// A copy of the particles after one movement step, in the layout the run uses. The collision
+// stage sends each one back once checked, so its allocations are reused for a later step.
+pub(crate) struct Snapshot<T: Scalar, D: Dimension> {
+    // Movement steps taken when it was copied
+    pub step: usize,
+    pub particles: SnapshotParticles<T, D>
+}
+
+pub(crate) enum SnapshotParticles<T: Scalar, D: Dimension> {
+    Structs(Vec<Particle<T, D>>),
+    Columns(Box<ParticleColumns<T, D>>)
+}
+
+impl<T: Scalar, D: Dimension> SnapshotParticles<T, D> {
+    pub fn as_slice(&self) -> ParticleSlice<'_, T, D> {
+        match self {
+            SnapshotParticles::Structs(particles) => ParticleSlice::Structs(particles),
+            SnapshotParticles::Columns(columns) => ParticleSlice::Columns(columns)
+        }
+    }
+}
@@ -0,0 +1,312 @@
+use crate::{Domain, ParticleStore, Scalar};
+
+// Adaptive quadtree over particle positions. Unlike the uniform grid, leaves split only where
+// particles actually are, so clumped distributions don't end up with thousands of particles
+// sharing one cell.
+//
+// The tree keeps track of which leaf holds each particle, so after a movement step `update`
+// only moves the particles that left their leaf instead of rebuilding from scratch.
+//
+// The tree only indexes x & y, so it is for flat domains only: in 3D it would hand every pair
+// stacked in z to the exact test. broad_phase_for & SimulationConfig::validate refuse it there.
+pub struct Quadtree<T: Scalar = f32> {
+    nodes: Vec<Node<T>>,
+    leaf_of: Vec<usize>,
+    // Recycled node slots, freed when four leaves merge back into their parent
+    free: Vec<usize>,
+    capacity: usize,
+    max_depth: usize
+}
+
+// Axis-aligned box, min inclusive & max inclusive
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb<T: Scalar = f32> {
+    pub min: (T, T),
+    pub max: (T, T)
+}
+
+struct Node<T: Scalar> {
+    bounds: Aabb<T>,
+    depth: usize,
+    parent: Option<usize>,
+    kind: NodeKind
+}
+
+enum NodeKind {
+    Leaf(Vec<usize>),
+    // Children in the order (low x, low y), (high x, low y), (low x, high y), (high x, high y)
+    Branch([usize; 4])
+}
+
+// Particles per leaf before it splits
+const DEFAULT_CAPACITY: usize = 8;
+// Stops identical positions (e.g. particles clamped into the same corner) splitting forever
+const DEFAULT_MAX_DEPTH: usize = 16;
+
+impl<T: Scalar> Aabb<T> {
+    pub fn around(centre: (T, T), half_extent: T) -> Aabb<T> {
+        Aabb {
+            min: (centre.0 - half_extent, centre.1 - half_extent),
+            max: (centre.0 + half_extent, centre.1 + half_extent)
+        }
+    }
+    // The xy extent of `domain`
+    pub fn of_domain(domain: &Domain<T>) -> Aabb<T> {
+        Aabb { min: (-domain.half.0, -domain.half.1), max: (domain.half.0, domain.half.1) }
+    }
+    pub fn contains(&self, point: (T, T)) -> bool {
+        point.0 >= self.min.0 && point.0 <= self.max.0 && point.1 >= self.min.1 && point.1 <= self.max.1
+    }
+    pub fn intersects(&self, other: &Aabb<T>) -> bool {
+        self.min.0 <= other.max.0 && self.max.0 >= other.min.0 && self.min.1 <= other.max.1 && self.max.1 >= other.min.1
+    }
+    fn centre(&self) -> (T, T) {
+        let half = T::from_f32(0.5);
+        ((self.min.0 + self.max.0) * half, (self.min.1 + self.max.1) * half)
+    }
+    fn quadrant(&self, quadrant: usize) -> Aabb<T> {
+        let centre = self.centre();
+        let (min_x, max_x) = if quadrant & 1 == 0 { (self.min.0, centre.0) } else { (centre.0, self.max.0) };
+        let (min_y, max_y) = if quadrant & 2 == 0 { (self.min.1, centre.1) } else { (centre.1, self.max.1) };
+        Aabb { min: (min_x, min_y), max: (max_x, max_y) }
+    }
+}
+
+// Offsets to search a region [min, max] at along one axis: itself, plus its image across each
+// wall it hangs over if the axis is periodic. The first `count` entries are used.
+fn seam_shifts<T: Scalar>(min: T, max: T, half: T, periodic: bool) -> ([T; 3], usize) {
+    let mut shifts = [T::ZERO; 3];
+    let mut count = 1;
+    if periodic {
+        if max > half {
+            shifts[count] = T::from_f32(-2.0) * half;
+            count += 1;
+        }
+        if min < -half {
+            shifts[count] = T::from_f32(2.0) * half;
+            count += 1;
+        }
+    }
+    (shifts, count)
+}
+
+// The part of particle i's position the tree indexes
+fn xy<T: Scalar, P: ParticleStore<T> + ?Sized>(particles: &P, i: usize) -> (T, T) {
+    let (x, y, _) = particles.position(i);
+    (x, y)
+}
+
+impl<T: Scalar> Quadtree<T> {
+    // Build a tree covering `bounds` (or the particles, if any lie outside it)
+    pub fn build<P: ParticleStore<T> + ?Sized>(particles: &P, bounds: Aabb<T>) -> Quadtree<T> {
+        Quadtree::with_capacity(particles, bounds, DEFAULT_CAPACITY, DEFAULT_MAX_DEPTH)
+    }
+    pub fn with_capacity<P: ParticleStore<T> + ?Sized>(particles: &P, bounds: Aabb<T>, capacity: usize, max_depth: usize) -> Quadtree<T> {
+        let mut root_bounds = bounds;
+        for i in 0..particles.len() {
+            let (x, y, _) = particles.position(i);
+            root_bounds.min = (root_bounds.min.0.min(x), root_bounds.min.1.min(y));
+            root_bounds.max = (root_bounds.max.0.max(x), root_bounds.max.1.max(y));
+        }
+
+        let mut tree = Quadtree {
+            nodes: vec![Node { bounds: root_bounds, depth: 0, parent: None, kind: NodeKind::Leaf(Vec::new()) }],
+            leaf_of: vec![0; particles.len()],
+            free: Vec::new(),
+            capacity: capacity.max(1),
+            max_depth
+        };
+        for i in 0..particles.len() {
+            tree.insert(particles, i);
+        }
+        tree
+    }
+
+    pub fn bounds(&self) -> Aabb<T> {
+        self.nodes[0].bounds
+    }
+
+    // Re-file every particle that has left its leaf. Falls back to a full rebuild if the particle
+    // count changed or something escaped the root.
+    pub fn update<P: ParticleStore<T> + ?Sized>(&mut self, particles: &P) {
+        let root = self.bounds();
+        if particles.len() != self.leaf_of.len() || (0..particles.len()).any(|i| !root.contains(xy(particles, i))) {
+            *self = Quadtree::with_capacity(particles, root, self.capacity, self.max_depth);
+            return;
+        }
+
+        for i in 0..particles.len() {
+            let leaf = self.leaf_of[i];
+            if self.nodes[leaf].bounds.contains(xy(particles, i)) {
+                continue;
+            }
+            self.remove(i);
+            self.insert(particles, i);
+        }
+    }
+
+    // Particles whose position lies inside `region`
+    pub fn query_region<P: ParticleStore<T> + ?Sized>(&self, region: &Aabb<T>, particles: &P, found: &mut Vec<usize>) {
+        self.for_each_in_region(region, particles, |i| found.push(i));
+    }
+
+    pub fn for_each_in_region<P: ParticleStore<T> + ?Sized>(&self, region: &Aabb<T>, particles: &P, mut f: impl FnMut(usize)) {
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            if !node.bounds.intersects(region) {
+                continue;
+            }
+            match &node.kind {
+                NodeKind::Leaf(items) => {
+                    for &i in items {
+                        if region.contains(xy(particles, i)) {
+                            f(i);
+                        }
+                    }
+                }
+                NodeKind::Branch(children) => stack.extend_from_slice(children)
+            }
+        }
+    }
+
+    // Candidate partners for particle `i` (those close enough on both axes to touch it, given that
+    // no particle is wider than half of `contact_distance`), restricted to j > i so every pair
+    // comes out once. On periodic axes of `domain`, a region hanging over one wall is also
+    // searched at the opposite wall.
+    pub fn for_each_candidate<P: ParticleStore<T> + ?Sized>(&self, i: usize, particles: &P, contact_distance: T, domain: &Domain<T>, mut f: impl FnMut(usize)) {
+        // Slightly wider than needed, so rounding can never drop a pair that exactly touches
+        let reach = (particles.radius(i) + contact_distance * T::from_f32(0.5)) * T::from_f32(1.001);
+        let region = Aabb::around(xy(particles, i), reach);
+        let periodic = domain.periodic();
+        let (x_shifts, x_count) = seam_shifts(region.min.0, region.max.0, domain.half.0, periodic.0);
+        let (y_shifts, y_count) = seam_shifts(region.min.1, region.max.1, domain.half.1, periodic.1);
+        if x_count == 1 && y_count == 1 {
+            self.for_each_in_region(&region, particles, |j| {
+                if j > i {
+                    f(j);
+                }
+            });
+            return;
+        }
+
+        // Near a seam. In a domain narrower than the region the copies overlap, so drop repeats
+        let mut found = Vec::new();
+        for &dx in &x_shifts[..x_count] {
+            for &dy in &y_shifts[..y_count] {
+                let shifted = Aabb { min: (region.min.0 + dx, region.min.1 + dy), max: (region.max.0 + dx, region.max.1 + dy) };
+                self.for_each_in_region(&shifted, particles, |j| {
+                    if j > i {
+                        found.push(j);
+                    }
+                });
+            }
+        }
+        found.sort_unstable();
+        found.dedup();
+        found.into_iter().for_each(f);
+    }
+
+    fn child_for(&self, node: usize, point: (T, T)) -> usize {
+        let NodeKind::Branch(children) = &self.nodes[node].kind else {
+            unreachable!("child_for called on a leaf");
+        };
+        let centre = self.nodes[node].bounds.centre();
+        let quadrant = (point.0 >= centre.0) as usize | (((point.1 >= centre.1) as usize) << 1);
+        children[quadrant]
+    }
+
+    fn insert<P: ParticleStore<T> + ?Sized>(&mut self, particles: &P, i: usize) {
+        let point = xy(particles, i);
+        let mut node = 0;
+        while let NodeKind::Branch(_) = self.nodes[node].kind {
+            node = self.child_for(node, point);
+        }
+
+        let NodeKind::Leaf(items) = &mut self.nodes[node].kind else { unreachable!() };
+        items.push(i);
+        self.leaf_of[i] = node;
+        if items.len() > self.capacity && self.nodes[node].depth < self.max_depth {
+            self.split(particles, node);
+        }
+    }
+
+    fn split<P: ParticleStore<T> + ?Sized>(&mut self, particles: &P, node: usize) {
+        let bounds = self.nodes[node].bounds;
+        let depth = self.nodes[node].depth + 1;
+        let mut children = [0; 4];
+        for (quadrant, child) in children.iter_mut().enumerate() {
+            let new_node = Node { bounds: bounds.quadrant(quadrant), depth, parent: Some(node), kind: NodeKind::Leaf(Vec::new()) };
+            *child = match self.free.pop() {
+                Some(slot) => {
+                    self.nodes[slot] = new_node;
+                    slot
+                }
+                None => {
+                    self.nodes.push(new_node);
+                    self.nodes.len() - 1
+                }
+            };
+        }
+
+        let NodeKind::Leaf(items) = std::mem::replace(&mut self.nodes[node].kind, NodeKind::Branch(children)) else { unreachable!() };
+        // Re-insert from this node down; a child may split again if everything landed in it
+        for i in items {
+            let point = xy(particles, i);
+            let child = self.child_for(node, point);
+            let NodeKind::Leaf(child_items) = &mut self.nodes[child].kind else { unreachable!() };
+            child_items.push(i);
+            self.leaf_of[i] = child;
+        }
+        for child in children {
+            let len = match &self.nodes[child].kind {
+                NodeKind::Leaf(items) => items.len(),
+                NodeKind::Branch(_) => 0
+            };
+            if len > self.capacity && depth < self.max_depth {
+                self.split(particles, child);
+            }
+        }
+    }
+
+    fn remove(&mut self, i: usize) {
+        let leaf = self.leaf_of[i];
+        let NodeKind::Leaf(items) = &mut self.nodes[leaf].kind else { unreachable!() };
+        let position = items.iter().position(|&item| item == i).expect("particle missing from its leaf");
+        items.swap_remove(position);
+
+        // Collapse the parent back into a leaf once its four children hold few enough particles
+        if let Some(parent) = self.nodes[leaf].parent {
+            self.try_merge(parent);
+        }
+    }
+
+    fn try_merge(&mut self, node: usize) {
+        let NodeKind::Branch(children) = self.nodes[node].kind else { return };
+        let mut total = 0;
+        for child in children {
+            match &self.nodes[child].kind {
+                NodeKind::Leaf(items) => total += items.len(),
+                NodeKind::Branch(_) => return
+            }
+        }
+        if total > self.capacity / 2 {
+            return;
+        }
+
+        let mut merged = Vec::with_capacity(total);
+        for child in children {
+            if let NodeKind::Leaf(items) = std::mem::replace(&mut self.nodes[child].kind, NodeKind::Leaf(Vec::new())) {
+                merged.extend(items);
+            }
+            self.free.push(child);
+        }
+        for &i in &merged {
+            self.leaf_of[i] = node;
+        }
+        self.nodes[node].kind = NodeKind::Leaf(merged);
+        if let Some(parent) = self.nodes[node].parent {
+            self.try_merge(parent);
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use rand::{Rng, RngCore};
+
+// Counter-based random streams. Every particle gets its own stream for every step, derived only
+// from the master seed, the particle id & the step number. Which thread happens to move a
+// particle (or how many threads there are) therefore never changes the numbers it draws.
+//
+// The generator is SplitMix64: tiny to seed, so a fresh one per particle per step is cheap, and
+// unlike StdRng its output is fixed, so seeds stay reproducible across rand versions.
+#[derive(Debug, Clone)]
+pub struct ParticleRng {
+    state: u64
+}
+
+const GOLDEN_GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+// Keeps the spawn streams apart from the movement streams of step 0
+const SPAWN_DOMAIN: u64 = 0x5350_4157_4E5F_5F5F;
+
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+impl ParticleRng {
+    pub fn new(seed: u64) -> ParticleRng {
+        ParticleRng { state: mix(seed) }
+    }
+    // Stream used to place particle `id` when the system is populated
+    pub fn for_spawn(seed: u64, id: usize) -> ParticleRng {
+        ParticleRng::new(mix(seed ^ SPAWN_DOMAIN) ^ mix(id as u64))
+    }
+    // Stream used to move particle `id` during `step`
+    pub fn for_step(seed: u64, id: usize, step: usize) -> ParticleRng {
+        ParticleRng::new(mix(mix(seed) ^ id as u64) ^ mix(step as u64 ^ GOLDEN_GAMMA))
+    }
+}
+
+impl RngCore for ParticleRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(GOLDEN_GAMMA);
+        mix(self.state)
+    }
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+}
+
+// One draw from N(0, 1), by Box-Muller. 1 - u keeps the log argument in (0, 1].
+pub(crate) fn standard_normal<R: Rng + ?Sized>(rng: &mut R) -> f32 {
+    let u: f32 = 1.0 - rng.random::<f32>();
+    let v: f32 = rng.random();
+    (-2.0 * u.ln()).sqrt() * (std::f32::consts::TAU * v).cos()
+}
+
+// A uniformly random unit vector: a heading in the xy plane for 2D, or a point on the unit sphere
+// for 3D. The 2D case is a single draw, the same as every 2D heading before 3D support.
+pub(crate) fn random_direction<R: Rng + ?Sized>(rng: &mut R, dimensions: usize) -> (f32, f32, f32) {
+    let heading = rng.random_range(0.0..std::f32::consts::TAU);
+    if dimensions < 3 {
+        return (heading.cos(), heading.sin(), 0.0);
+    }
+    // z uniform in [-1, 1] gives an even spread over the sphere (Archimedes' hat-box theorem)
+    let z: f32 = rng.random_range(-1.0..=1.0);
+    let ring = (1.0 - z * z).sqrt();
+    (ring * heading.cos(), ring * heading.sin(), z)
+}
@@ -0,0 +1,146 @@
+use std::cmp::Ordering;
+use std::fmt::{Debug, Display};
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+use serde::{Deserialize, Serialize};
+use crate::seqlock::atomic::{self, AtomicU32, AtomicU64};
+
+// The floating point type particle positions, velocities & masses are stored & integrated in.
+// Implemented for f32 (the default everywhere) and f64.
+//
+// Config values (bounds, speeds, radii...) stay f32 & are converted with from_f32, and random
+// numbers are always drawn as f32 from the same streams. An f64 run therefore starts from exactly
+// the same particles & gets exactly the same random steps as the f32 run with that seed; only the
+// rounding of everything computed from them differs.
+pub trait Scalar:
+    Copy
+    + Send
+    + Sync
+    + Default
+    + PartialOrd
+    + Debug
+    + Display
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+    + AddAssign
+    + SubAssign
+    + MulAssign
+    + DivAssign
+    + 'static
+{
+    const ZERO: Self;
+    const ONE: Self;
+    const INFINITY: Self;
+    const NEG_INFINITY: Self;
+    const MIN_POSITIVE: Self;
+    const PRECISION: Precision;
+    // Atomic integer as wide as Self, holding its bits (for AtomicPositions)
+    type Atomic: Send + Sync + Debug;
+
+    fn from_f32(value: f32) -> Self;
+    fn from_usize(value: usize) -> Self;
+    fn to_f64(self) -> f64;
+    // Truncates, saturating at 0 & usize::MAX like `as`
+    fn to_usize(self) -> usize;
+
+    fn sqrt(self) -> Self;
+    fn abs(self) -> Self;
+    fn floor(self) -> Self;
+    fn round(self) -> Self;
+    fn powf(self, exponent: Self) -> Self;
+    fn rem_euclid(self, divisor: Self) -> Self;
+    fn min(self, other: Self) -> Self;
+    fn max(self, other: Self) -> Self;
+    fn clamp(self, min: Self, max: Self) -> Self;
+    fn is_finite(self) -> bool;
+    fn total_cmp(&self, other: &Self) -> Ordering;
+
+    fn to_atomic(self) -> Self::Atomic;
+    // Relaxed; AtomicPositions' sequence counters do the ordering
+    fn load_atomic(atomic: &Self::Atomic) -> Self;
+    fn store_atomic(atomic: &Self::Atomic, value: Self);
+}
+
+macro_rules! impl_scalar {
+    ($t:ident, $precision:ident, $atomic:ident) => {
+        impl Scalar for $t {
+            const ZERO: $t = 0.0;
+            const ONE: $t = 1.0;
+            const INFINITY: $t = $t::INFINITY;
+            const NEG_INFINITY: $t = $t::NEG_INFINITY;
+            const MIN_POSITIVE: $t = $t::MIN_POSITIVE;
+            const PRECISION: Precision = Precision::$precision;
+            type Atomic = $atomic;
+
+            fn from_f32(value: f32) -> $t {
+                value as $t
+            }
+            fn from_usize(value: usize) -> $t {
+                value as $t
+            }
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+            fn to_usize(self) -> usize {
+                self as usize
+            }
+            fn sqrt(self) -> $t {
+                $t::sqrt(self)
+            }
+            fn abs(self) -> $t {
+                $t::abs(self)
+            }
+            fn floor(self) -> $t {
+                $t::floor(self)
+            }
+            fn round(self) -> $t {
+                $t::round(self)
+            }
+            fn powf(self, exponent: $t) -> $t {
+                $t::powf(self, exponent)
+            }
+            fn rem_euclid(self, divisor: $t) -> $t {
+                $t::rem_euclid(self, divisor)
+            }
+            fn min(self, other: $t) -> $t {
+                $t::min(self, other)
+            }
+            fn max(self, other: $t) -> $t {
+                $t::max(self, other)
+            }
+            fn clamp(self, min: $t, max: $t) -> $t {
+                $t::clamp(self, min, max)
+            }
+            fn is_finite(self) -> bool {
+                $t::is_finite(self)
+            }
+            fn total_cmp(&self, other: &$t) -> Ordering {
+                $t::total_cmp(self, other)
+            }
+            fn to_atomic(self) -> $atomic {
+                $atomic::new(self.to_bits())
+            }
+            fn load_atomic(bits: &$atomic) -> $t {
+                $t::from_bits(bits.load(atomic::Ordering::Relaxed))
+            }
+            fn store_atomic(bits: &$atomic, value: $t) {
+                bits.store(value.to_bits(), atomic::Ordering::Relaxed);
+            }
+        }
+    };
+}
+
+impl_scalar!(f32, F32, AtomicU32);
+impl_scalar!(f64, F64, AtomicU64);
+
+// Which Scalar a run uses, as chosen on the command line (--precision) or in a scenario file
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum Precision {
+    #[default]
+    F32,
+    F64
+}
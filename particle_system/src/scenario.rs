@@ -0,0 +1,381 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use crate::config::xy_or_xyz;
+use crate::{Boundary, BroadPhaseKind, CounterKind, Dimension, Dynamics, MovementModel, ParticleSystem, Precision, RadiusDistribution, Scalar, SchedulerKind, SimdLevel, SimulationConfig, Species, StorageLayout, ThreeD, TwoD};
+
+// A whole run described in a TOML or JSON file, so scenarios can be kept in version control
+// next to the results they produced. See scenarios/ in the repository root for examples.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    #[serde(default)]
+    pub name: String,
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub strategy: Strategy,
+    // How every particle moves, unless `species` is given
+    #[serde(default)]
+    pub movement: MovementModel,
+    // Several kinds of particle, each with its own movement; replaces `movement`
+    #[serde(default)]
+    pub species: Vec<Species>,
+    #[serde(default)]
+    pub broad_phase: BroadPhaseKind,
+    pub iterations: usize,
+    #[serde(default)]
+    pub log_collisions: bool,
+    // Keep every collision event; needed by the `events` output sink
+    #[serde(default)]
+    pub record_collisions: bool,
+    // `[dynamics]` with `mode = "integrated"` (plus integrator, dt & optionally force) to integrate
+    // velocity & force instead of random walking
+    #[serde(default)]
+    pub dynamics: Dynamics,
+    // Bounce colliding particles off each other with elastic impulses
+    #[serde(default)]
+    pub collision_response: bool,
+    // "f32" (the default) or "f64"
+    #[serde(default)]
+    pub precision: Precision,
+    // "array_of_structs" (the default), "struct_of_arrays" or "shared_atomic"
+    #[serde(default)]
+    pub storage: Option<StorageLayout>,
+    // Widest SIMD collision kernel: "scalar", "sse2", "avx2" or "avx512"; the best the CPU has if left out.
+    // Only struct_of_arrays storage without periodic boundaries is vectorised, so giving a level
+    // other than scalar without `storage` picks struct_of_arrays
+    #[serde(default)]
+    pub simd: Option<SimdLevel>,
+    // How collisions are counted: "mutex", "atomic_relaxed", "atomic_acq_rel", "atomic_seq_cst",
+    // "sharded" (the default) or "per_thread"
+    #[serde(default)]
+    pub counter: CounterKind,
+    // How collision tasks are shared between threads: "static" or "work_stealing" (the default)
+    #[serde(default)]
+    pub scheduler: SchedulerKind,
+    pub particles: ParticleSettings,
+    pub bounds: BoundsSettings,
+    pub threads: ThreadSettings,
+    #[serde(default)]
+    pub output: Vec<OutputSink>
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ParticleSettings {
+    pub count: usize,
+    // A number for one fixed size, or a table describing a distribution (see RadiusDistribution)
+    pub radius: RadiusDistribution,
+    #[serde(default)]
+    pub initial_speed: f32,
+    // [min, max]; every particle has unit mass if left out
+    #[serde(default = "unit_mass")]
+    pub mass: (f32, f32)
+}
+
+fn unit_mass() -> (f32, f32) {
+    (1.0, 1.0)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BoundsSettings {
+    pub x: f32,
+    pub y: f32,
+    // Leave out (or 0) for a 2D run
+    #[serde(default)]
+    pub z: f32,
+    // [x, y] or [x, y, z], each one of "clamp" (the default), "reflect", "periodic" or "absorb"
+    #[serde(default, deserialize_with = "xy_or_xyz")]
+    pub boundary: (Boundary, Boundary, Boundary)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ThreadSettings {
+    pub total: usize,
+    #[serde(default)]
+    pub movement: Option<usize>,
+    // Steps movement may run ahead of collision checking, for the pipelined strategy
+    #[serde(default)]
+    pub pipeline_depth: Option<usize>
+}
+
+// Which ParticleSystem method(s) drive the run
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategy {
+    // move_particles_loop followed by a single collide_particles (Q1 / Q2)
+    #[default]
+    MoveThenCollide,
+    // move_and_collide_particles, checking collisions every iteration (Q3)
+    MoveAndCollide,
+    // pipelined_move_and_collide_particles: as move_and_collide, with the stages joined by a channel
+    Pipelined
+}
+
+impl Strategy {
+    // As written in scenario files
+    pub fn name(self) -> &'static str {
+        match self {
+            Strategy::MoveThenCollide => "move_then_collide",
+            Strategy::MoveAndCollide => "move_and_collide",
+            Strategy::Pipelined => "pipelined"
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum OutputSink {
+    // Print the run summary to stdout
+    Stdout,
+    // Write the run summary to a file, as JSON if the extension is .json & TOML otherwise
+    Summary { path: PathBuf },
+    // Write the final particle positions as CSV (id,x,y,z,radius; z is 0 in 2D)
+    Positions { path: PathBuf },
+    // Write every collision event as CSV (step,thread,a,b,ax,ay,az,bx,by,bz)
+    Events { path: PathBuf }
+}
+
+// What a run produced, written to the output sinks
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunSummary {
+    pub name: String,
+    pub seed: u64,
+    pub strategy: Strategy,
+    #[serde(default)]
+    pub precision: Precision,
+    // Particles left at the end, after any were absorbed
+    pub particle_count: usize,
+    pub iterations: usize,
+    pub collisions: usize,
+    #[serde(default)]
+    pub absorbed: usize,
+    pub duration_ms: u64,
+    // Time each pipeline stage spent waiting on the other; only for the pipelined strategy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub movement_stall_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collision_stall_ms: Option<u64>,
+    // Time each collision thread spent running tasks, & how uneven that was (see SchedulerStats)
+    #[serde(default)]
+    pub collision_busy_ms: Vec<u64>,
+    #[serde(default)]
+    pub collision_imbalance: f64
+}
+
+#[derive(Debug, Error)]
+pub enum ScenarioError {
+    #[error("could not read or write {path}: {source}")]
+    Io { path: PathBuf, source: std::io::Error },
+    #[error("unsupported scenario format for {0} (expected a .toml or .json file)")]
+    UnsupportedFormat(PathBuf),
+    #[error("invalid TOML scenario: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("invalid JSON scenario: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("could not serialise summary as TOML: {0}")]
+    TomlSerialise(#[from] toml::ser::Error),
+    #[error("invalid value for `{field}`: {reason}")]
+    Invalid { field: &'static str, reason: String }
+}
+
+enum Format {
+    Toml,
+    Json
+}
+impl Format {
+    fn from_path(path: &Path) -> Option<Format> {
+        match path.extension()?.to_str()? {
+            "toml" => Some(Format::Toml),
+            "json" => Some(Format::Json),
+            _ => None
+        }
+    }
+}
+
+impl Scenario {
+    pub fn load(path: impl AsRef<Path>) -> Result<Scenario, ScenarioError> {
+        let path = path.as_ref();
+        let format = Format::from_path(path).ok_or_else(|| ScenarioError::UnsupportedFormat(path.to_path_buf()))?;
+        let text = fs::read_to_string(path).map_err(|source| ScenarioError::Io { path: path.to_path_buf(), source })?;
+        let scenario = match format {
+            Format::Toml => Scenario::from_toml(&text)?,
+            Format::Json => Scenario::from_json(&text)?
+        };
+        Ok(scenario)
+    }
+    pub fn from_toml(text: &str) -> Result<Scenario, ScenarioError> {
+        let scenario: Scenario = toml::from_str(text)?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+    pub fn from_json(text: &str) -> Result<Scenario, ScenarioError> {
+        let scenario: Scenario = serde_json::from_str(text)?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    // The same checks as the command line (SimulationConfig::validate), reported under the names
+    // the scenario file uses, plus the ones only a scenario can get wrong
+    pub fn validate(&self) -> Result<(), ScenarioError> {
+        fn invalid(field: &'static str, reason: impl Into<String>) -> Result<(), ScenarioError> {
+            Err(ScenarioError::Invalid { field, reason: reason.into() })
+        }
+        if let Err(error) = self.config().validate() {
+            let field = match error.field {
+                "particle_count" => "particles.count",
+                "particle_radius" => "particles.radius",
+                "initial_speed" => "particles.initial_speed",
+                "mass_range" => "particles.mass",
+                // Without a [[species]] table every particle moves by the top level `movement`
+                "species.movement" if self.species.is_empty() => "movement",
+                "num_iterations" => "iterations",
+                "thread_count" => "threads.total",
+                "movement_thread_count" => "threads.movement",
+                "pipeline_depth" => "threads.pipeline_depth",
+                field => field
+            };
+            return invalid(field, error.reason);
+        }
+        let overlapped = matches!(self.strategy, Strategy::MoveAndCollide | Strategy::Pipelined);
+        match (overlapped, self.threads.movement) {
+            (true, None) => {
+                return invalid("threads.movement", format!("is required by the {} strategy", self.strategy.name()));
+            }
+            (true, Some(movement)) if movement >= self.threads.total => {
+                return invalid("threads.movement", format!("must be between 1 and threads.total - 1 ({}), got {}", self.threads.total - 1, movement));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub fn config(&self) -> SimulationConfig {
+        let defaults = SimulationConfig::default();
+        SimulationConfig {
+            particle_count: self.particles.count,
+            bounds: (self.bounds.x, self.bounds.y, self.bounds.z),
+            boundaries: self.bounds.boundary,
+            particle_radius: self.particles.radius,
+            species: if self.species.is_empty() { vec![Species { weight: 1.0, movement: self.movement }] } else { self.species.clone() },
+            initial_speed: self.particles.initial_speed,
+            mass_range: self.particles.mass,
+            dynamics: self.dynamics,
+            collision_response: self.collision_response,
+            num_iterations: self.iterations,
+            thread_count: self.threads.total,
+            movement_thread_count: self.threads.movement.unwrap_or(defaults.movement_thread_count),
+            pipeline_depth: self.threads.pipeline_depth.unwrap_or(defaults.pipeline_depth),
+            log_collisions: self.log_collisions,
+            record_collisions: self.record_collisions || self.output.iter().any(|sink| matches!(sink, OutputSink::Events { .. })),
+            broad_phase: self.broad_phase,
+            precision: self.precision,
+            storage: self.storage.or(StorageLayout::implied_by(self.simd)).unwrap_or(defaults.storage),
+            simd: self.simd.unwrap_or(defaults.simd),
+            counter: self.counter,
+            scheduler: self.scheduler,
+            seed: self.seed
+        }
+    }
+
+    // Build a populated ParticleSystem for this scenario in precision T & dimension D (run picks T
+    // from `precision` & D from the bounds' depth)
+    pub fn build<T: Scalar, D: Dimension>(&self) -> ParticleSystem<T, D> {
+        let mut particle_system = ParticleSystem::with_config(self.config());
+        particle_system.spawn_particles();
+        particle_system
+    }
+
+    // Build the system in the scenario's precision & dimension, run the chosen strategy & write every output sink
+    pub fn run(&self) -> Result<RunSummary, ScenarioError> {
+        match (self.precision, self.config().is_3d()) {
+            (Precision::F32, false) => self.run_in::<f32, TwoD>(),
+            (Precision::F32, true) => self.run_in::<f32, ThreeD>(),
+            (Precision::F64, false) => self.run_in::<f64, TwoD>(),
+            (Precision::F64, true) => self.run_in::<f64, ThreeD>()
+        }
+    }
+
+    fn run_in<T: Scalar, D: Dimension>(&self) -> Result<RunSummary, ScenarioError> {
+        let mut particle_system = self.build::<T, D>();
+
+        let start_time = time::Instant::now();
+        let mut stalls = None;
+        match self.strategy {
+            Strategy::MoveThenCollide => {
+                particle_system.move_particles_loop();
+                particle_system.collide_particles();
+            }
+            Strategy::MoveAndCollide => particle_system.move_and_collide_particles(),
+            Strategy::Pipelined => stalls = Some(particle_system.pipelined_move_and_collide_particles())
+        }
+        let duration = time::Instant::now().duration_since(start_time);
+
+        let summary = RunSummary {
+            name: self.name.clone(),
+            seed: particle_system.seed,
+            strategy: self.strategy,
+            precision: T::PRECISION,
+            particle_count: particle_system.particles.len(),
+            iterations: self.iterations,
+            collisions: particle_system.collision_count(),
+            absorbed: particle_system.absorbed_count(),
+            duration_ms: duration.as_millis() as u64,
+            movement_stall_ms: stalls.map(|stats| stats.movement_stall.as_millis() as u64),
+            collision_stall_ms: stalls.map(|stats| stats.collision_stall.as_millis() as u64),
+            collision_busy_ms: particle_system.scheduler_stats().workers.iter().map(|stats| stats.busy.as_millis() as u64).collect(),
+            collision_imbalance: particle_system.scheduler_stats().imbalance()
+        };
+        for sink in &self.output {
+            sink.write(&summary, &particle_system)?;
+        }
+        Ok(summary)
+    }
+}
+
+impl OutputSink {
+    pub fn write<T: Scalar, D: Dimension>(&self, summary: &RunSummary, particle_system: &ParticleSystem<T, D>) -> Result<(), ScenarioError> {
+        match self {
+            OutputSink::Stdout => {
+                println!("{:#?}", summary);
+                Ok(())
+            }
+            OutputSink::Summary { path } => {
+                let text = match Format::from_path(path) {
+                    Some(Format::Json) => serde_json::to_string_pretty(summary)?,
+                    _ => toml::to_string_pretty(summary)?
+                };
+                write_file(path, text.as_bytes())
+            }
+            OutputSink::Positions { path } => {
+                let mut csv = String::from("id,x,y,z,radius\n");
+                for particle in &particle_system.particles {
+                    csv.push_str(&format!("{},{},{},{},{}\n", particle.id, particle.x, particle.y, particle.z(), particle.radius));
+                }
+                write_file(path, csv.as_bytes())
+            }
+            OutputSink::Events { path } => {
+                let mut csv = String::from("step,thread,a,b,ax,ay,az,bx,by,bz\n");
+                for event in particle_system.collision_events() {
+                    let (a, b) = (event.a_position, event.b_position);
+                    csv.push_str(&format!("{},{},{},{},{},{},{},{},{},{}\n", event.step, event.thread_id, event.a, event.b, a.0, a.1, a.2, b.0, b.1, b.2));
+                }
+                write_file(path, csv.as_bytes())
+            }
+        }
+    }
+}
+
+fn write_file(path: &Path, contents: &[u8]) -> Result<(), ScenarioError> {
+    let io_error = |source| ScenarioError::Io { path: path.to_path_buf(), source };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(io_error)?;
+    }
+    let mut file = fs::File::create(path).map_err(io_error)?;
+    file.write_all(contents).map_err(io_error)
+}
@@ -0,0 +1,156 @@
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::Mutex;
+use std::time::{self, Duration};
+use crate::SchedulerKind;
+
+// Sharing one collision pass between its threads.
+//
+// Brute force row i tests the pairs (i, i+1) .. (i, n-1), so early rows are far more work than
+// late ones, and even ranges cut by pair count (partition_pairs) come out uneven once the threads
+// are competing for cores, the broad phase is uneven, or particles cluster. With one task per
+// thread the pass lasts as long as its slowest thread while the others sit idle.
+//
+// With work stealing the pass is split into TASKS_PER_THREAD tasks per thread, and each thread
+// gets a queue holding a contiguous block of them. It works through its own queue from the
+// front; once that is empty it takes tasks from the back of the others' (the ones their owners
+// would get to last) until every queue is empty. No task ever adds more, so a thread that finds
+// nothing anywhere is done.
+
+// How finely work stealing splits a pass. More tasks even out the threads better, at the cost of
+// more queue locking & broad phase bookkeeping per pass
+pub const TASKS_PER_THREAD: usize = 8;
+
+// The tasks of one collision pass, queued per thread
+#[derive(Debug)]
+pub struct TaskQueues {
+    queues: Vec<Mutex<VecDeque<Range<usize>>>>,
+    stealing: bool,
+    // What each thread did this pass, filled in as it finishes
+    stats: Vec<Mutex<WorkerStats>>
+}
+
+impl TaskQueues {
+    // Deal `tasks` (in the order the broad phase gave them) to `workers` threads in contiguous blocks
+    pub fn new(tasks: Vec<Range<usize>>, workers: usize, kind: SchedulerKind) -> TaskQueues {
+        let workers = workers.max(1);
+        let block = tasks.len().div_ceil(workers).max(1);
+        let mut queues: Vec<VecDeque<Range<usize>>> = (0..workers).map(|_| VecDeque::new()).collect();
+        for (index, task) in tasks.into_iter().enumerate() {
+            queues[index / block].push_back(task);
+        }
+        TaskQueues {
+            queues: queues.into_iter().map(Mutex::new).collect(),
+            stealing: kind == SchedulerKind::WorkStealing,
+            stats: (0..workers).map(|_| Mutex::new(WorkerStats::default())).collect()
+        }
+    }
+
+    // Number of tasks the broad phase should split a pass for `workers` threads into
+    pub fn task_count(workers: usize, kind: SchedulerKind) -> usize {
+        match kind {
+            SchedulerKind::Static => workers,
+            SchedulerKind::WorkStealing => workers * TASKS_PER_THREAD
+        }
+    }
+
+    pub fn workers(&self) -> usize {
+        self.queues.len()
+    }
+
+    // Run `run` on tasks as thread `worker` until there are none left that it may take. `run`
+    // returns how many collisions the task found
+    pub fn work(&self, worker: usize, mut run: impl FnMut(Range<usize>) -> usize) {
+        let mut stats = WorkerStats::default();
+        loop {
+            // Popped before the match, so this queue's lock is released before trying the others
+            let own = self.queues[worker].lock().unwrap().pop_front();
+            let (task, stolen) = match own {
+                Some(task) => (task, false),
+                None => match self.steal(worker) {
+                    Some(task) => (task, true),
+                    None => break
+                }
+            };
+            let start_time = time::Instant::now();
+            stats.collisions += run(task);
+            stats.busy += start_time.elapsed();
+            stats.tasks += 1;
+            stats.stolen += stolen as usize;
+        }
+        *self.stats[worker].lock().unwrap() = stats;
+    }
+
+    // The last task of the first other queue with any left, starting from the next thread along
+    // so thieves don't all pile onto the same victim
+    fn steal(&self, thief: usize) -> Option<Range<usize>> {
+        if !self.stealing {
+            return None;
+        }
+        let workers = self.workers();
+        (1..workers).find_map(|offset| self.queues[(thief + offset) % workers].lock().unwrap().pop_back())
+    }
+
+    // What each thread did, once the pass is over
+    pub fn stats(&self) -> Vec<WorkerStats> {
+        self.stats.iter().map(|stats| *stats.lock().unwrap()).collect()
+    }
+}
+
+// One collision thread's share of the work
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct WorkerStats {
+    // Time spent running tasks (not waiting for the pass to end)
+    pub busy: Duration,
+    pub tasks: usize,
+    // How many of those were taken from another thread's queue
+    pub stolen: usize,
+    pub collisions: usize
+}
+
+// Per collision thread totals over every pass a system has run
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SchedulerStats {
+    pub workers: Vec<WorkerStats>,
+    pub passes: usize
+}
+
+impl SchedulerStats {
+    pub fn add_pass(&mut self, pass: &[WorkerStats]) {
+        self.add(pass);
+        self.passes += 1;
+    }
+
+    // Fold in the totals of another run, e.g. the pipeline's collision stage
+    pub fn merge(&mut self, other: &SchedulerStats) {
+        self.add(&other.workers);
+        self.passes += other.passes;
+    }
+
+    fn add(&mut self, workers: &[WorkerStats]) {
+        if self.workers.len() < workers.len() {
+            self.workers.resize(workers.len(), WorkerStats::default());
+        }
+        for (total, stats) in self.workers.iter_mut().zip(workers) {
+            total.busy += stats.busy;
+            total.tasks += stats.tasks;
+            total.stolen += stats.stolen;
+            total.collisions += stats.collisions;
+        }
+    }
+
+    // The busiest thread's busy time over the average: 1.0 when the work was shared out evenly,
+    // up to the thread count when one thread did all of it
+    pub fn imbalance(&self) -> f64 {
+        let busy: Vec<f64> = self.workers.iter().map(|stats| stats.busy.as_secs_f64()).collect();
+        let mean = busy.iter().sum::<f64>() / busy.len().max(1) as f64;
+        if mean > 0.0 { busy.iter().fold(0.0, |max: f64, &busy| max.max(busy)) / mean } else { 1.0 }
+    }
+
+    // e.g. "4 ms, 5 ms, 4 ms (imbalance 1.10, 12 tasks stolen)"
+    pub fn busy_summary(&self) -> String {
+        let busy: Vec<String> = self.workers.iter().map(|stats| format!("{} ms", stats.busy.as_millis())).collect();
+        let stolen: usize = self.workers.iter().map(|stats| stats.stolen).sum();
+        format!("{} (imbalance {:.2}, {} tasks stolen)", busy.join(", "), self.imbalance(), stolen)
+    }
+}
@@ -0,0 +1,125 @@
+use std::marker::PhantomData;
+use crate::{Dimension, Particle, ParticleStore, Scalar, TwoD};
+
+// loom's model checked versions with `--cfg loom` (see tests/loom.rs), the real ones otherwise
+#[cfg(loom)]
+pub(crate) use loom::{hint, sync::atomic};
+#[cfg(not(loom))]
+pub(crate) use std::{hint, sync::atomic};
+use atomic::{fence, AtomicU32, Ordering};
+
+// Particle positions that movement threads write while collision threads read them, with no lock
+// & no copy. Each coordinate is an atomic holding its bits (Scalar::Atomic), so no single load can
+// tear, and each particle has a sequence counter so its x, y & z are always read as one position:
+//
+// - a write makes the counter odd, stores the coordinates, then makes it even again
+// - a read loads the counter, the coordinates, then the counter again. If it was odd, or changed
+//   in between, a write overlapped the read & it tries again
+//
+// Only one thread may write a given particle at a time (each movement thread writes its own chunk);
+// any number may read. Radii & ids don't change during a run, so they are plain columns. In 2D
+// there is no z to share, so that column stays empty.
+#[derive(Debug)]
+pub struct AtomicPositions<T: Scalar = f32, D: Dimension = TwoD> {
+    // Even when the particle's position is settled, odd while it is being written
+    sequence: Vec<AtomicU32>,
+    x: Vec<T::Atomic>,
+    y: Vec<T::Atomic>,
+    z: Vec<T::Atomic>,
+    radius: Vec<T>,
+    id: Vec<usize>,
+    dimension: PhantomData<D>
+}
+
+// Not derived: that would need Default for the atomics too
+impl<T: Scalar, D: Dimension> Default for AtomicPositions<T, D> {
+    fn default() -> Self {
+        AtomicPositions { sequence: Vec::new(), x: Vec::new(), y: Vec::new(), z: Vec::new(), radius: Vec::new(), id: Vec::new(), dimension: PhantomData }
+    }
+}
+
+impl<T: Scalar, D: Dimension> AtomicPositions<T, D> {
+    pub fn from_particles(particles: &[Particle<T, D>]) -> AtomicPositions<T, D> {
+        let mut positions = AtomicPositions::default();
+        positions.load(particles);
+        positions
+    }
+
+    // Replace the contents with `particles`' positions, radii & ids
+    pub fn load(&mut self, particles: &[Particle<T, D>]) {
+        self.sequence.clear();
+        for column in [&mut self.x, &mut self.y, &mut self.z] {
+            column.clear();
+        }
+        self.radius.clear();
+        self.id.clear();
+        for particle in particles {
+            self.sequence.push(AtomicU32::new(0));
+            self.x.push(particle.x.to_atomic());
+            self.y.push(particle.y.to_atomic());
+            if D::AXES == 3 {
+                self.z.push(particle.z().to_atomic());
+            }
+            self.radius.push(particle.radius);
+            self.id.push(particle.id);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.id.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.id.is_empty()
+    }
+
+    // Particle `i`'s position as of some complete write, never a mix of two
+    pub fn read(&self, i: usize) -> (T, T, T) {
+        let sequence = &self.sequence[i];
+        loop {
+            let before = sequence.load(Ordering::Acquire);
+            if before.is_multiple_of(2) {
+                let z = if D::AXES == 3 { T::load_atomic(&self.z[i]) } else { T::ZERO };
+                let position = (T::load_atomic(&self.x[i]), T::load_atomic(&self.y[i]), z);
+                // Keeps the coordinate loads above the second counter load
+                fence(Ordering::Acquire);
+                if sequence.load(Ordering::Relaxed) == before {
+                    return position;
+                }
+            }
+            hint::spin_loop();
+        }
+    }
+
+    // Publish particle `i`'s new position (z is dropped in 2D). Only its owning thread may call this
+    pub fn write(&self, i: usize, (x, y, z): (T, T, T)) {
+        let sequence = &self.sequence[i];
+        let before = sequence.load(Ordering::Relaxed);
+        sequence.store(before.wrapping_add(1), Ordering::Relaxed);
+        // Keeps the coordinate stores below the odd counter
+        fence(Ordering::Release);
+        T::store_atomic(&self.x[i], x);
+        T::store_atomic(&self.y[i], y);
+        if D::AXES == 3 {
+            T::store_atomic(&self.z[i], z);
+        }
+        sequence.store(before.wrapping_add(2), Ordering::Release);
+    }
+}
+
+impl<T: Scalar, D: Dimension> ParticleStore<T> for AtomicPositions<T, D> {
+    type Dimension = D;
+
+    fn len(&self) -> usize {
+        AtomicPositions::len(self)
+    }
+    fn position(&self, i: usize) -> (T, T, T) {
+        self.read(i)
+    }
+    fn radius(&self, i: usize) -> T {
+        self.radius[i]
+    }
+    // Only what collision events use: velocity, mass & species aren't shared
+    fn particle(&self, i: usize) -> Particle<T, D> {
+        Particle::at(self.read(i), self.id[i]).with_radius(self.radius[i])
+    }
+}
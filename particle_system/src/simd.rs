@@ -0,0 +1,207 @@
+use std::ops::Range;
+use std::sync::OnceLock;
+use serde::{Deserialize, Serialize};
+use crate::{Dimension, ParticleColumns};
+
+// Vectorised contact tests for f32 ParticleColumns: one particle against 4 (SSE2), 8 (AVX2) or
+// 16 (AVX-512) others per instruction. The arithmetic is the scalar test's, in the same order
+// ((dx² + dy²) + dz² <= (ri + rj)², with no dz in 2D, separate multiplies & adds, no FMA), so every
+// lane rounds exactly like the scalar code and the same pairs are found, in the same order.
+//
+// Only used without periodic axes: the minimum-image rounding has no matching vector instruction.
+// Other x86_64 CPUs & architectures, f64, periodic domains and the other storage layouts use the
+// scalar loops (which is why asking for a level picks StructOfArrays, see StorageLayout::implied_by).
+
+// Widest kernel to use. Ordered narrowest first, so a request is capped at what the CPU has with min.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "snake_case")]
+pub enum SimdLevel {
+    // One pair at a time
+    Scalar,
+    // 4 lanes
+    Sse2,
+    // 8 lanes
+    Avx2,
+    // 16 lanes
+    Avx512
+}
+
+impl SimdLevel {
+    // The widest level this CPU supports, detected on first use
+    pub fn detect() -> SimdLevel {
+        static DETECTED: OnceLock<SimdLevel> = OnceLock::new();
+        *DETECTED.get_or_init(|| {
+            #[cfg(target_arch = "x86_64")]
+            {
+                if is_x86_feature_detected!("avx512f") {
+                    return SimdLevel::Avx512;
+                }
+                if is_x86_feature_detected!("avx2") {
+                    return SimdLevel::Avx2;
+                }
+                if is_x86_feature_detected!("sse2") {
+                    return SimdLevel::Sse2;
+                }
+            }
+            SimdLevel::Scalar
+        })
+    }
+    // This level if the CPU has it, otherwise the widest one it does have
+    pub fn supported(self) -> SimdLevel {
+        self.min(SimdLevel::detect())
+    }
+    pub fn lanes(self) -> usize {
+        match self {
+            SimdLevel::Scalar => 1,
+            SimdLevel::Sse2 => 4,
+            SimdLevel::Avx2 => 8,
+            SimdLevel::Avx512 => 16
+        }
+    }
+}
+
+// Detected, so runs use the fastest kernel unless told otherwise
+impl Default for SimdLevel {
+    fn default() -> Self {
+        SimdLevel::detect()
+    }
+}
+
+// Call `f(j)` for every j in `others` that touches particle `i`, in increasing order
+pub(crate) fn contacts_in_range<D: Dimension>(level: SimdLevel, columns: &ParticleColumns<f32, D>, i: usize, others: Range<usize>, f: &mut dyn FnMut(usize)) {
+    match level.supported() {
+        // SAFETY (all three): supported() only returns levels the CPU was detected to have
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Avx512 => unsafe { x86::range_avx512(columns, i, others, f) },
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Avx2 => unsafe { x86::range_avx2(columns, i, others, f) },
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Sse2 => unsafe { x86::range_sse2(columns, i, others, f) },
+        _ => range_scalar(columns, i, others, f)
+    }
+}
+
+// Call `f(j)` for every j > i in `candidates` that touches particle `i`, in the order given.
+// The candidates' coordinates are gathered into lanes, then tested like contacts_in_range.
+pub(crate) fn later_contacts_among<D: Dimension>(level: SimdLevel, columns: &ParticleColumns<f32, D>, i: usize, candidates: &[usize], f: &mut dyn FnMut(usize)) {
+    match level.supported() {
+        // SAFETY (all three): as above
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Avx512 => unsafe { x86::among_avx512(columns, i, candidates, f) },
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Avx2 => unsafe { x86::among_avx2(columns, i, candidates, f) },
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Sse2 => unsafe { x86::among_sse2(columns, i, candidates, f) },
+        _ => among_scalar(columns, i, candidates, f)
+    }
+}
+
+// The scalar test, also used for whatever is left over after the last full vector
+fn touches<D: Dimension>(columns: &ParticleColumns<f32, D>, i: usize, j: usize) -> bool {
+    let separation = (columns.x[j] - columns.x[i], columns.y[j] - columns.y[i], D::depth(columns.z[j]) - D::depth(columns.z[i]));
+    let contact = columns.radius[i] + columns.radius[j];
+    D::dot(separation, separation) <= contact * contact
+}
+
+fn range_scalar<D: Dimension>(columns: &ParticleColumns<f32, D>, i: usize, others: Range<usize>, f: &mut dyn FnMut(usize)) {
+    for j in others {
+        if touches(columns, i, j) {
+            f(j);
+        }
+    }
+}
+
+fn among_scalar<D: Dimension>(columns: &ParticleColumns<f32, D>, i: usize, candidates: &[usize], f: &mut dyn FnMut(usize)) {
+    for &j in candidates {
+        if j > i && touches(columns, i, j) {
+            f(j);
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+    use std::ops::Range;
+    use super::{among_scalar, range_scalar};
+    use crate::{Dimension, ParticleColumns};
+
+    // One kernel pair per instruction set. `$le` compares two vectors lane by lane & gives the
+    // lanes where a <= b (false for NaN, like the scalar <=) as the low bits of a u32. In 2D there
+    // is no z column (Dimension::depths is None), so dz is left out.
+    macro_rules! kernels {
+        ($feature:literal, $range:ident, $among:ident, $lanes:literal, $loadu:ident, $set1:ident, $sub:ident, $mul:ident, $add:ident, |$a:ident, $b:ident| $le:expr) => {
+            #[target_feature(enable = $feature)]
+            pub(super) fn $range<D: Dimension>(columns: &ParticleColumns<f32, D>, i: usize, others: Range<usize>, f: &mut dyn FnMut(usize)) {
+                let depths = D::depths(&columns.z);
+                let (xi, yi, ri) = ($set1(columns.x[i]), $set1(columns.y[i]), $set1(columns.radius[i]));
+                let zi = $set1(depths.map_or(0.0, |z| z[i]));
+                // Slicing checks every column is long enough, so the loads below stay in bounds
+                let (x, y, r) = (&columns.x[others.clone()], &columns.y[others.clone()], &columns.radius[others.clone()]);
+                let z = depths.map(|z| &z[others.clone()]);
+                let mut k = 0;
+                while k + $lanes <= x.len() {
+                    // SAFETY: k + $lanes <= the length of all four slices
+                    let (xj, yj, rj) = unsafe { ($loadu(x.as_ptr().add(k)), $loadu(y.as_ptr().add(k)), $loadu(r.as_ptr().add(k))) };
+                    let (dx, dy) = ($sub(xj, xi), $sub(yj, yi));
+                    let mut distance = $add($mul(dx, dx), $mul(dy, dy));
+                    if let Some(z) = z {
+                        // SAFETY: as above
+                        let dz = $sub(unsafe { $loadu(z.as_ptr().add(k)) }, zi);
+                        distance = $add(distance, $mul(dz, dz));
+                    }
+                    let contact = $add(ri, rj);
+                    let (a, b) = (distance, $mul(contact, contact));
+                    let mut hits: u32 = { let ($a, $b) = (a, b); $le };
+                    while hits != 0 {
+                        f(others.start + k + hits.trailing_zeros() as usize);
+                        hits &= hits - 1;
+                    }
+                    k += $lanes;
+                }
+                range_scalar(columns, i, others.start + k..others.end, f);
+            }
+
+            #[target_feature(enable = $feature)]
+            pub(super) fn $among<D: Dimension>(columns: &ParticleColumns<f32, D>, i: usize, candidates: &[usize], f: &mut dyn FnMut(usize)) {
+                let depths = D::depths(&columns.z);
+                let (xi, yi, ri) = ($set1(columns.x[i]), $set1(columns.y[i]), $set1(columns.radius[i]));
+                let zi = $set1(depths.map_or(0.0, |z| z[i]));
+                let mut batches = candidates.chunks_exact($lanes);
+                for batch in &mut batches {
+                    let mut lanes = [[0.0f32; $lanes]; 4];
+                    for (k, &j) in batch.iter().enumerate() {
+                        (lanes[0][k], lanes[1][k], lanes[3][k]) = (columns.x[j], columns.y[j], columns.radius[j]);
+                        if let Some(z) = depths {
+                            lanes[2][k] = z[j];
+                        }
+                    }
+                    // SAFETY: each array holds exactly $lanes floats
+                    let (xj, yj, zj, rj) = unsafe { ($loadu(lanes[0].as_ptr()), $loadu(lanes[1].as_ptr()), $loadu(lanes[2].as_ptr()), $loadu(lanes[3].as_ptr())) };
+                    let (dx, dy) = ($sub(xj, xi), $sub(yj, yi));
+                    let mut distance = $add($mul(dx, dx), $mul(dy, dy));
+                    if depths.is_some() {
+                        let dz = $sub(zj, zi);
+                        distance = $add(distance, $mul(dz, dz));
+                    }
+                    let contact = $add(ri, rj);
+                    let (a, b) = (distance, $mul(contact, contact));
+                    let mut hits: u32 = { let ($a, $b) = (a, b); $le };
+                    while hits != 0 {
+                        let j = batch[hits.trailing_zeros() as usize];
+                        if j > i {
+                            f(j);
+                        }
+                        hits &= hits - 1;
+                    }
+                }
+                among_scalar(columns, i, batches.remainder(), f);
+            }
+        };
+    }
+
+    kernels!("sse2", range_sse2, among_sse2, 4, _mm_loadu_ps, _mm_set1_ps, _mm_sub_ps, _mm_mul_ps, _mm_add_ps, |a, b| _mm_movemask_ps(_mm_cmple_ps(a, b)) as u32);
+    kernels!("avx2", range_avx2, among_avx2, 8, _mm256_loadu_ps, _mm256_set1_ps, _mm256_sub_ps, _mm256_mul_ps, _mm256_add_ps, |a, b| _mm256_movemask_ps(_mm256_cmp_ps::<_CMP_LE_OQ>(a, b)) as u32);
+    kernels!("avx512f", range_avx512, among_avx512, 16, _mm512_loadu_ps, _mm512_set1_ps, _mm512_sub_ps, _mm512_mul_ps, _mm512_add_ps, |a, b| _mm512_cmp_ps_mask::<_CMP_LE_OQ>(a, b) as u32);
+}
@@ -0,0 +1,387 @@
+use std::any::Any;
+use std::ops::Range;
+use crate::simd::{contacts_in_range, later_contacts_among};
+use crate::{AtomicPositions, Dimension, Domain, Particle, Scalar, SimdLevel, TwoD};
+
+// Structure-of-arrays copy of a particle list: one contiguous column per field, in the same order
+// as the particles. The collision kernels only read x, y, z & radius, so they stream through 4
+// tightly packed columns instead of striding over whole particles (ids, velocities, masses...).
+// In 2D the z & vz columns hold Flat, so there is nothing in them to stream.
+//
+// ParticleSystem runs in one of these when config.storage is StructOfArrays (see StorageLayout);
+// `particles` stays the public state & is refreshed from the columns when each run returns.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParticleColumns<T: Scalar = f32, D: Dimension = TwoD> {
+    pub x: Vec<T>,
+    pub y: Vec<T>,
+    pub z: Vec<D::Depth<T>>,
+    pub vx: Vec<T>,
+    pub vy: Vec<T>,
+    pub vz: Vec<D::Depth<T>>,
+    pub mass: Vec<T>,
+    pub radius: Vec<T>,
+    pub id: Vec<usize>,
+    pub species: Vec<usize>,
+    // Contact test kernel for f32 columns (see simd.rs); the best the CPU has unless set lower
+    pub simd: SimdLevel
+}
+
+// One thread's share of a ParticleColumns, from chunks_mut. Ids & species never change during a run.
+pub struct ColumnsMut<'a, T: Scalar, D: Dimension = TwoD> {
+    pub x: &'a mut [T],
+    pub y: &'a mut [T],
+    pub z: &'a mut [D::Depth<T>],
+    pub vx: &'a mut [T],
+    pub vy: &'a mut [T],
+    pub vz: &'a mut [D::Depth<T>],
+    pub mass: &'a [T],
+    pub radius: &'a [T],
+    pub id: &'a [usize],
+    pub species: &'a [usize]
+}
+
+impl<T: Scalar, D: Dimension> ParticleColumns<T, D> {
+    pub fn from_particles(particles: &[Particle<T, D>]) -> ParticleColumns<T, D> {
+        let mut columns = ParticleColumns::default();
+        columns.load(particles);
+        columns
+    }
+
+    // Replace the contents with `particles`, reusing the columns' allocations
+    pub fn load(&mut self, particles: &[Particle<T, D>]) {
+        self.clear();
+        for particle in particles {
+            self.push(particle);
+        }
+    }
+
+    // Write the columns back out as particles, reusing `particles`' allocation
+    pub fn store(&self, particles: &mut Vec<Particle<T, D>>) {
+        particles.clear();
+        particles.extend((0..self.len()).map(|i| self.get(i)));
+    }
+
+    // Make this an exact copy of `other`, reusing the columns' allocations (unlike clone)
+    pub fn copy_from(&mut self, other: &ParticleColumns<T, D>) {
+        for (column, source) in [&mut self.x, &mut self.y, &mut self.vx, &mut self.vy, &mut self.mass, &mut self.radius].into_iter().zip([&other.x, &other.y, &other.vx, &other.vy, &other.mass, &other.radius]) {
+            column.clone_from(source);
+        }
+        self.z.clone_from(&other.z);
+        self.vz.clone_from(&other.vz);
+        self.id.clone_from(&other.id);
+        self.species.clone_from(&other.species);
+        self.simd = other.simd;
+    }
+
+    pub fn push(&mut self, particle: &Particle<T, D>) {
+        self.x.push(particle.x);
+        self.y.push(particle.y);
+        self.z.push(particle.z);
+        self.vx.push(particle.vx);
+        self.vy.push(particle.vy);
+        self.vz.push(particle.vz);
+        self.mass.push(particle.mass);
+        self.radius.push(particle.radius);
+        self.id.push(particle.id);
+        self.species.push(particle.species);
+    }
+
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    pub fn len(&self) -> usize {
+        self.id.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.id.is_empty()
+    }
+
+    // Particle `i`, gathered from every column
+    pub fn get(&self, i: usize) -> Particle<T, D> {
+        Particle {
+            x: self.x[i],
+            y: self.y[i],
+            z: self.z[i],
+            id: self.id[i],
+            vx: self.vx[i],
+            vy: self.vy[i],
+            vz: self.vz[i],
+            mass: self.mass[i],
+            radius: self.radius[i],
+            species: self.species[i]
+        }
+    }
+    // Scatter the position & velocity of `particle` back into row `i`
+    pub fn set_motion(&mut self, i: usize, particle: &Particle<T, D>) {
+        (self.x[i], self.y[i], self.z[i]) = (particle.x, particle.y, particle.z);
+        (self.vx[i], self.vy[i], self.vz[i]) = (particle.vx, particle.vy, particle.vz);
+    }
+
+    // Keep only the rows whose particle passes `keep`, in order (Vec::retain for every column)
+    pub fn retain(&mut self, mut keep: impl FnMut(&Particle<T, D>) -> bool) {
+        let mut kept = 0;
+        for i in 0..self.len() {
+            let particle = self.get(i);
+            if keep(&particle) {
+                self.set_motion(kept, &particle);
+                (self.mass[kept], self.radius[kept], self.id[kept], self.species[kept]) = (particle.mass, particle.radius, particle.id, particle.species);
+                kept += 1;
+            }
+        }
+        self.truncate(kept);
+    }
+
+    fn truncate(&mut self, len: usize) {
+        for column in [&mut self.x, &mut self.y, &mut self.vx, &mut self.vy, &mut self.mass, &mut self.radius] {
+            column.truncate(len);
+        }
+        self.z.truncate(len);
+        self.vz.truncate(len);
+        self.id.truncate(len);
+        self.species.truncate(len);
+    }
+
+    // Split into consecutive runs of `chunk_len` rows (the last may be shorter), like slice::chunks_mut
+    pub fn chunks_mut(&mut self, chunk_len: usize) -> Vec<ColumnsMut<'_, T, D>> {
+        let chunk_len = chunk_len.max(1);
+        let count = self.len().div_ceil(chunk_len);
+        let (mut x, mut y, mut z) = (self.x.chunks_mut(chunk_len), self.y.chunks_mut(chunk_len), self.z.chunks_mut(chunk_len));
+        let (mut vx, mut vy, mut vz) = (self.vx.chunks_mut(chunk_len), self.vy.chunks_mut(chunk_len), self.vz.chunks_mut(chunk_len));
+        let (mut mass, mut radius) = (self.mass.chunks(chunk_len), self.radius.chunks(chunk_len));
+        let (mut id, mut species) = (self.id.chunks(chunk_len), self.species.chunks(chunk_len));
+        (0..count).map(|_| ColumnsMut {
+            x: x.next().unwrap(),
+            y: y.next().unwrap(),
+            z: z.next().unwrap(),
+            vx: vx.next().unwrap(),
+            vy: vy.next().unwrap(),
+            vz: vz.next().unwrap(),
+            mass: mass.next().unwrap(),
+            radius: radius.next().unwrap(),
+            id: id.next().unwrap(),
+            species: species.next().unwrap()
+        }).collect()
+    }
+}
+
+impl<T: Scalar, D: Dimension> ColumnsMut<'_, T, D> {
+    pub fn len(&self) -> usize {
+        self.id.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.id.is_empty()
+    }
+    pub fn get(&self, i: usize) -> Particle<T, D> {
+        Particle {
+            x: self.x[i],
+            y: self.y[i],
+            z: self.z[i],
+            id: self.id[i],
+            vx: self.vx[i],
+            vy: self.vy[i],
+            vz: self.vz[i],
+            mass: self.mass[i],
+            radius: self.radius[i],
+            species: self.species[i]
+        }
+    }
+    pub fn set_motion(&mut self, i: usize, particle: &Particle<T, D>) {
+        (self.x[i], self.y[i], self.z[i]) = (particle.x, particle.y, particle.z);
+        (self.vx[i], self.vy[i], self.vz[i]) = (particle.vx, particle.vy, particle.vz);
+    }
+}
+
+// Read access to what the broad phases & collision kernels need, whichever layout the particles
+// are stored in. Kernels are written once, generic over this, and compiled for each layout.
+pub trait ParticleStore<T: Scalar>: Sync {
+    // TwoD or ThreeD; position's z is always 0 in 2D
+    type Dimension: Dimension;
+
+    fn len(&self) -> usize;
+    fn position(&self, i: usize) -> (T, T, T);
+    fn radius(&self, i: usize) -> T;
+    // The whole particle; only for recording & logging collisions, so it can be slow
+    fn particle(&self, i: usize) -> Particle<T, Self::Dimension>;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    // Particle::collide_in between rows i & j
+    fn collide_in(&self, i: usize, j: usize, domain: &Domain<T>) -> bool {
+        let separation = domain.separation_between(self.position(i), self.position(j));
+        let contact = self.radius(i) + self.radius(j);
+        Self::Dimension::dot(separation, separation) <= contact * contact
+    }
+    // Call `f(j)` for every j in `others` that collides with i
+    fn for_each_contact(&self, i: usize, others: Range<usize>, domain: &Domain<T>, mut f: impl FnMut(usize)) {
+        for j in others {
+            if self.collide_in(i, j, domain) {
+                f(j);
+            }
+        }
+    }
+    // Call `f(j)` for every j > i in `candidates` that collides with i
+    fn for_each_later_contact(&self, i: usize, candidates: &[usize], domain: &Domain<T>, mut f: impl FnMut(usize)) {
+        for &j in candidates {
+            if j > i && self.collide_in(i, j, domain) {
+                f(j);
+            }
+        }
+    }
+}
+
+impl<T: Scalar, D: Dimension> ParticleStore<T> for [Particle<T, D>] {
+    type Dimension = D;
+
+    fn len(&self) -> usize {
+        <[Particle<T, D>]>::len(self)
+    }
+    fn position(&self, i: usize) -> (T, T, T) {
+        self[i].position()
+    }
+    fn radius(&self, i: usize) -> T {
+        self[i].radius
+    }
+    fn particle(&self, i: usize) -> Particle<T, D> {
+        self[i]
+    }
+    fn collide_in(&self, i: usize, j: usize, domain: &Domain<T>) -> bool {
+        self[i].collide_in(&self[j], domain)
+    }
+}
+
+impl<T: Scalar, D: Dimension> ParticleStore<T> for Vec<Particle<T, D>> {
+    type Dimension = D;
+
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+    fn position(&self, i: usize) -> (T, T, T) {
+        self[i].position()
+    }
+    fn radius(&self, i: usize) -> T {
+        self[i].radius
+    }
+    fn particle(&self, i: usize) -> Particle<T, D> {
+        self[i]
+    }
+    fn collide_in(&self, i: usize, j: usize, domain: &Domain<T>) -> bool {
+        self[i].collide_in(&self[j], domain)
+    }
+}
+
+impl<T: Scalar, D: Dimension> ParticleStore<T> for ParticleColumns<T, D> {
+    type Dimension = D;
+
+    fn len(&self) -> usize {
+        ParticleColumns::len(self)
+    }
+    fn position(&self, i: usize) -> (T, T, T) {
+        (self.x[i], self.y[i], D::depth(self.z[i]))
+    }
+    fn radius(&self, i: usize) -> T {
+        self.radius[i]
+    }
+    fn particle(&self, i: usize) -> Particle<T, D> {
+        self.get(i)
+    }
+    // The brute force inner loop. f32 columns without periodic axes go to the SIMD kernels;
+    // otherwise zipped column slices, with no bounds checks & nothing loaded that the test
+    // doesn't use. Without periodic axes the separation is a plain difference.
+    fn for_each_contact(&self, i: usize, others: Range<usize>, domain: &Domain<T>, mut f: impl FnMut(usize)) {
+        if !domain.any_periodic() {
+            if let Some(columns) = (self as &dyn Any).downcast_ref::<ParticleColumns<f32, D>>() {
+                return contacts_in_range(self.simd, columns, i, others, &mut f);
+            }
+        }
+        let (xi, yi, zi, ri) = (self.x[i], self.y[i], D::depth(self.z[i]), self.radius[i]);
+        let start = others.start;
+        let rows = self.x[others.clone()].iter().zip(&self.y[others.clone()]).zip(&self.z[others.clone()]).zip(&self.radius[others]);
+        if domain.any_periodic() {
+            for (k, (((&x, &y), &z), &r)) in rows.enumerate() {
+                let separation = domain.separation_between((xi, yi, zi), (x, y, D::depth(z)));
+                let contact = ri + r;
+                if D::dot(separation, separation) <= contact * contact {
+                    f(start + k);
+                }
+            }
+        } else {
+            for (k, (((&x, &y), &z), &r)) in rows.enumerate() {
+                let separation = (x - xi, y - yi, D::depth(z) - zi);
+                let contact = ri + r;
+                if D::dot(separation, separation) <= contact * contact {
+                    f(start + k);
+                }
+            }
+        }
+    }
+    // The grid's inner loop, over one run of neighbouring cells
+    fn for_each_later_contact(&self, i: usize, candidates: &[usize], domain: &Domain<T>, mut f: impl FnMut(usize)) {
+        if !domain.any_periodic() {
+            if let Some(columns) = (self as &dyn Any).downcast_ref::<ParticleColumns<f32, D>>() {
+                return later_contacts_among(self.simd, columns, i, candidates, &mut f);
+            }
+        }
+        for &j in candidates {
+            if j > i && self.collide_in(i, j, domain) {
+                f(j);
+            }
+        }
+    }
+}
+
+// The particles handed to a BroadPhase, in any layout. Broad phases match on this once per
+// call & run a kernel compiled for that layout, so there is no per-particle dispatch.
+#[derive(Debug, Copy, Clone)]
+pub enum ParticleSlice<'a, T: Scalar = f32, D: Dimension = TwoD> {
+    Structs(&'a [Particle<T, D>]),
+    Columns(&'a ParticleColumns<T, D>),
+    Shared(&'a AtomicPositions<T, D>)
+}
+
+impl<T: Scalar, D: Dimension> ParticleSlice<'_, T, D> {
+    pub fn len(&self) -> usize {
+        match self {
+            ParticleSlice::Structs(particles) => particles.len(),
+            ParticleSlice::Columns(columns) => columns.len(),
+            ParticleSlice::Shared(positions) => positions.len()
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<'a, T: Scalar, D: Dimension> From<&'a [Particle<T, D>]> for ParticleSlice<'a, T, D> {
+    fn from(particles: &'a [Particle<T, D>]) -> Self {
+        ParticleSlice::Structs(particles)
+    }
+}
+impl<'a, T: Scalar, D: Dimension> From<&'a Vec<Particle<T, D>>> for ParticleSlice<'a, T, D> {
+    fn from(particles: &'a Vec<Particle<T, D>>) -> Self {
+        ParticleSlice::Structs(particles)
+    }
+}
+impl<'a, T: Scalar, D: Dimension> From<&'a ParticleColumns<T, D>> for ParticleSlice<'a, T, D> {
+    fn from(columns: &'a ParticleColumns<T, D>) -> Self {
+        ParticleSlice::Columns(columns)
+    }
+}
+impl<'a, T: Scalar, D: Dimension> From<&'a AtomicPositions<T, D>> for ParticleSlice<'a, T, D> {
+    fn from(positions: &'a AtomicPositions<T, D>) -> Self {
+        ParticleSlice::Shared(positions)
+    }
+}
+
+// Evaluate `$body` with `$particles` bound to the concrete layout inside a ParticleSlice
+macro_rules! per_layout {
+    ($slice:expr, $particles:ident => $body:expr) => {
+        match $slice {
+            $crate::ParticleSlice::Structs($particles) => $body,
+            $crate::ParticleSlice::Columns($particles) => $body,
+            $crate::ParticleSlice::Shared($particles) => $body
+        }
+    };
+}
+pub(crate) use per_layout;
@@ -0,0 +1,170 @@
+use std::cmp::Ordering;
+use crate::{ParticleStore, Scalar};
+
+// Sweep-and-prune broad phase along the x axis. Each particle is the interval [x - r, x + r] of
+// its own radius, so two intervals overlap exactly when the particles are within the sum of their
+// radii of each other on x. The 2n interval endpoints are kept sorted; sweeping them in order with
+// a set of "open" intervals gives every overlapping pair once. Only x is swept & y is left to the
+// exact test, so it is for flat domains only; broad_phase_for & SimulationConfig::validate refuse
+// it in 3D.
+//
+// A particle moves at most one unit per step, so the order barely changes between steps.
+// `update` therefore repairs the previous order with an insertion sort, which is close to O(n)
+// on nearly sorted input, instead of sorting from scratch.
+#[derive(Clone)]
+pub struct SweepAndPrune<T: Scalar = f32> {
+    endpoints: Vec<Endpoint<T>>,
+    particle_count: usize,
+    // Extra intervals for a periodic x axis (see build_periodic): interval particle_count + k is a
+    // copy of particle ghosts[k]
+    ghosts: Vec<u32>,
+    // Result of the last sweep()
+    pairs: Vec<(u32, u32)>
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Endpoint<T: Scalar> {
+    value: T,
+    // Interval index: the particle's position in the slice (not Particle::id, which differs once
+    // particles have been absorbed), or particle_count + k for ghost k
+    index: u32,
+    is_min: bool
+}
+
+impl<T: Scalar> Endpoint<T> {
+    // Min endpoints sort before max endpoints at the same value, so intervals that only touch
+    // still count as overlapping (Particle::collide uses <=)
+    fn cmp(&self, other: &Endpoint<T>) -> Ordering {
+        self.value.total_cmp(&other.value).then(other.is_min.cmp(&self.is_min))
+    }
+}
+
+// Slightly wider than the radius, so rounding can never drop a pair that exactly touches
+fn half_width<T: Scalar>(radius: T) -> T {
+    radius * T::from_f32(1.001)
+}
+
+impl<T: Scalar> SweepAndPrune<T> {
+    pub fn build<P: ParticleStore<T> + ?Sized>(particles: &P) -> SweepAndPrune<T> {
+        let mut sweep_and_prune = SweepAndPrune {
+            endpoints: Vec::with_capacity(particles.len() * 2),
+            particle_count: particles.len(),
+            ghosts: Vec::new(),
+            pairs: Vec::new()
+        };
+        for i in 0..particles.len() {
+            sweep_and_prune.push_interval(i as u32, particles.position(i).0, half_width(particles.radius(i)));
+        }
+        sweep_and_prune.endpoints.sort_unstable_by(Endpoint::cmp);
+        sweep_and_prune
+    }
+
+    // As build, for an x axis that wraps round between -half_x & half_x. Particles within
+    // `contact_distance` of the left wall get a ghost interval one domain width to the right, where
+    // it overlaps partners just inside the right wall. The ghosts change from step to step, so a
+    // periodic sweep is rebuilt every pass instead of repaired.
+    pub fn build_periodic<P: ParticleStore<T> + ?Sized>(particles: &P, half_x: T, contact_distance: T) -> SweepAndPrune<T> {
+        let mut sweep_and_prune = SweepAndPrune::build(particles);
+        for i in 0..particles.len() {
+            let x = particles.position(i).0;
+            if x < -half_x + contact_distance {
+                let ghost = (particles.len() + sweep_and_prune.ghosts.len()) as u32;
+                sweep_and_prune.ghosts.push(i as u32);
+                sweep_and_prune.push_interval(ghost, x + T::from_f32(2.0) * half_x, half_width(particles.radius(i)));
+            }
+        }
+        sweep_and_prune.endpoints.sort_unstable_by(Endpoint::cmp);
+        sweep_and_prune
+    }
+
+    fn push_interval(&mut self, index: u32, x: T, half_width: T) {
+        self.endpoints.push(Endpoint { value: x - half_width, index, is_min: true });
+        self.endpoints.push(Endpoint { value: x + half_width, index, is_min: false });
+    }
+
+    // Refresh every endpoint from the new positions & restore the order. Returns the number of
+    // swaps the insertion sort needed, a measure of how much the order changed.
+    //
+    // In dense systems a single step can carry a particle past hundreds of others, and insertion
+    // sort degrades towards O(n^2). Once the swaps pass a couple per endpoint, the repair gives
+    // up & sorts from scratch instead, so a step is never much slower than a rebuild.
+    pub fn update<P: ParticleStore<T> + ?Sized>(&mut self, particles: &P) -> usize {
+        if particles.len() != self.particle_count || !self.ghosts.is_empty() {
+            *self = SweepAndPrune::build(particles);
+            return 0;
+        }
+
+        for endpoint in self.endpoints.iter_mut() {
+            let i = endpoint.index as usize;
+            let (x, half_width) = (particles.position(i).0, half_width(particles.radius(i)));
+            endpoint.value = if endpoint.is_min { x - half_width } else { x + half_width };
+        }
+
+        let len = self.endpoints.len();
+        let swap_budget = len * 2;
+        let mut swaps = 0;
+        for i in 1..len {
+            let mut j = i;
+            while j > 0 && self.endpoints[j - 1].cmp(&self.endpoints[j]) == Ordering::Greater {
+                self.endpoints.swap(j - 1, j);
+                j -= 1;
+                swaps += 1;
+            }
+            if swaps > swap_budget {
+                self.endpoints.sort_unstable_by(Endpoint::cmp);
+                break;
+            }
+        }
+        swaps
+    }
+
+    // Sweep the current order, keeping the overlapping pairs for pairs()
+    pub fn sweep(&mut self) {
+        let mut pairs = std::mem::take(&mut self.pairs);
+        self.overlapping_pairs(&mut pairs);
+        self.pairs = pairs;
+    }
+    pub fn pairs(&self) -> &[(u32, u32)] {
+        &self.pairs
+    }
+
+    // Every pair whose x intervals overlap, as (lower index, higher index), each exactly once
+    pub fn overlapping_pairs(&self, pairs: &mut Vec<(u32, u32)>) {
+        pairs.clear();
+        // `open` holds the intervals started but not yet ended; `slot` finds a particle in it
+        let mut open: Vec<u32> = Vec::new();
+        let mut slot = vec![usize::MAX; self.particle_count + self.ghosts.len()];
+        for endpoint in &self.endpoints {
+            let index = endpoint.index;
+            if endpoint.is_min {
+                let particle = self.particle_of(index);
+                for &other in &open {
+                    let other = self.particle_of(other);
+                    if other != particle {
+                        pairs.push((particle.min(other), particle.max(other)));
+                    }
+                }
+                slot[index as usize] = open.len();
+                open.push(index);
+            } else {
+                let position = slot[index as usize];
+                open.swap_remove(position);
+                if let Some(&moved) = open.get(position) {
+                    slot[moved as usize] = position;
+                }
+            }
+        }
+        // In a very narrow domain a pair can overlap both directly & through a ghost
+        if !self.ghosts.is_empty() {
+            pairs.sort_unstable();
+            pairs.dedup();
+        }
+    }
+
+    fn particle_of(&self, interval: u32) -> u32 {
+        match (interval as usize).checked_sub(self.particle_count) {
+            Some(ghost) => self.ghosts[ghost],
+            None => interval
+        }
+    }
+}
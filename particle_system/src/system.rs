@@ -0,0 +1,675 @@
+use std::mem;
+use std::ops::Range;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{self, Duration};
+use std::sync::Arc;
+use rand::{rng, Rng, RngCore};
+use scoped_threadpool::Pool;
+use crate::partition::chunk_len;
+use crate::pipeline::{Snapshot, SnapshotParticles};
+use crate::rng::random_direction;
+use crate::threads::report_workers;
+use crate::{broad_phase_for, collision_worker, counter_for, max_contact_distance, merge_event_buffers, motion_models_for, resolve_elastic_collision_in, thread_main, thread_main_columns, thread_main_shared, Aabb, AtomicPositions, BroadPhase, BruteForce, CollisionCounter, CollisionEvent, Dimension, EventBuffer, MotionModel, Particle, ParticleColumns, ParticleRng, ParticleSlice, ParticleStore, PipelineStats, Quadtree, Scalar, SchedulerStats, SimulationConfig, StorageLayout, TaskQueues, TwoD, WorkerStats};
+
+// Generic over the precision particles are stored & moved in (see Scalar) and the dimension they
+// move in (see Dimension). ParticleSystem::new gives the usual f32 2D system;
+// ParticleSystem::<f64>::with_config a double precision one & ParticleSystem::<f32, ThreeD> a 3D one.
+pub struct ParticleSystem<T: Scalar = f32, D: Dimension = TwoD> {
+    pub config: SimulationConfig,
+    pub particles: Vec<Particle<T, D>>,
+    // Working copy of `particles` while a run is in progress with config.storage StructOfArrays.
+    // Loaded when each run starts & stored back into `particles` when it returns
+    columns: ParticleColumns<T, D>,
+    // Back buffers for move_and_collide_particles, which writes each step into these while the
+    // collision threads read the last one; then the two are swapped
+    particles_back: Vec<Particle<T, D>>,
+    columns_back: ParticleColumns<T, D>,
+    // Positions published for the collision threads with config.storage SharedAtomic, which move
+    // `particles` in place & write each new position here as they go
+    shared: AtomicPositions<T, D>,
+    // What the collision threads count into; config.counter picks the backend
+    pub collision_counter: Arc<dyn CollisionCounter>,
+    // Master seed for every random stream. Taken from the config, or drawn once if none was given
+    pub seed: u64,
+    // Number of movement steps taken so far
+    pub step: usize,
+    // How collision candidates are found. Kept between passes so it can update rather than rebuild
+    broad_phase: Box<dyn BroadPhase<T, D>>,
+    // One per species in config.species, indexed by Particle::species
+    motion_models: Vec<Box<dyn MotionModel<T, D>>>,
+    // Only built for region queries
+    quadtree: Option<Quadtree<T>>,
+    // Every collision found so far, when config.record_collisions is set
+    collision_events: Vec<CollisionEvent<T>>,
+    // How busy each collision thread has been, over every pass so far
+    scheduler_stats: SchedulerStats,
+    // Particles removed by absorbing walls so far
+    absorbed: usize
+}
+impl<T: Scalar, D: Dimension> Default for ParticleSystem<T, D> {
+    fn default() -> Self {
+        Self::with_config(SimulationConfig::default())
+    }
+}
+impl ParticleSystem {
+    // Single precision, using the broad phase selected by config.broad_phase. config.precision is
+    // left to the caller, since it decides the type; see with_config.
+    pub fn new(config: SimulationConfig)-> ParticleSystem {
+        ParticleSystem::with_config(config)
+    }
+}
+impl<T: Scalar, D: Dimension> ParticleSystem<T, D> {
+    // As new, in precision T & dimension D
+    pub fn with_config(config: SimulationConfig) -> ParticleSystem<T, D> {
+        let broad_phase = broad_phase_for(&config);
+        ParticleSystem::with_broad_phase(config, broad_phase)
+    }
+    // Uses a custom broad phase, ignoring config.broad_phase
+    pub fn with_broad_phase(config: SimulationConfig, broad_phase: Box<dyn BroadPhase<T, D>>) -> ParticleSystem<T, D> {
+        // The config's bounds have to agree with the type: a flat domain for TwoD, a depth for ThreeD
+        assert_eq!(config.is_3d(), D::AXES == 3, "bounds {:?} don't match a {}D system", config.bounds, D::AXES);
+        let seed = config.seed.unwrap_or_else(|| rng().next_u64());
+        let motion_models = motion_models_for(&config.species);
+        let collision_counter = counter_for(config.counter);
+        ParticleSystem {
+            config,
+            particles: Vec::new(),
+            columns: ParticleColumns::default(),
+            particles_back: Vec::new(),
+            columns_back: ParticleColumns::default(),
+            shared: AtomicPositions::default(),
+            collision_counter,
+            seed,
+            step: 0,
+            broad_phase,
+            motion_models,
+            quadtree: None,
+            collision_events: Vec::new(),
+            scheduler_stats: SchedulerStats::default(),
+            absorbed: 0
+        }
+    }
+    pub fn broad_phase(&self) -> &dyn BroadPhase<T, D> {
+        &*self.broad_phase
+    }
+    pub fn set_broad_phase(&mut self, broad_phase: Box<dyn BroadPhase<T, D>>) {
+        self.broad_phase = broad_phase;
+    }
+    pub fn motion_model(&self, species: usize) -> &dyn MotionModel<T, D> {
+        &*self.motion_models[species]
+    }
+    // Replace how one species moves, e.g. with a VectorFieldDrift, which config files can't describe
+    pub fn set_motion_model(&mut self, species: usize, motion_model: Box<dyn MotionModel<T, D>>) {
+        self.motion_models[species] = motion_model;
+    }
+    // Count into a custom backend rather than the one config.counter picked. Counts so far stay in the old one
+    pub fn set_collision_counter(&mut self, collision_counter: Arc<dyn CollisionCounter>) {
+        self.collision_counter = collision_counter;
+    }
+    pub fn spawn_particles(&mut self) {
+        let count = self.config.particle_count;
+        let bounds_half = self.config.bounds_half();
+        println!("Creating {} particles (seed {})...", count, self.seed);
+        for _ in 0..count {
+            // Generate random positions within bounds
+            // Ids keep counting up past any absorbed particles, so they stay unique & in order
+            let id = self.particles.last().map_or(0, |p| p.id + 1);
+            let mut rng = ParticleRng::for_spawn(self.seed, id);
+            let x = rng.random_range(-bounds_half.0..bounds_half.0);
+            let y = rng.random_range(-bounds_half.1..bounds_half.1);
+            let z = if D::AXES == 3 { rng.random_range(-bounds_half.2..bounds_half.2) } else { 0.0 };
+
+            // Random heading & speed, mass and size. Drawn after the position so seeds place particles the same as before
+            let heading = random_direction(&mut rng, D::AXES);
+            let speed = self.config.initial_speed * rng.random::<f32>();
+            let (mass_min, mass_max) = self.config.mass_range;
+            let mass = if mass_max > mass_min { rng.random_range(mass_min..=mass_max) } else { mass_min };
+            let radius = self.config.particle_radius.sample(&mut rng);
+            let species = self.pick_species(&mut rng);
+
+            // Create instance with generated position. Everything is drawn in f32, so both
+            // precisions spawn the same particles
+            let scalar = T::from_f32;
+            let particle = Particle::at((scalar(x), scalar(y), scalar(z)), id)
+                .with_velocity(scalar(speed * heading.0), scalar(speed * heading.1))
+                .with_mass(scalar(mass))
+                .with_radius(scalar(radius))
+                .with_species(species);
+            let particle = Particle { vz: D::to_depth(scalar(speed * heading.2)), ..particle };
+
+            // Announce position
+            // println!("Created particle {} with position ({}, {})", particle.id, particle.x, particle.y);
+
+            // Add instance to system
+            self.particles.push(particle);
+        }
+    }
+    // Weighted by Species::weight. A single species needs no draw, so existing seeds are unaffected
+    fn pick_species(&self, rng: &mut ParticleRng) -> usize {
+        let species = &self.config.species;
+        if species.len() <= 1 {
+            return 0;
+        }
+        let total: f32 = species.iter().map(|s| s.weight).sum();
+        let mut target = rng.random::<f32>() * total;
+        for (index, s) in species.iter().enumerate() {
+            if target < s.weight {
+                return index;
+            }
+            target -= s.weight;
+        }
+        species.len() - 1
+    }
+    pub fn collision_count(&self) -> usize {
+        self.collision_counter.total()
+    }
+    pub fn absorbed_count(&self) -> usize {
+        self.absorbed
+    }
+    // Per collision thread busy time, tasks run & tasks stolen over every pass so far
+    pub fn scheduler_stats(&self) -> &SchedulerStats {
+        &self.scheduler_stats
+    }
+    // Recorded collisions in step order, then particle id order within a step
+    pub fn collision_events(&self) -> &[CollisionEvent<T>] {
+        &self.collision_events
+    }
+    pub fn take_collision_events(&mut self) -> Vec<CollisionEvent<T>> {
+        std::mem::take(&mut self.collision_events)
+    }
+    pub fn total_momentum(&self) -> (f64, f64, f64) {
+        self.particles.iter().fold((0.0, 0.0, 0.0), |total, p| {
+            let momentum = p.momentum();
+            (total.0 + momentum.0.to_f64(), total.1 + momentum.1.to_f64(), total.2 + momentum.2.to_f64())
+        })
+    }
+    pub fn kinetic_energy(&self) -> f64 {
+        self.particles.iter().map(|p| p.kinetic_energy().to_f64()).sum()
+    }
+    // Potential energy in config.dynamics' force field (zero for the random walk)
+    pub fn potential_energy(&self) -> f64 {
+        let force = self.config.dynamics.force();
+        self.particles.iter().map(|p| force.potential_energy(p).to_f64()).sum()
+    }
+    pub fn total_energy(&self) -> f64 {
+        self.kinetic_energy() + self.potential_energy()
+    }
+    pub fn move_particles_loop(&mut self) {
+        let num_iterations = self.config.num_iterations;
+        let thread_count = self.config.thread_count;
+        let particles_per_thread = chunk_len(self.particles.len(), thread_count);
+        println!("Moving {} particles {} times across {} threads...", self.particles.len(), num_iterations, thread_count);
+        let start_time = time::Instant::now();
+
+        // Initialise threads
+        let mut pool = Pool::new(thread_count as u32);
+        self.load_columns();
+        self.move_pass(&mut pool, particles_per_thread, self.step..self.step + num_iterations);
+        self.store_columns();
+
+        let duration = time::Instant::now().duration_since(start_time);
+        println!("Took {} ms to move {} particles {} times", duration.as_millis(), self.particles.len(), num_iterations);
+    }
+    pub fn collide_particles(&mut self) {
+        let thread_count = self.config.thread_count;
+        let mut collision_pool = Pool::new(thread_count as u32);
+
+        println!("Checking collisions...");
+        let start_time = time::Instant::now();
+
+        self.load_columns();
+        self.collide_pass(&mut collision_pool, thread_count);
+        self.store_columns();
+
+        let duration = time::Instant::now().duration_since(start_time);
+        println!("Took {} ms to check collisions. Detected {} collisions", duration.as_millis(), self.collision_count());
+        println!("Collision threads were busy for {}", self.scheduler_stats.busy_summary());
+    }
+    // Movement & collision threads run at the same time, on two copies of the particles. While the
+    // movement threads write step n + 1 into the back buffer, the collision threads check the front
+    // buffer, which holds step n & isn't touched until they are all done. The pool finishing is the
+    // barrier: then the buffers swap & the next step starts. Every step is still checked once, with
+    // the same tasks & in the same order as moving then colliding, so the result is the same.
+    //
+    // With SharedAtomic storage there is no back buffer: the collision threads read the published
+    // positions while the movement threads overwrite them, one particle at a time. Nothing is
+    // copied, but a pass sees each particle at step n or n + 1 (never half of each), so which step
+    // a pair is counted in varies from run to run. Every pair is checked (see double_buffered_pass),
+    // so a pair is found whenever it touches at the positions the pass happens to see.
+    //
+    // With collision_response the next move needs the velocities the collision pass sets, so the
+    // two can't overlap; movement & collision then take turns instead.
+    pub fn move_and_collide_particles(&mut self) {
+        let num_iterations = self.config.num_iterations;
+        let num_threads_movement = self.config.movement_thread_count;
+        let num_threads_collision = self.config.collision_thread_count();
+        let num_particles_total = self.particles.len();
+        let num_particles_movement = chunk_len(num_particles_total, num_threads_movement);
+
+        let start_time = time::Instant::now();
+
+        // Set up thread pools
+        let mut pool_movement = Pool::new(num_threads_movement as u32);
+        let mut pool_collision = Pool::new(num_threads_collision as u32);
+
+        // Iteratively run threads
+        self.load_columns();
+        if self.config.collision_response {
+            for _ in 0..num_iterations {
+                // Run movement threads
+                // println!("Moving {} particles across {} threads...", self.particles.len(), num_threads_movement);
+                self.move_pass(&mut pool_movement, num_particles_movement, self.step..self.step + 1);
+
+                // Run collision threads
+                // println!("Checking collisions across {} threads...", num_threads_collision);
+                self.collide_pass(&mut pool_collision, num_threads_collision);
+            }
+        } else if num_iterations > 0 {
+            // One pool with room for both sets of threads
+            let mut pool = Pool::new((num_threads_movement + num_threads_collision) as u32);
+            // The first step has nothing before it to check, and the last one is checked on its own
+            self.move_pass(&mut pool_movement, num_particles_movement, self.step..self.step + 1);
+            for _ in 1..num_iterations {
+                self.double_buffered_pass(&mut pool, num_particles_movement, num_threads_collision);
+            }
+            self.collide_pass(&mut pool_collision, num_threads_collision);
+        }
+        self.store_columns();
+
+        let duration = time::Instant::now().duration_since(start_time);
+        println!("Took {} ms to move {} particles & check collisions over {} iterations.", duration.as_millis(), num_particles_total, num_iterations);
+        println!("Detected {} collisions in total.", self.collision_count());
+        println!("Collision threads were busy for {}", self.scheduler_stats.busy_summary());
+    }
+
+    // An alternative to move_and_collide_particles, as two pipeline stages joined by a channel of
+    // at most config.pipeline_depth steps. This thread runs the movement pool; after each step it
+    // copies the particles into a snapshot & sends it on. A second thread receives the snapshots
+    // & runs the collision pool over each in turn, so checking step n overlaps moving step n + 1
+    // (and more, up to the channel depth) with no barrier shared between the two. Checked
+    // snapshots are sent back to be reused, so steady state allocates nothing.
+    //
+    // The same steps are checked with the same tasks as move_and_collide_particles, so the result
+    // is the same. Returns how long each stage stalled on the other. Collision response needs each
+    // step's collisions before the next move, so with it this falls back to move_and_collide_particles.
+    pub fn pipelined_move_and_collide_particles(&mut self) -> PipelineStats {
+        if self.config.collision_response {
+            println!("Collision response can't be pipelined; moving & colliding in turn instead");
+            self.move_and_collide_particles();
+            return PipelineStats::default();
+        }
+        let num_iterations = self.config.num_iterations;
+        let num_threads_movement = self.config.movement_thread_count;
+        let num_particles_total = self.particles.len();
+        let num_particles_movement = chunk_len(num_particles_total, num_threads_movement);
+        let start_time = time::Instant::now();
+
+        let mut pool_movement = Pool::new(num_threads_movement as u32);
+        let (snapshot_sender, snapshot_receiver) = mpsc::sync_channel(self.config.pipeline_depth.max(1));
+        let (recycle_sender, recycle_receiver) = mpsc::channel();
+        let mut stats = PipelineStats::default();
+
+        // The collision stage gets the broad phase for the duration; it goes back afterwards
+        let mut broad_phase = mem::replace(&mut self.broad_phase, Box::new(BruteForce));
+        let counter = Arc::clone(&self.collision_counter);
+        let config = self.config.clone();
+
+        self.load_columns();
+        let (events, scheduler_stats) = thread::scope(|scope| {
+            let collision = scope.spawn(|| collision_stage(snapshot_receiver, recycle_sender, &mut *broad_phase, &*counter, &config));
+            for _ in 0..num_iterations {
+                self.move_pass(&mut pool_movement, num_particles_movement, self.step..self.step + 1);
+
+                let mut snapshot = recycle_receiver.try_recv().unwrap_or_else(|_| Snapshot {
+                    step: 0,
+                    particles: match self.config.storage {
+                        StorageLayout::ArrayOfStructs | StorageLayout::SharedAtomic => SnapshotParticles::Structs(Vec::new()),
+                        StorageLayout::StructOfArrays => SnapshotParticles::Columns(Box::default())
+                    }
+                });
+                snapshot.step = self.step;
+                match &mut snapshot.particles {
+                    SnapshotParticles::Structs(particles) => particles.clone_from(&self.particles),
+                    SnapshotParticles::Columns(columns) => columns.copy_from(&self.columns)
+                }
+
+                let waiting = time::Instant::now();
+                snapshot_sender.send(snapshot).expect("collision stage stopped early");
+                stats.movement_stall += waiting.elapsed();
+                stats.snapshots += 1;
+            }
+            // Hanging up tells the collision stage there is nothing more to come
+            drop(snapshot_sender);
+            let (events, stall, scheduler_stats) = collision.join().expect("collision stage panicked");
+            stats.collision_stall = stall;
+            (events, scheduler_stats)
+        });
+        self.store_columns();
+        self.broad_phase = broad_phase;
+        self.collision_events.extend(events);
+        self.scheduler_stats.merge(&scheduler_stats);
+
+        let duration = time::Instant::now().duration_since(start_time);
+        println!("Took {} ms to move {} particles & check collisions over {} pipelined iterations.", duration.as_millis(), num_particles_total, num_iterations);
+        println!("Detected {} collisions in total.", self.collision_count());
+        println!("Collision threads were busy for {}", self.scheduler_stats.busy_summary());
+        println!("Movement stalled {} ms waiting on collisions; collisions stalled {} ms waiting on movement ({} is the bottleneck).", stats.movement_stall.as_millis(), stats.collision_stall.as_millis(), stats.bottleneck());
+        stats
+    }
+
+    // The quadtree over the current positions, updated incrementally from the last pass
+    pub fn quadtree(&mut self) -> &Quadtree<T> {
+        match &mut self.quadtree {
+            Some(tree) => tree.update(&self.particles),
+            None => {
+                let bounds = Aabb::of_domain(&self.config.domain());
+                self.quadtree = Some(Quadtree::build(&self.particles, bounds));
+            }
+        }
+        self.quadtree.as_ref().unwrap()
+    }
+    // Indices of the particles inside `region`
+    pub fn particles_in_region(&mut self, region: &Aabb<T>) -> Vec<usize> {
+        let mut found = Vec::new();
+        self.quadtree();
+        let tree = self.quadtree.as_ref().unwrap();
+        tree.query_region(region, &self.particles, &mut found);
+        found
+    }
+
+    // Moves every particle through `steps` in chunks of `chunk_len`, one thread of `pool` per chunk
+    fn move_pass(&mut self, pool: &mut Pool, chunk_len: usize, steps: Range<usize>) {
+        let config = &self.config;
+        let motion_models = &self.motion_models[..];
+        let seed = self.seed;
+        match config.storage {
+            StorageLayout::ArrayOfStructs => pool.scoped(|scope| {
+                for (i, chunk) in self.particles.chunks_mut(chunk_len).enumerate() {
+                    let steps = steps.clone();
+                    scope.execute(move || thread_main(chunk, config, motion_models, seed, steps, i));
+                }
+            }),
+            StorageLayout::StructOfArrays => pool.scoped(|scope| {
+                for (i, chunk) in self.columns.chunks_mut(chunk_len).into_iter().enumerate() {
+                    let steps = steps.clone();
+                    scope.execute(move || thread_main_columns(chunk, config, motion_models, seed, steps, i));
+                }
+            }),
+            StorageLayout::SharedAtomic => {
+                let positions = &self.shared;
+                pool.scoped(|scope| {
+                    for (i, chunk) in self.particles.chunks_mut(chunk_len).enumerate() {
+                        let steps = steps.clone();
+                        scope.execute(move || thread_main_shared(chunk, positions, i * chunk_len, config, motion_models, seed, steps));
+                    }
+                })
+            }
+        }
+        self.step = steps.end;
+        self.remove_absorbed();
+    }
+
+    // One collision check over the current positions, split across `thread_count` threads of `pool`
+    fn collide_pass(&mut self, pool: &mut Pool, thread_count: usize) {
+        let (contact_distance, queues, mut buffers) = self.prepare_collide_pass(thread_count);
+        let list = self.particle_slice();
+        let counter = &*self.collision_counter;
+        let config = &self.config;
+        let broad_phase = &*self.broad_phase;
+        let queues = &queues;
+        pool.scoped(|scope| {
+            for buffer in buffers.iter_mut() {
+                scope.execute(move || collision_worker(list, broad_phase, counter, config, queues, contact_distance, buffer));
+            }
+        });
+        self.finish_collide_pass(buffers, &queues.stats());
+    }
+
+    // A collision pass over the current positions (step n) alongside a movement step from a copy
+    // of them, written into the back buffer (step n + 1). Both sets of threads share `pool`; once
+    // they are all done the back buffer becomes the current one. SharedAtomic storage moves in
+    // place instead, under the collision threads' reads. Any broad phase but BruteForce would be
+    // built from step n & could leave out a pair that only touches once one of them has moved, so
+    // that pass checks every pair, whatever config.broad_phase says.
+    fn double_buffered_pass(&mut self, pool: &mut Pool, chunk_len: usize, collision_threads: usize) {
+        let (contact_distance, queues, mut buffers) = match self.config.storage {
+            StorageLayout::SharedAtomic => prepare_pass(ParticleSlice::Shared(&self.shared), &mut BruteForce, &self.config, collision_threads, self.step),
+            _ => self.prepare_collide_pass(collision_threads)
+        };
+        let queues = &queues;
+        let config = &self.config;
+        let motion_models = &self.motion_models[..];
+        let seed = self.seed;
+        let step = self.step;
+        let counter = &*self.collision_counter;
+        let broad_phase: &dyn BroadPhase<T, D> = match config.storage {
+            StorageLayout::SharedAtomic => &BruteForce,
+            _ => &*self.broad_phase
+        };
+        match config.storage {
+            StorageLayout::ArrayOfStructs => {
+                self.particles_back.clone_from(&self.particles);
+                let front = ParticleSlice::Structs(&self.particles);
+                pool.scoped(|scope| {
+                    for (i, chunk) in self.particles_back.chunks_mut(chunk_len).enumerate() {
+                        scope.execute(move || thread_main(chunk, config, motion_models, seed, step..step + 1, i));
+                    }
+                    for buffer in buffers.iter_mut() {
+                        scope.execute(move || collision_worker(front, broad_phase, counter, config, queues, contact_distance, buffer));
+                    }
+                });
+                mem::swap(&mut self.particles, &mut self.particles_back);
+            }
+            StorageLayout::StructOfArrays => {
+                self.columns_back.copy_from(&self.columns);
+                let front = ParticleSlice::Columns(&self.columns);
+                pool.scoped(|scope| {
+                    for (i, chunk) in self.columns_back.chunks_mut(chunk_len).into_iter().enumerate() {
+                        scope.execute(move || thread_main_columns(chunk, config, motion_models, seed, step..step + 1, i));
+                    }
+                    for buffer in buffers.iter_mut() {
+                        scope.execute(move || collision_worker(front, broad_phase, counter, config, queues, contact_distance, buffer));
+                    }
+                });
+                mem::swap(&mut self.columns, &mut self.columns_back);
+            }
+            StorageLayout::SharedAtomic => {
+                let positions = &self.shared;
+                let front = ParticleSlice::Shared(positions);
+                pool.scoped(|scope| {
+                    for (i, chunk) in self.particles.chunks_mut(chunk_len).enumerate() {
+                        scope.execute(move || thread_main_shared(chunk, positions, i * chunk_len, config, motion_models, seed, step..step + 1));
+                    }
+                    for buffer in buffers.iter_mut() {
+                        scope.execute(move || collision_worker(front, broad_phase, counter, config, queues, contact_distance, buffer));
+                    }
+                });
+            }
+        }
+        self.step += 1;
+        self.remove_absorbed();
+        self.finish_collide_pass(buffers, &queues.stats());
+    }
+
+    // The particles in the layout currently being run in
+    fn particle_slice(&self) -> ParticleSlice<'_, T, D> {
+        match self.config.storage {
+            StorageLayout::ArrayOfStructs => ParticleSlice::Structs(&self.particles),
+            StorageLayout::StructOfArrays => ParticleSlice::Columns(&self.columns),
+            StorageLayout::SharedAtomic => ParticleSlice::Shared(&self.shared)
+        }
+    }
+
+    // The serial start of a collision pass over the current positions (see prepare_pass)
+    fn prepare_collide_pass(&mut self, thread_count: usize) -> (T, TaskQueues, Vec<EventBuffer<T>>) {
+        // Not particle_slice(): that would borrow the broad phase too
+        let list = match self.config.storage {
+            StorageLayout::ArrayOfStructs => ParticleSlice::Structs(&self.particles),
+            StorageLayout::StructOfArrays => ParticleSlice::Columns(&self.columns),
+            StorageLayout::SharedAtomic => ParticleSlice::Shared(&self.shared)
+        };
+        prepare_pass(list, &mut *self.broad_phase, &self.config, thread_count, self.step)
+    }
+
+    // Total the pass' counts & busy times, merge its events, then respond to & keep them as configured
+    fn finish_collide_pass(&mut self, buffers: Vec<EventBuffer<T>>, workers: &[WorkerStats]) {
+        self.collision_counter.end_pass();
+        self.scheduler_stats.add_pass(workers);
+        report_workers(workers, self.config.log_collisions);
+        let mut pass_events = Vec::new();
+        merge_event_buffers(buffers, &mut pass_events);
+
+        if self.config.collision_response {
+            self.resolve_collisions(&pass_events);
+        }
+        if self.config.record_collisions {
+            self.collision_events.append(&mut pass_events);
+        }
+    }
+
+    // Response phase: apply an elastic impulse to every colliding pair found by the last pass.
+    // Runs serially in the merged (step, a, b) order, so the result is the same for any thread count.
+    fn resolve_collisions(&mut self, events: &[CollisionEvent<T>]) {
+        let domain = self.config.domain();
+        for event in events {
+            let (a, b) = (self.index_of(event.a), self.index_of(event.b));
+            match self.config.storage {
+                // Only velocities change, so there are no positions to publish
+                StorageLayout::ArrayOfStructs | StorageLayout::SharedAtomic => {
+                    let (low, high) = self.particles.split_at_mut(b);
+                    resolve_elastic_collision_in(&mut low[a], &mut high[0], &domain);
+                }
+                StorageLayout::StructOfArrays => {
+                    let (mut first, mut second) = (self.columns.get(a), self.columns.get(b));
+                    resolve_elastic_collision_in(&mut first, &mut second, &domain);
+                    self.columns.set_motion(a, &first);
+                    self.columns.set_motion(b, &second);
+                }
+            }
+        }
+    }
+
+    // Events name particles by id. Particles stay sorted by id, and until something is absorbed
+    // the id is also the index.
+    fn index_of(&self, id: usize) -> usize {
+        let ids = match self.config.storage {
+            StorageLayout::ArrayOfStructs | StorageLayout::SharedAtomic => return match self.particles.get(id) {
+                Some(particle) if particle.id == id => id,
+                _ => self.particles.binary_search_by_key(&id, |p| p.id).expect("event for a particle that no longer exists")
+            },
+            StorageLayout::StructOfArrays => &self.columns.id
+        };
+        match ids.get(id) {
+            Some(&found) if found == id => id,
+            _ => ids.binary_search(&id).expect("event for a particle that no longer exists")
+        }
+    }
+
+    // Drop every particle that went through an absorbing wall during the last movement
+    fn remove_absorbed(&mut self) {
+        let domain = self.config.domain();
+        if !domain.absorbs() {
+            return;
+        }
+        let before = self.len();
+        match self.config.storage {
+            StorageLayout::ArrayOfStructs => self.particles.retain(|particle| !domain.is_absorbed(particle)),
+            StorageLayout::StructOfArrays => self.columns.retain(|particle| !domain.is_absorbed(particle)),
+            StorageLayout::SharedAtomic => {
+                self.particles.retain(|particle| !domain.is_absorbed(particle));
+                // Rows have shifted, so republish (only when something went)
+                if self.particles.len() < before {
+                    self.shared.load(&self.particles);
+                }
+            }
+        }
+        self.absorbed += before - self.len();
+    }
+
+    // Number of particles in the layout currently being run in
+    fn len(&self) -> usize {
+        match self.config.storage {
+            StorageLayout::ArrayOfStructs | StorageLayout::SharedAtomic => self.particles.len(),
+            StorageLayout::StructOfArrays => self.columns.len()
+        }
+    }
+
+    // Copy `particles` into the columns before a run in StructOfArrays storage (or publish their
+    // positions for SharedAtomic)...
+    fn load_columns(&mut self) {
+        match self.config.storage {
+            StorageLayout::ArrayOfStructs => {}
+            StorageLayout::StructOfArrays => {
+                self.columns.load(&self.particles);
+                self.columns.simd = self.config.simd;
+            }
+            StorageLayout::SharedAtomic => self.shared.load(&self.particles)
+        }
+    }
+    // ...and back out once it's done, so `particles` is up to date whichever layout ran. SharedAtomic
+    // runs move `particles` themselves
+    fn store_columns(&mut self) {
+        if self.config.storage == StorageLayout::StructOfArrays {
+            self.columns.store(&mut self.particles);
+        }
+    }
+}
+
+// The serial start of a collision pass over `list`: bring the broad phase up to date, split the
+// pass into tasks queued for the threads as config.scheduler says, & give each thread an event
+// buffer for `step`
+fn prepare_pass<T: Scalar, D: Dimension>(list: ParticleSlice<'_, T, D>, broad_phase: &mut dyn BroadPhase<T, D>, config: &SimulationConfig, thread_count: usize, step: usize) -> (T, TaskQueues, Vec<EventBuffer<T>>) {
+    // Sized from the largest particle actually present, not the distribution's upper limit
+    let contact_distance = match list {
+        ParticleSlice::Structs(particles) => max_contact_distance(particles),
+        ParticleSlice::Columns(columns) => T::from_f32(2.0) * columns.radius.iter().fold(T::ZERO, |max, &radius| max.max(radius)),
+        ParticleSlice::Shared(positions) => T::from_f32(2.0) * (0..positions.len()).fold(T::ZERO, |max, i| max.max(positions.radius(i)))
+    };
+    broad_phase.update(list, contact_distance, &config.domain());
+    let tasks = broad_phase.tasks(list.len(), TaskQueues::task_count(thread_count, config.scheduler));
+    let queues = TaskQueues::new(tasks, thread_count, config.scheduler);
+
+    // One buffer per thread, for every task it runs, merged once they have all finished.
+    // The response phase needs the colliding pairs too, even if the history isn't kept.
+    let recording = config.record_collisions || config.collision_response;
+    let buffers = (0..queues.workers()).map(|thread_id| EventBuffer::new(thread_id, step, recording)).collect();
+    (contact_distance, queues, buffers)
+}
+
+// The collision stage of the pipeline: check every snapshot that arrives, until movement hangs up.
+// Returns the events to keep, the time spent waiting for snapshots & how busy the threads were.
+fn collision_stage<T: Scalar, D: Dimension>(snapshots: Receiver<Snapshot<T, D>>, recycle: Sender<Snapshot<T, D>>, broad_phase: &mut dyn BroadPhase<T, D>, counter: &dyn CollisionCounter, config: &SimulationConfig) -> (Vec<CollisionEvent<T>>, Duration, SchedulerStats) {
+    let thread_count = config.collision_thread_count();
+    let mut pool = Pool::new(thread_count as u32);
+    let mut events = Vec::new();
+    let mut stall = Duration::ZERO;
+    let mut scheduler_stats = SchedulerStats::default();
+    loop {
+        let waiting = time::Instant::now();
+        let Ok(snapshot) = snapshots.recv() else { break };
+        stall += waiting.elapsed();
+
+        let list = snapshot.particles.as_slice();
+        let (contact_distance, queues, mut buffers) = prepare_pass(list, broad_phase, config, thread_count, snapshot.step);
+        let broad_phase = &*broad_phase;
+        let queues = &queues;
+        pool.scoped(|scope| {
+            for buffer in buffers.iter_mut() {
+                scope.execute(move || collision_worker(list, broad_phase, counter, config, queues, contact_distance, buffer));
+            }
+        });
+        counter.end_pass();
+        let workers = queues.stats();
+        scheduler_stats.add_pass(&workers);
+        report_workers(&workers, config.log_collisions);
+        let mut pass_events = Vec::new();
+        merge_event_buffers(buffers, &mut pass_events);
+        if config.record_collisions {
+            events.append(&mut pass_events);
+        }
+        // Movement may already have finished, in which case nobody needs it back
+        let _ = recycle.send(snapshot);
+    }
+    (events, stall, scheduler_stats)
+}
@@ -0,0 +1,123 @@
+use std::ops::Range;
+use crate::soa::{per_layout, ColumnsMut};
+use crate::{AtomicPositions, BroadPhase, CollisionCounter, Dimension, Domain, Dynamics, EventBuffer, MotionModel, Particle, ParticleRng, ParticleSlice, ParticleStore, Scalar, SimulationConfig, TaskQueues, WorkerStats};
+
+// Moves every particle in the chunk once per step in `steps`, as set by config.dynamics: either its
+// species' motion model plus its velocity, or one integrator step of dt. Each particle draws from
+// its own (seed, id, step) stream, so the result doesn't depend on how the particles were split
+// into chunks.
+pub fn thread_main<T: Scalar, D: Dimension>(chunk: &mut [Particle<T, D>], config: &SimulationConfig, motion_models: &[Box<dyn MotionModel<T, D>>], seed: u64, steps: Range<usize>, _thread_index: usize) {
+    let domain = config.domain::<T>();
+    for step in steps {
+        // println!("Thread {} moving particles...", _thread_index);
+        for particle in chunk.iter_mut() {
+            move_particle(particle, config, &domain, motion_models, seed, step);
+        }
+    }
+}
+
+// As thread_main, for a chunk of ParticleColumns. A random walk without absorbing walls runs
+// column-wise: every particle is moved, then each axis' boundary is applied down its whole column.
+// Anything else (integration, or particles dropping out mid-chunk) gathers each particle, moves
+// it as thread_main would & writes its position & velocity back. Both give the same bits as thread_main.
+pub fn thread_main_columns<T: Scalar, D: Dimension>(mut chunk: ColumnsMut<'_, T, D>, config: &SimulationConfig, motion_models: &[Box<dyn MotionModel<T, D>>], seed: u64, steps: Range<usize>, _thread_index: usize) {
+    let domain = config.domain::<T>();
+    for step in steps {
+        match config.dynamics {
+            Dynamics::RandomWalk if !domain.absorbs() => {
+                for i in 0..chunk.len() {
+                    let mut rng = ParticleRng::for_step(seed, chunk.id[i], step);
+                    let xyz = motion_models[chunk.species[i]].displacement(&chunk.get(i), &mut rng);
+                    chunk.x[i] += xyz.0 + chunk.vx[i];
+                    chunk.y[i] += xyz.1 + chunk.vy[i];
+                    let vz = D::depth(chunk.vz[i]);
+                    if let Some(z) = D::depth_mut(&mut chunk.z[i]) {
+                        *z += xyz.2 + vz;
+                    }
+                }
+                domain.apply_columns(&mut chunk);
+            }
+            _ => {
+                for i in 0..chunk.len() {
+                    let mut particle = chunk.get(i);
+                    move_particle(&mut particle, config, &domain, motion_models, seed, step);
+                    chunk.set_motion(i, &particle);
+                }
+            }
+        }
+    }
+}
+
+// As thread_main, also publishing each particle's new position to `positions` as soon as it has
+// moved, for collision threads reading them at the same time. The chunk starts at row `first`.
+pub fn thread_main_shared<T: Scalar, D: Dimension>(chunk: &mut [Particle<T, D>], positions: &AtomicPositions<T, D>, first: usize, config: &SimulationConfig, motion_models: &[Box<dyn MotionModel<T, D>>], seed: u64, steps: Range<usize>) {
+    let domain = config.domain::<T>();
+    for step in steps {
+        for (k, particle) in chunk.iter_mut().enumerate() {
+            move_particle(particle, config, &domain, motion_models, seed, step);
+            positions.write(first + k, particle.position());
+        }
+    }
+}
+
+fn move_particle<T: Scalar, D: Dimension>(particle: &mut Particle<T, D>, config: &SimulationConfig, domain: &Domain<T>, motion_models: &[Box<dyn MotionModel<T, D>>], seed: u64, step: usize) {
+    // Already gone through an absorbing wall earlier in `steps`; removed once they finish
+    if domain.absorbs() && domain.is_absorbed(particle) {
+        return;
+    }
+    match config.dynamics {
+        Dynamics::RandomWalk => {
+            let mut rng = ParticleRng::for_step(seed, particle.id, step);
+            let xyz = motion_models[particle.species].displacement(particle, &mut rng);
+
+            // Apply vector to particle, plus one step of its own velocity (zero unless given one)
+            particle.translate((xyz.0 + particle.vx, xyz.1 + particle.vy, xyz.2 + particle.vz()));
+        }
+        Dynamics::Integrated { integrator, dt, force } => integrator.step(particle, &force, dt)
+    }
+
+    // Clamp, reflect or wrap the particle back inside the declared boundaries
+    domain.apply(particle);
+
+    // println!("Particle {} moved. New position: ({}, {})", particle.id, particle.x, particle.y);
+}
+
+// Runs one task of a collision pass: every candidate pair `broad_phase` assigns to `task` is
+// tested exactly; `contact_distance` is the one the broad phase was updated with. Returns the
+// number of collisions the task found. Each one is counted in `counter` and recorded in the
+// running thread's own event buffer.
+// Printing each collision (config.log_collisions) is optional, since stdout in the hot loop destroys throughput.
+pub fn thread_collide<T: Scalar, D: Dimension>(list: ParticleSlice<'_, T, D>, broad_phase: &dyn BroadPhase<T, D>, counter: &dyn CollisionCounter, config: &SimulationConfig, task: Range<usize>, contact_distance: T, events: &mut EventBuffer<T>) -> usize {
+    let domain = config.domain::<T>();
+    let log_collisions = config.log_collisions;
+
+    let local_collision_count = broad_phase.collide_task(list, task, contact_distance, &domain, &mut |i, j| {
+        let (a, b) = per_layout!(list, p => (p.particle(i), p.particle(j)));
+        counter.count(events.thread_id);
+        log_collision(&a, &b, log_collisions);
+        events.record(&a, &b);
+    });
+    counter.task_done(events.thread_id, local_collision_count);
+    local_collision_count
+}
+
+// One collision thread of a pass: runs thread_collide on tasks from `queues`, its own first, until
+// there are none left it may take. `events` belongs to this thread, whose id is its thread_id.
+pub fn collision_worker<T: Scalar, D: Dimension>(list: ParticleSlice<'_, T, D>, broad_phase: &dyn BroadPhase<T, D>, counter: &dyn CollisionCounter, config: &SimulationConfig, queues: &TaskQueues, contact_distance: T, events: &mut EventBuffer<T>) {
+    queues.work(events.thread_id, |task| thread_collide(list, broad_phase, counter, config, task, contact_distance, events));
+}
+
+fn log_collision<T: Scalar, D: Dimension>(particle: &Particle<T, D>, other: &Particle<T, D>, log_collisions: bool) {
+    if log_collisions {
+        println!("Collision found between particles {} ({}, {}) and {} ({}, {})", particle.id, particle.x, particle.y, other.id, other.x, other.y);
+    }
+}
+
+// One line per collision thread once a pass is over, from what the scheduler saw it do
+pub(crate) fn report_workers(workers: &[WorkerStats], log_collisions: bool) {
+    if log_collisions {
+        for (thread_id, stats) in workers.iter().enumerate() {
+            println!("Thread {} spent {} ms on collision checking over {} tasks ({} stolen), and detected {} total collisions", thread_id, stats.busy.as_millis(), stats.tasks, stats.stolen, stats.collisions);
+        }
+    }
+}
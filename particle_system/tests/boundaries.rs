@@ -0,0 +1,113 @@
+use clap::Parser;
+use particle_system::{resolve_elastic_collision, resolve_elastic_collision_in, Boundary, BroadPhaseKind, Cli, Domain, MovementModel, Particle, ParticleSystem, Scenario, SimulationConfig, Species};
+
+fn domain(x: Boundary, y: Boundary) -> Domain {
+    Domain { half: (5.0, 5.0, 0.0), boundaries: (x, y, Boundary::Clamp) }
+}
+
+fn applied(domain: &Domain, particle: Particle) -> Particle {
+    let mut particle = particle;
+    domain.apply(&mut particle);
+    particle
+}
+
+#[test]
+fn reflect_mirrors_back_inside_and_reverses_velocity() {
+    let reflect = domain(Boundary::Reflect, Boundary::Reflect);
+    let p = applied(&reflect, Particle::new(6.0, -5.5, 0).with_velocity(1.0, -2.0));
+    assert_eq!((p.x, p.y, p.vx, p.vy), (4.0, -4.5, -1.0, 2.0));
+
+    // A jump of more than the whole width bounces off both walls, so ends up going the same way
+    let p = applied(&reflect, Particle::new(-17.0, 0.0, 0).with_velocity(-3.0, 0.0));
+    assert_eq!((p.x, p.vx), (3.0, -3.0));
+
+    // Inside, nothing changes
+    let p = applied(&reflect, Particle::new(4.9, 0.0, 0).with_velocity(1.0, 1.0));
+    assert_eq!((p.x, p.y, p.vx, p.vy), (4.9, 0.0, 1.0, 1.0));
+}
+
+#[test]
+fn periodic_wraps_to_the_opposite_wall() {
+    let periodic = domain(Boundary::Periodic, Boundary::Periodic);
+    let p = applied(&periodic, Particle::new(5.5, -6.0, 0).with_velocity(1.0, -1.0));
+    assert_eq!((p.x, p.y, p.vx, p.vy), (-4.5, 4.0, 1.0, -1.0));
+}
+
+#[test]
+fn periodic_collisions_use_the_minimum_image() {
+    let a = Particle::new(4.95, 0.0, 0).with_velocity(1.0, 0.0);
+    let b = Particle::new(-4.95, 0.05, 1).with_velocity(-1.0, 0.0);
+    assert!(!a.collide(&b));
+    assert!(!a.collide_in(&b, &domain(Boundary::Clamp, Boundary::Periodic)));
+    assert!(a.collide_in(&b, &domain(Boundary::Periodic, Boundary::Clamp)));
+
+    // Without periodic walls they are moving apart, so nothing happens; across the seam they are
+    // approaching each other, so equal masses swap their velocities along x
+    let (mut a, mut b) = (a.with_radius(1.0), b.with_radius(1.0));
+    assert!(!resolve_elastic_collision(&mut a, &mut b));
+    assert!(resolve_elastic_collision_in(&mut a, &mut b, &domain(Boundary::Periodic, Boundary::Periodic)));
+    assert!(a.vx < 0.0 && b.vx > 0.0);
+}
+
+#[test]
+fn every_broad_phase_finds_pairs_across_the_seam() {
+    for broad_phase in [BroadPhaseKind::BruteForce, BroadPhaseKind::Grid, BroadPhaseKind::Quadtree, BroadPhaseKind::SweepAndPrune] {
+        let mut particle_system = ParticleSystem::new(SimulationConfig {
+            bounds: (10.0, 10.0, 0.0),
+            boundaries: (Boundary::Periodic, Boundary::Periodic, Boundary::Clamp),
+            broad_phase,
+            log_collisions: false,
+            ..SimulationConfig::default()
+        });
+        // One pair across the x seam, one across the corner & one that only looks close
+        particle_system.particles = vec![
+            Particle::new(-4.95, 1.0, 0),
+            Particle::new(4.95, 1.0, 1),
+            Particle::new(-4.95, -4.95, 2),
+            Particle::new(4.95, 4.95, 3),
+            Particle::new(0.0, 0.0, 4),
+            Particle::new(0.3, 0.0, 5)
+        ];
+        particle_system.collide_particles();
+        assert_eq!(particle_system.collision_count(), 2, "{:?}", broad_phase);
+    }
+}
+
+#[test]
+fn absorbing_walls_remove_and_count_particles() {
+    let config = SimulationConfig {
+        particle_count: 500,
+        bounds: (10.0, 10.0, 0.0),
+        // Absorb on x only; y walls still clamp
+        boundaries: (Boundary::Absorb, Boundary::Clamp, Boundary::Clamp),
+        species: vec![Species { weight: 1.0, movement: MovementModel::Drift { velocity: (1.0, 1.0, 0.0) } }],
+        num_iterations: 3,
+        thread_count: 4,
+        log_collisions: false,
+        seed: Some(9),
+        ..SimulationConfig::default()
+    };
+    let mut particle_system = ParticleSystem::new(config);
+    particle_system.spawn_particles();
+    // Everyone within 3 of the right wall drifts out of it
+    let expected = particle_system.particles.iter().filter(|p| p.x + 3.0 > 5.0).count();
+    particle_system.move_and_collide_particles();
+
+    assert_eq!(particle_system.absorbed_count(), expected);
+    assert_eq!(particle_system.particles.len(), 500 - expected);
+    assert!(particle_system.particles.iter().all(|p| p.x <= 5.0 && p.y <= 5.0));
+    // ..and the ones that reached the top wall are still there, clamped
+    assert!(particle_system.particles.iter().any(|p| p.y == 5.0));
+}
+
+#[test]
+fn boundaries_are_chosen_per_axis_on_the_command_line_and_in_scenarios() {
+    let parse = |args: &[&str]| Cli::parse_from([&["colliding_particles"], args].concat()).apply(SimulationConfig::default()).boundaries;
+    assert_eq!(parse(&[]), (Boundary::Clamp, Boundary::Clamp, Boundary::Clamp));
+    assert_eq!(parse(&["--boundary", "periodic"]), (Boundary::Periodic, Boundary::Periodic, Boundary::Periodic));
+    assert_eq!(parse(&["--boundary", "reflect", "--boundary-y", "absorb"]), (Boundary::Reflect, Boundary::Absorb, Boundary::Reflect));
+
+    let text = "iterations = 1\n[particles]\ncount = 10\nradius = 0.1\n[bounds]\nx = 5.0\ny = 5.0\nboundary = [\"periodic\", \"reflect\"]\n[threads]\ntotal = 2\n";
+    assert_eq!(Scenario::from_toml(text).unwrap().config().boundaries, (Boundary::Periodic, Boundary::Reflect, Boundary::Clamp));
+    assert!(Scenario::from_toml(&text.replace("reflect", "bounce")).is_err());
+}
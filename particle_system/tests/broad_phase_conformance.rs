@@ -0,0 +1,212 @@
+use std::collections::BTreeSet;
+use std::ops::Range;
+use rand::Rng;
+use particle_system::{broad_phase_for, max_contact_distance, motion_models_for, thread_main, AtomicPositions, Boundary, BroadPhase, BroadPhaseKind, Dimension, Domain, Particle, ParticleColumns, ParticleRng, ParticleSlice, ParticleStore, Scalar, SimulationConfig, ThreeD, TwoD};
+
+// Conformance checks for BroadPhase implementations. Every built-in broad phase runs through
+// check_broad_phase below; a new implementation should be added to the list too.
+//
+// For a range of particle layouts, sizes & thread counts, the broad phase's output is compared
+// against brute force. Every layout mixes three particle sizes, so pairs of unequal radii are
+// covered too. Each layout is also run with periodic walls, where pairs across the seam must be
+// found using minimum-image distances, and in a 3D box. The broad phase is given the particles in
+// both storage layouts (see StorageLayout). Panics describing the first case that fails.
+// Checks the broad phase at precision T in dimension D, so only the 2D or only the 3D domains
+// below; the layouts are the same for f32 & f64.
+fn check_broad_phase<T: Scalar, D: Dimension>(make: &dyn Fn(&SimulationConfig) -> Box<dyn BroadPhase<T, D>>) {
+    let (clamp, periodic) = (Boundary::Clamp, Boundary::Periodic);
+    // (depth, walls); a depth of 0 is 2D
+    let domains = [
+        (0.0, (clamp, clamp, clamp)),
+        (0.0, (periodic, periodic, clamp)),
+        (0.0, (periodic, clamp, clamp)),
+        (10.0, (clamp, clamp, clamp)),
+        (10.0, (periodic, periodic, periodic))
+    ];
+    for (depth, boundaries) in domains.into_iter().filter(|&(depth, _)| (depth > 0.0) == (D::AXES == 3)) {
+        for layout in [Layout::Uniform, Layout::Clustered, Layout::Coincident, Layout::Line, Layout::Seams] {
+            for particle_count in [0, 2, 17, 64] {
+                for largest_radius in [0.1, 1.0] {
+                    let config = SimulationConfig {
+                        particle_count,
+                        bounds: (10.0, 10.0, depth),
+                        boundaries,
+                        log_collisions: false,
+                        seed: Some(particle_count as u64),
+                        ..SimulationConfig::default()
+                    };
+                    let domain = config.domain();
+                    let mut particles: Vec<Particle<T, D>> = layout.particles(&config, largest_radius);
+                    let contact_distance = max_contact_distance(&particles);
+                    let mut broad_phase = make(&config);
+                    let motion_models = motion_models_for(&config.species);
+
+                    // Several steps, so structures that update incrementally are checked after moving too
+                    for step in 0..2 {
+                        let expected = brute_force(&particles, &domain);
+                        let columns = ParticleColumns::from_particles(&particles);
+                        let shared = AtomicPositions::from_particles(&particles);
+                        for slice in [ParticleSlice::Structs(&particles), ParticleSlice::Columns(&columns), ParticleSlice::Shared(&shared)] {
+                            let storage = match slice { ParticleSlice::Structs(_) => "structs", ParticleSlice::Columns(_) => "columns", ParticleSlice::Shared(_) => "shared atomics" };
+                            let case = format!("{} with {:?} layout in {}, {:?} walls, depth {}, n={}, contact={}, step {}", broad_phase.name(), layout, storage, boundaries, depth, particle_count, contact_distance, step);
+                            broad_phase.update(slice, contact_distance, &domain);
+                            // How tasks split the work doesn't depend on the layout or the step, so the rest get one thread count
+                            let thread_counts: &[usize] = match slice { ParticleSlice::Structs(_) if step == 0 => &[1, 3, 200], _ => &[3] };
+                            for &thread_count in thread_counts {
+                                check_pass(&*broad_phase, slice, contact_distance, &domain, thread_count, &expected, &case);
+                            }
+                        }
+                        thread_main(&mut particles, &config, &motion_models, config.seed.unwrap(), step..step + 1, 0);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Layout {
+    Uniform,
+    // Three tight clumps
+    Clustered,
+    // Everyone in the same place, e.g. all clamped into one corner
+    Coincident,
+    // Spread along x only, the worst case for sweep-and-prune on x
+    Line,
+    // Hugging the walls & corners, so periodic runs have plenty of pairs across the seams
+    Seams
+}
+
+impl Layout {
+    // Radii cycle through `largest` & two smaller sizes. z is only drawn in a 3D domain.
+    fn particles<T: Scalar, D: Dimension>(self, config: &SimulationConfig, largest: f32) -> Vec<Particle<T, D>> {
+        let half = config.bounds_half();
+        let three_d = config.is_3d();
+        (0..config.particle_count).map(|i| {
+            let mut rng = ParticleRng::for_spawn(config.seed.unwrap_or(0), i);
+            let (x, y, z) = match self {
+                Layout::Uniform => (rng.random_range(-half.0..half.0), rng.random_range(-half.1..half.1), depth(&mut rng, three_d, -half.2..half.2)),
+                Layout::Clustered => {
+                    let centre = [(-3.0, -3.0, 1.0), (2.5, 1.0, -2.0), (4.0, -4.5, 0.0)][i % 3];
+                    (centre.0 + rng.random_range(-0.3..0.3), centre.1 + rng.random_range(-0.3..0.3), if three_d { centre.2 + rng.random_range(-0.3..0.3) } else { 0.0 })
+                }
+                Layout::Coincident => (-half.0, -half.1, -half.2),
+                Layout::Line => (rng.random_range(-half.0..half.0), 0.0, 0.0),
+                Layout::Seams => {
+                    let x = half.0 - rng.random_range(0.0..0.5);
+                    let y = rng.random_range(-half.1..half.1);
+                    // Left or right wall, bottom or top wall, or a corner (of the box, in 3D)
+                    match i % 4 {
+                        0 => (x, y, 0.0),
+                        1 => (-x, y, 0.0),
+                        2 => (y, half.1 - rng.random_range(0.0..0.5), 0.0),
+                        _ => (x * [1.0, -1.0][i / 4 % 2], -half.1 + rng.random_range(0.0..0.5), half.2 - depth(&mut rng, three_d, 0.0..0.5))
+                    }
+                }
+            };
+            Particle::at((T::from_f32(x), T::from_f32(y), T::from_f32(z)), i).with_radius(T::from_f32(largest * [1.0, 0.2, 0.6][i % 3]))
+        }).collect()
+    }
+}
+
+// A random z within `range`, or 0 in 2D
+fn depth(rng: &mut ParticleRng, three_d: bool, range: Range<f32>) -> f32 {
+    if three_d { rng.random_range(range) } else { 0.0 }
+}
+
+fn brute_force<T: Scalar, D: Dimension>(particles: &[Particle<T, D>], domain: &Domain<T>) -> BTreeSet<(usize, usize)> {
+    let mut expected = BTreeSet::new();
+    for i in 0..particles.len() {
+        for j in i + 1..particles.len() {
+            if particles[i].collide_in(&particles[j], domain) {
+                expected.insert((i, j));
+            }
+        }
+    }
+    expected
+}
+
+fn check_pass<T: Scalar, D: Dimension>(broad_phase: &dyn BroadPhase<T, D>, particles: ParticleSlice<'_, T, D>, contact_distance: T, domain: &Domain<T>, thread_count: usize, expected: &BTreeSet<(usize, usize)>, case: &str) {
+    let tasks = broad_phase.tasks(particles.len(), thread_count);
+    assert!(tasks.len() <= thread_count.max(1), "{}: {} tasks for {} threads", case, tasks.len(), thread_count);
+
+    // Candidate pairs: well formed & never repeated, within or across tasks
+    let mut candidates = BTreeSet::new();
+    for task in &tasks {
+        broad_phase.for_each_candidate(particles, task.clone(), contact_distance, domain, &mut |i, j| {
+            assert!(i < j && j < particles.len(), "{}: malformed pair ({}, {})", case, i, j);
+            assert!(candidates.insert((i, j)), "{}: pair ({}, {}) produced twice", case, i, j);
+        });
+    }
+
+    let found: BTreeSet<_> = candidates.into_iter().filter(|&(i, j)| particle(particles, i).collide_in(&particle(particles, j), domain)).collect();
+    assert_eq!(&found, expected, "{}: candidate pairs miss collisions ({} threads)", case, thread_count);
+
+    // collide_task must report the same pairs as the candidates it is built on
+    let mut reported = BTreeSet::new();
+    let mut count = 0;
+    for task in tasks {
+        count += broad_phase.collide_task(particles, task, contact_distance, domain, &mut |i, j| {
+            assert!(reported.insert((i, j)), "{}: collision ({}, {}) reported twice", case, i, j);
+        });
+    }
+    assert_eq!(&reported, expected, "{}: collide_task reported the wrong pairs ({} threads)", case, thread_count);
+    assert_eq!(count, expected.len(), "{}: collide_task returned the wrong count", case);
+}
+
+fn particle<T: Scalar, D: Dimension>(particles: ParticleSlice<'_, T, D>, i: usize) -> Particle<T, D> {
+    match particles {
+        ParticleSlice::Structs(particles) => particles.particle(i),
+        ParticleSlice::Columns(columns) => columns.particle(i),
+        ParticleSlice::Shared(positions) => positions.particle(i)
+    }
+}
+
+// Quadtree & sweep-and-prune only prune on x (& y), so they are refused in 3D & only checked in 2D
+fn check_kind<T: Scalar>(kind: BroadPhaseKind) {
+    check_broad_phase::<T, TwoD>(&|config: &SimulationConfig| broad_phase_for(&SimulationConfig { broad_phase: kind, ..config.clone() }));
+    if !matches!(kind, BroadPhaseKind::Quadtree | BroadPhaseKind::SweepAndPrune) {
+        check_broad_phase::<T, ThreeD>(&|config: &SimulationConfig| broad_phase_for(&SimulationConfig { broad_phase: kind, ..config.clone() }));
+    }
+}
+
+#[test]
+fn brute_force_conforms() {
+    check_kind::<f32>(BroadPhaseKind::BruteForce);
+}
+
+#[test]
+fn grid_conforms() {
+    check_kind::<f32>(BroadPhaseKind::Grid);
+}
+
+#[test]
+fn quadtree_conforms() {
+    check_kind::<f32>(BroadPhaseKind::Quadtree);
+}
+
+#[test]
+fn sweep_and_prune_conforms() {
+    check_kind::<f32>(BroadPhaseKind::SweepAndPrune);
+}
+
+// The same layouts in double precision
+#[test]
+fn brute_force_conforms_in_f64() {
+    check_kind::<f64>(BroadPhaseKind::BruteForce);
+}
+
+#[test]
+fn grid_conforms_in_f64() {
+    check_kind::<f64>(BroadPhaseKind::Grid);
+}
+
+#[test]
+fn quadtree_conforms_in_f64() {
+    check_kind::<f64>(BroadPhaseKind::Quadtree);
+}
+
+#[test]
+fn sweep_and_prune_conforms_in_f64() {
+    check_kind::<f64>(BroadPhaseKind::SweepAndPrune);
+}
@@ -0,0 +1,59 @@
+use particle_system::{resolve_elastic_collision, Particle, ParticleSystem, RadiusDistribution, SimulationConfig};
+
+#[test]
+fn equal_masses_swap_velocities_head_on() {
+    let mut a: Particle = Particle::new(0.0, 0.0, 0).with_velocity(1.0, 0.0);
+    let mut b = Particle::new(0.2, 0.0, 1).with_velocity(-0.5, 0.0);
+    assert!(resolve_elastic_collision(&mut a, &mut b));
+    assert!((a.vx + 0.5).abs() < 1e-6 && a.vy.abs() < 1e-6);
+    assert!((b.vx - 1.0).abs() < 1e-6 && b.vy.abs() < 1e-6);
+
+    // Now separating, so a second resolve must leave them alone
+    assert!(!resolve_elastic_collision(&mut a, &mut b));
+    assert!((a.vx + 0.5).abs() < 1e-6);
+}
+
+#[test]
+fn coincident_particles_are_left_alone() {
+    let mut a = Particle::new(1.0, 1.0, 0).with_velocity(1.0, 0.0);
+    let mut b = Particle::new(1.0, 1.0, 1);
+    assert!(!resolve_elastic_collision(&mut a, &mut b));
+    assert_eq!((a.vx, b.vx), (1.0, 0.0));
+}
+
+// Velocities only change in the response phase, so momentum & kinetic energy should be the same
+// at the end of a run as at the start, apart from f32 rounding
+#[test]
+fn momentum_and_energy_are_conserved_over_a_run() {
+    let config = SimulationConfig {
+        particle_count: 300,
+        bounds: (8.0, 8.0, 0.0),
+        particle_radius: RadiusDistribution::Fixed(0.2),
+        initial_speed: 0.3,
+        mass_range: (0.5, 4.0),
+        collision_response: true,
+        num_iterations: 40,
+        thread_count: 4,
+        movement_thread_count: 2,
+        log_collisions: false,
+        seed: Some(11),
+        ..SimulationConfig::default()
+    };
+    let mut particle_system = ParticleSystem::new(config);
+    particle_system.spawn_particles();
+    let momentum = particle_system.total_momentum();
+    let energy = particle_system.kinetic_energy();
+    let velocities: Vec<(f32, f32)> = particle_system.particles.iter().map(|p| (p.vx, p.vy)).collect();
+
+    particle_system.move_and_collide_particles();
+    assert!(particle_system.collision_count() > 0);
+    // The response actually did something
+    assert!(particle_system.particles.iter().zip(&velocities).any(|(p, v)| (p.vx, p.vy) != *v));
+
+    let after = particle_system.total_momentum();
+    let scale = energy.max(1.0);
+    assert!((after.0 - momentum.0).abs() < 1e-3 * scale, "x momentum {} -> {}", momentum.0, after.0);
+    assert!((after.1 - momentum.1).abs() < 1e-3 * scale, "y momentum {} -> {}", momentum.1, after.1);
+    let energy_after = particle_system.kinetic_energy();
+    assert!((energy_after - energy).abs() < 1e-3 * scale, "kinetic energy {} -> {}", energy, energy_after);
+}
@@ -0,0 +1,78 @@
+use clap::Parser;
+use particle_system::{Boundary, BroadPhaseKind, Cli, ConfigError, Dynamics, Integrator, RadiusDistribution, Scenario, ScenarioError, SimulationConfig};
+
+fn parse(args: &[&str]) -> SimulationConfig {
+    Cli::try_parse_from([&["colliding_particles"], args].concat()).unwrap().apply(SimulationConfig::default())
+}
+
+// The field the command line would be rejected for, if any
+fn rejected(args: &[&str]) -> Option<&'static str> {
+    parse(args).validate().err().map(|error| error.field)
+}
+
+#[test]
+fn no_arguments_leaves_the_defaults() {
+    let config = parse(&[]);
+    let defaults = SimulationConfig::default();
+    assert_eq!((config.particle_count, config.bounds, config.particle_radius), (defaults.particle_count, defaults.bounds, defaults.particle_radius));
+    assert_eq!((config.num_iterations, config.thread_count, config.movement_thread_count), (defaults.num_iterations, defaults.thread_count, defaults.movement_thread_count));
+    assert_eq!((config.broad_phase, config.seed), (defaults.broad_phase, defaults.seed));
+    assert_eq!(config.validate(), Ok(()));
+}
+
+#[test]
+fn arguments_override_the_defaults() {
+    let config = parse(&["-n", "50", "--bounds-x", "4", "--bounds-z", "2", "--boundary", "periodic", "--boundary-y", "reflect", "-r", "0.1", "--radius-max", "0.3"]);
+    assert_eq!((config.particle_count, config.bounds), (50, (4.0, 10.0, 2.0)));
+    assert_eq!(config.boundaries, (Boundary::Periodic, Boundary::Reflect, Boundary::Periodic));
+    assert_eq!(config.particle_radius, RadiusDistribution::Uniform { min: 0.1, max: 0.3 });
+
+    let config = parse(&["-i", "7", "-t", "5", "--movement-threads", "2", "--integrator", "velocity-verlet", "--dt", "0.05", "--broad-phase", "grid", "-s", "9"]);
+    assert_eq!((config.num_iterations, config.thread_count, config.movement_thread_count), (7, 5, 2));
+    assert!(matches!(config.dynamics, Dynamics::Integrated { integrator: Integrator::VelocityVerlet, dt, .. } if dt == 0.05));
+    assert_eq!((config.broad_phase, config.seed), (BroadPhaseKind::Grid, Some(9)));
+    assert_eq!(config.validate(), Ok(()));
+}
+
+#[test]
+fn values_a_run_cant_start_with_are_rejected() {
+    assert_eq!(rejected(&["-n", "0"]), Some("particle_count"));
+    assert_eq!(rejected(&["--bounds-x", "0"]), Some("bounds.x"));
+    assert_eq!(rejected(&["--bounds-y=-3"]), Some("bounds.y"));
+    assert_eq!(rejected(&["--bounds-z=-1"]), Some("bounds.z"));
+    assert_eq!(rejected(&["--bounds-x", "inf"]), Some("bounds.x"));
+    assert_eq!(rejected(&["-r", "0"]), Some("particle_radius"));
+    assert_eq!(rejected(&["--radius=-1"]), Some("particle_radius"));
+    assert_eq!(rejected(&["-r", "0.5", "--radius-max", "0.2"]), Some("particle_radius"));
+    assert_eq!(rejected(&["--initial-speed=-1"]), Some("initial_speed"));
+    assert_eq!(rejected(&["--mass-min", "2", "--mass-max", "1"]), Some("mass_range"));
+    assert_eq!(rejected(&["--integrator", "explicit-euler", "--dt", "0"]), Some("dynamics"));
+    assert_eq!(rejected(&["-i", "0"]), Some("num_iterations"));
+    // Neither prunes on z
+    assert_eq!(rejected(&["--bounds-z", "2", "--broad-phase", "quadtree"]), Some("broad_phase"));
+    assert_eq!(rejected(&["--bounds-z", "2", "--broad-phase", "sweep-and-prune"]), Some("broad_phase"));
+    assert_eq!(rejected(&["--broad-phase", "quadtree"]), None);
+    // Zero threads never gets as far as a config
+    assert!(Cli::try_parse_from(["colliding_particles", "-t", "0"]).is_err());
+
+    let error = parse(&["--bounds-x", "0"]).validate().unwrap_err();
+    assert_eq!(error.to_string(), "invalid value for `bounds.x`: must be a positive number, got 0");
+}
+
+#[test]
+fn scenarios_are_checked_the_same_way_under_their_own_names() {
+    let scenario = |particles: &str| format!("iterations = 5\n[particles]\n{}\n[bounds]\nx = 5.0\ny = 5.0\n[threads]\ntotal = 2\n", particles);
+    let field = |text: &str| match Scenario::from_toml(text) {
+        Err(ScenarioError::Invalid { field, .. }) => Some(field),
+        _ => None
+    };
+    assert_eq!(field(&scenario("count = 0\nradius = 0.1")), Some("particles.count"));
+    assert_eq!(field(&scenario("count = 10\nradius = 0.0")), Some("particles.radius"));
+    assert_eq!(field(&scenario("count = 10\nradius = 0.1\nmass = [2.0, 1.0]")), Some("particles.mass"));
+    assert!(Scenario::from_toml(&scenario("count = 10\nradius = 0.1")).is_ok());
+
+    // The same config the scenario runs with passes SimulationConfig::validate
+    let config = Scenario::from_toml(&scenario("count = 10\nradius = 0.1")).unwrap().config();
+    assert_eq!(config.validate(), Ok(()));
+    assert_eq!(SimulationConfig { pipeline_depth: 0, ..config }.validate(), Err(ConfigError { field: "pipeline_depth", reason: "must be at least 1".to_string() }));
+}
@@ -0,0 +1,129 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use clap::{Parser, ValueEnum};
+use particle_system::{compare_counters, counter_for, BroadPhaseKind, Cli, CollisionCounter, CounterKind, ParticleSystem, RadiusDistribution, Scenario, ShardedCounter, SimulationConfig, TwoD, SHARDS};
+
+fn config() -> SimulationConfig {
+    SimulationConfig {
+        particle_count: 400,
+        bounds: (12.0, 12.0, 0.0),
+        particle_radius: RadiusDistribution::Uniform { min: 0.1, max: 0.4 },
+        num_iterations: 10,
+        movement_thread_count: 1,
+        log_collisions: false,
+        record_collisions: true,
+        seed: Some(2023),
+        ..SimulationConfig::default()
+    }
+}
+
+#[test]
+fn sharded_counts_only_reach_the_total_at_the_end_of_a_pass() {
+    let counter = ShardedCounter::default();
+    counter.count(0);
+    counter.count(1);
+    // Past the last shard wraps round to the first ones
+    counter.count(SHARDS);
+    counter.task_done(0, 2);
+    assert_eq!(counter.total(), 0);
+    counter.end_pass();
+    assert_eq!(counter.total(), 3);
+    // Ending a pass empties the shards, so nothing is counted twice
+    counter.end_pass();
+    assert_eq!(counter.total(), 3);
+}
+
+#[test]
+fn every_backend_counts_concurrent_tasks_over_several_passes() {
+    for &kind in CounterKind::value_variants() {
+        let counter = counter_for(kind);
+        for _ in 0..3 {
+            thread::scope(|scope| {
+                // Twice as many threads as shards, so some share
+                for thread_id in 0..2 * SHARDS {
+                    let counter = &*counter;
+                    scope.spawn(move || {
+                        for _ in 0..500 {
+                            counter.count(thread_id);
+                        }
+                        counter.task_done(thread_id, 500);
+                    });
+                }
+            });
+            counter.end_pass();
+        }
+        assert_eq!(counter.total(), 3 * 2 * SHARDS * 500, "{}", counter.name());
+    }
+}
+
+#[test]
+fn totals_are_the_same_for_any_counter_and_thread_count() {
+    let run = |counter, broad_phase, thread_count| {
+        let mut particle_system = ParticleSystem::new(SimulationConfig { counter, broad_phase, thread_count, ..config() });
+        particle_system.spawn_particles();
+        particle_system.move_and_collide_particles();
+        assert_eq!(particle_system.collision_count(), particle_system.collision_events().len());
+        particle_system.collision_count()
+    };
+    let expected = run(CounterKind::Sharded, BroadPhaseKind::BruteForce, 2);
+    assert!(expected > 0);
+    for &counter in CounterKind::value_variants() {
+        for broad_phase in [BroadPhaseKind::BruteForce, BroadPhaseKind::Grid] {
+            // 100 threads leaves 99 collision threads, more than there are shards
+            for thread_count in [3, 13, 100] {
+                assert_eq!(run(counter, broad_phase, thread_count), expected, "{:?} counter, {:?} with {} threads", counter, broad_phase, thread_count);
+            }
+        }
+    }
+}
+
+// Counts every collision twice over, to show set_collision_counter is what gets used
+#[derive(Default)]
+struct Doubling(AtomicUsize);
+
+impl CollisionCounter for Doubling {
+    fn name(&self) -> &'static str {
+        "doubling"
+    }
+    fn count(&self, _thread_id: usize) {
+        self.0.fetch_add(2, Ordering::Relaxed);
+    }
+    fn total(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[test]
+fn a_custom_counter_can_be_plugged_in() {
+    let mut particle_system = ParticleSystem::new(config());
+    particle_system.set_collision_counter(Arc::new(Doubling::default()));
+    particle_system.spawn_particles();
+    particle_system.move_and_collide_particles();
+    assert!(!particle_system.collision_events().is_empty());
+    assert_eq!(particle_system.collision_count(), 2 * particle_system.collision_events().len());
+    assert_eq!(particle_system.collision_counter.name(), "doubling");
+}
+
+#[test]
+fn comparing_counters_reports_every_backend_correct() {
+    let reports = compare_counters::<f32, TwoD>(&SimulationConfig { thread_count: 4, record_collisions: false, ..config() });
+    assert_eq!(reports.iter().map(|report| report.kind).collect::<Vec<_>>(), CounterKind::value_variants());
+    for report in &reports {
+        assert!(report.expected > 0);
+        assert!(report.correct(), "{} counted {} of {}", report.name, report.collisions, report.expected);
+        assert!(report.throughput() > 0.0);
+    }
+}
+
+#[test]
+fn counters_are_chosen_on_the_command_line_and_in_scenarios() {
+    let parse = |args: &[&str]| Cli::parse_from([&["colliding_particles"], args].concat()).apply(SimulationConfig::default()).counter;
+    assert_eq!(parse(&[]), CounterKind::Sharded);
+    assert_eq!(parse(&["--counter", "mutex"]), CounterKind::Mutex);
+    assert_eq!(parse(&["--counter", "atomic-acq-rel"]), CounterKind::AtomicAcqRel);
+    assert_eq!(parse(&["--counter", "per-thread"]), CounterKind::PerThread);
+
+    let text = "iterations = 5\ncounter = \"atomic_seq_cst\"\n[particles]\ncount = 20\nradius = 0.1\n[bounds]\nx = 5.0\ny = 5.0\n[threads]\ntotal = 2\n";
+    assert_eq!(Scenario::from_toml(text).unwrap().config().counter, CounterKind::AtomicSeqCst);
+}
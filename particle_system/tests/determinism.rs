@@ -0,0 +1,72 @@
+use particle_system::{ParticleSystem, SimulationConfig};
+
+fn seeded_config(thread_count: usize) -> SimulationConfig {
+    SimulationConfig {
+        particle_count: 60,
+        num_iterations: 200,
+        thread_count,
+        log_collisions: false,
+        seed: Some(1234),
+        ..SimulationConfig::default()
+    }
+}
+
+fn positions(particle_system: &ParticleSystem) -> Vec<(u32, u32)> {
+    particle_system.particles.iter().map(|p| (p.x.to_bits(), p.y.to_bits())).collect()
+}
+
+#[test]
+fn same_seed_gives_same_trajectories_for_any_thread_count() {
+    let mut reference = ParticleSystem::new(seeded_config(1));
+    reference.spawn_particles();
+    reference.move_particles_loop();
+
+    for thread_count in [2, 3, 7, 12] {
+        let mut particle_system = ParticleSystem::new(seeded_config(thread_count));
+        particle_system.spawn_particles();
+        particle_system.move_particles_loop();
+        assert_eq!(positions(&particle_system), positions(&reference), "{} threads diverged", thread_count);
+    }
+}
+
+#[test]
+fn stepping_one_at_a_time_matches_one_long_loop() {
+    let mut reference = ParticleSystem::new(seeded_config(4));
+    reference.spawn_particles();
+    reference.move_particles_loop();
+
+    let mut particle_system = ParticleSystem::new(SimulationConfig { num_iterations: 1, ..seeded_config(4) });
+    particle_system.spawn_particles();
+    for _ in 0..200 {
+        particle_system.move_particles_loop();
+    }
+    assert_eq!(particle_system.step, 200);
+    assert_eq!(positions(&particle_system), positions(&reference));
+}
+
+#[test]
+fn different_seeds_give_different_trajectories() {
+    let mut a = ParticleSystem::new(seeded_config(4));
+    let mut b = ParticleSystem::new(SimulationConfig { seed: Some(4321), ..seeded_config(4) });
+    a.spawn_particles();
+    b.spawn_particles();
+    a.move_particles_loop();
+    b.move_particles_loop();
+    assert_ne!(positions(&a), positions(&b));
+}
+
+#[test]
+fn same_seed_gives_same_collision_count_for_any_thread_split() {
+    let run = |thread_count, movement_thread_count| {
+        let config = SimulationConfig { num_iterations: 50, movement_thread_count, ..seeded_config(thread_count) };
+        let mut particle_system = ParticleSystem::new(config);
+        particle_system.spawn_particles();
+        particle_system.move_and_collide_particles();
+        (positions(&particle_system), particle_system.collision_count())
+    };
+
+    let reference = run(2, 1);
+    for (thread_count, movement_thread_count) in [(3, 1), (12, 2), (12, 5), (100, 40)] {
+        assert_eq!(run(thread_count, movement_thread_count), reference);
+    }
+}
@@ -0,0 +1,92 @@
+use particle_system::{Boundary, BroadPhaseKind, MovementModel, ParticleSystem, RadiusDistribution, SimulationConfig, Species, StorageLayout};
+
+fn config() -> SimulationConfig {
+    SimulationConfig {
+        particle_count: 300,
+        bounds: (14.0, 14.0, 0.0),
+        particle_radius: RadiusDistribution::Uniform { min: 0.1, max: 0.35 },
+        species: vec![
+            Species { weight: 2.0, movement: MovementModel::RandomWalk },
+            Species { weight: 1.0, movement: MovementModel::Brownian { diffusion: 0.5 } }
+        ],
+        initial_speed: 0.2,
+        num_iterations: 25,
+        thread_count: 5,
+        movement_thread_count: 2,
+        log_collisions: false,
+        record_collisions: true,
+        seed: Some(31),
+        ..SimulationConfig::default()
+    }
+}
+
+// (id, x, y as bits) per particle, collisions, absorbed & (step, a, b) per recorded event
+type Outcome = (Vec<(usize, u32, u32)>, usize, usize, Vec<(usize, usize, usize)>);
+
+fn outcome(particle_system: &ParticleSystem) -> Outcome {
+    (
+        particle_system.particles.iter().map(|p| (p.id, p.x.to_bits(), p.y.to_bits())).collect(),
+        particle_system.collision_count(),
+        particle_system.absorbed_count(),
+        particle_system.collision_events().iter().map(|event| (event.step, event.a, event.b)).collect()
+    )
+}
+
+// The sequential version: one movement step, then a collision pass over it, every iteration
+fn sequential(config: &SimulationConfig) -> Outcome {
+    let mut particle_system = ParticleSystem::new(SimulationConfig { num_iterations: 1, ..config.clone() });
+    particle_system.spawn_particles();
+    for _ in 0..config.num_iterations {
+        particle_system.move_particles_loop();
+        particle_system.collide_particles();
+    }
+    outcome(&particle_system)
+}
+
+fn double_buffered(config: &SimulationConfig) -> Outcome {
+    let mut particle_system = ParticleSystem::new(config.clone());
+    particle_system.spawn_particles();
+    particle_system.move_and_collide_particles();
+    assert_eq!(particle_system.step, config.num_iterations);
+    outcome(&particle_system)
+}
+
+#[test]
+fn double_buffered_runs_match_the_sequential_version() {
+    for broad_phase in [BroadPhaseKind::BruteForce, BroadPhaseKind::Grid, BroadPhaseKind::Quadtree, BroadPhaseKind::SweepAndPrune] {
+        for storage in [StorageLayout::ArrayOfStructs, StorageLayout::StructOfArrays] {
+            let config = SimulationConfig { broad_phase, storage, ..config() };
+            let expected = sequential(&config);
+            assert!(expected.1 > 0);
+            assert_eq!(double_buffered(&config), expected, "{:?} in {:?}", broad_phase, storage);
+        }
+    }
+}
+
+#[test]
+fn double_buffered_runs_match_with_absorbing_walls_and_any_thread_split() {
+    let config = SimulationConfig {
+        boundaries: (Boundary::Absorb, Boundary::Periodic, Boundary::Clamp),
+        initial_speed: 0.6,
+        broad_phase: BroadPhaseKind::Grid,
+        ..config()
+    };
+    let expected = sequential(&config);
+    assert!(expected.2 > 0, "nothing was absorbed");
+    for (thread_count, movement_thread_count) in [(2, 1), (5, 2), (9, 4)] {
+        for storage in [StorageLayout::ArrayOfStructs, StorageLayout::StructOfArrays] {
+            let config = SimulationConfig { thread_count, movement_thread_count, storage, ..config.clone() };
+            assert_eq!(double_buffered(&config), expected, "{} threads, {} moving, {:?}", thread_count, movement_thread_count, storage);
+        }
+    }
+}
+
+#[test]
+fn short_runs_check_every_step_once() {
+    for num_iterations in [0, 1, 2] {
+        let config = SimulationConfig { num_iterations, ..config() };
+        let outcome = double_buffered(&config);
+        assert_eq!(outcome, sequential(&config), "{} iterations", num_iterations);
+        assert!(outcome.3.iter().all(|&(step, _, _)| (1..=num_iterations).contains(&step)));
+    }
+}
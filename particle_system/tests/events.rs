@@ -0,0 +1,56 @@
+use particle_system::{BroadPhaseKind, CollisionEvent, ParticleSystem, SimulationConfig};
+
+fn run(thread_count: usize, broad_phase: BroadPhaseKind, record_collisions: bool) -> ParticleSystem {
+    let config = SimulationConfig {
+        particle_count: 200,
+        bounds: (6.0, 6.0, 0.0),
+        num_iterations: 30,
+        thread_count,
+        movement_thread_count: 1,
+        log_collisions: false,
+        record_collisions,
+        broad_phase,
+        seed: Some(99),
+        ..SimulationConfig::default()
+    };
+    let mut particle_system = ParticleSystem::new(config);
+    particle_system.spawn_particles();
+    particle_system.move_and_collide_particles();
+    particle_system
+}
+
+// Everything but the thread that happened to find it
+fn without_threads(events: &[CollisionEvent]) -> Vec<CollisionEvent> {
+    events.iter().map(|event| CollisionEvent { thread_id: 0, ..*event }).collect()
+}
+
+#[test]
+fn every_counted_collision_is_recorded() {
+    let particle_system = run(6, BroadPhaseKind::BruteForce, true);
+    let events = particle_system.collision_events();
+    assert!(!events.is_empty());
+    assert_eq!(events.len(), particle_system.collision_count());
+
+    for event in events {
+        assert!(event.a < event.b);
+        assert!(event.step >= 1 && event.step <= 30);
+        assert!(event.thread_id < 5);
+    }
+    // Ordered by step, then by ids within a step
+    assert!(events.windows(2).all(|pair| (pair[0].step, pair[0].a, pair[0].b) < (pair[1].step, pair[1].a, pair[1].b)));
+}
+
+#[test]
+fn history_is_the_same_for_any_thread_count_or_broad_phase() {
+    let reference = without_threads(run(2, BroadPhaseKind::BruteForce, true).collision_events());
+    for (thread_count, broad_phase) in [(12, BroadPhaseKind::BruteForce), (4, BroadPhaseKind::Grid), (7, BroadPhaseKind::SweepAndPrune)] {
+        assert_eq!(without_threads(run(thread_count, broad_phase, true).collision_events()), reference);
+    }
+}
+
+#[test]
+fn nothing_is_recorded_unless_asked() {
+    let particle_system = run(4, BroadPhaseKind::BruteForce, false);
+    assert!(particle_system.collision_count() > 0);
+    assert!(particle_system.collision_events().is_empty());
+}
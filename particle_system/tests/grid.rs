@@ -0,0 +1,100 @@
+use std::collections::BTreeSet;
+use particle_system::{broad_phase_for, Boundary, BroadPhaseKind, Domain, ParticleSystem, RadiusDistribution, SimulationConfig, UniformGrid};
+
+fn spawned(particle_count: usize, bounds: f32, particle_radius: f32, seed: u64) -> ParticleSystem {
+    let config = SimulationConfig {
+        particle_count,
+        bounds: (bounds, bounds, 0.0),
+        particle_radius: RadiusDistribution::Fixed(particle_radius),
+        log_collisions: false,
+        seed: Some(seed),
+        ..SimulationConfig::default()
+    };
+    let mut particle_system = ParticleSystem::new(config);
+    particle_system.spawn_particles();
+    particle_system
+}
+
+#[test]
+fn grid_finds_exactly_the_brute_force_collisions() {
+    for (particle_count, bounds, radius) in [(0, 10.0, 0.125), (1, 10.0, 0.125), (200, 10.0, 0.125), (500, 5.0, 0.25), (300, 100.0, 0.005), (50, 1.0, 1.5)] {
+        let particle_system = spawned(particle_count, bounds, radius, particle_count as u64);
+        let particles = &particle_system.particles;
+        let grid = UniformGrid::build(particles, 2.0 * radius, &Domain::default());
+
+        let mut brute_force = BTreeSet::new();
+        for i in 0..particles.len() {
+            for j in i + 1..particles.len() {
+                if particles[i].collide(&particles[j]) {
+                    brute_force.insert((i, j));
+                }
+            }
+        }
+
+        let mut candidates = BTreeSet::new();
+        for i in 0..particles.len() {
+            for j in grid.candidates(i) {
+                assert!(candidates.insert((i, j)), "pair ({}, {}) produced twice", i, j);
+            }
+        }
+        let from_grid: BTreeSet<_> = candidates.into_iter().filter(|&(i, j)| particles[i].collide(&particles[j])).collect();
+        assert_eq!(from_grid, brute_force, "n={} bounds={} radius={}", particle_count, bounds, radius);
+    }
+}
+
+#[test]
+fn grid_collision_count_matches_brute_force_after_moving() {
+    let mut brute_force = spawned(400, 8.0, 0.125, 7);
+    let mut grid = spawned(400, 8.0, 0.125, 7);
+    grid.config.broad_phase = BroadPhaseKind::Grid;
+    grid.set_broad_phase(broad_phase_for(&grid.config));
+    for particle_system in [&mut brute_force, &mut grid] {
+        particle_system.config.num_iterations = 20;
+        particle_system.config.thread_count = 5;
+        particle_system.config.movement_thread_count = 2;
+        particle_system.move_and_collide_particles();
+    }
+    assert!(brute_force.collision_count() > 0);
+    assert_eq!(grid.collision_count(), brute_force.collision_count());
+}
+
+#[test]
+fn zero_and_tiny_radii_still_build_a_grid_that_matches_brute_force() {
+    let clamped = Domain::default();
+    let periodic = Domain { half: (5.0, 5.0, 0.0), boundaries: (Boundary::Periodic, Boundary::Periodic, Boundary::Clamp) };
+    for radius in [0.0, f32::MIN_POSITIVE, 1e-30, 1e-7] {
+        let mut particle_system = spawned(300, 10.0, radius, 11);
+        // Stack some particles on top of others, so even zero sized ones touch
+        for i in (0..300).step_by(7) {
+            let (x, y) = (particle_system.particles[i].x, particle_system.particles[i].y);
+            (particle_system.particles[i + 1].x, particle_system.particles[i + 1].y) = (x, y);
+        }
+        let particles = &particle_system.particles;
+        for domain in [&clamped, &periodic] {
+            // The cell size blows up the cell count long before it reaches a sensible size
+            let grid = UniformGrid::build(particles, 2.0 * radius, domain);
+            let mut brute_force = BTreeSet::new();
+            for i in 0..particles.len() {
+                for j in i + 1..particles.len() {
+                    if particles[i].collide_in(&particles[j], domain) {
+                        brute_force.insert((i, j));
+                    }
+                }
+            }
+            let from_grid: BTreeSet<_> = (0..particles.len())
+                .flat_map(|i| grid.candidates(i).map(move |j| (i, j)))
+                .filter(|&(i, j)| particles[i].collide_in(&particles[j], domain))
+                .collect();
+            assert!(brute_force.len() >= 300 / 7, "radius {}", radius);
+            assert_eq!(from_grid, brute_force, "radius {} in {:?}", radius, domain.boundaries);
+        }
+    }
+
+    // The whole run, as with --radius 0 --broad-phase grid
+    let mut grid = spawned(200, 10.0, 0.0, 5);
+    grid.config.broad_phase = BroadPhaseKind::Grid;
+    grid.set_broad_phase(broad_phase_for(&grid.config));
+    grid.config.num_iterations = 5;
+    grid.move_particles_loop();
+    grid.collide_particles();
+}
@@ -0,0 +1,119 @@
+use particle_system::{motion_models_for, thread_main, Dynamics, ForceField, Integrator, Particle, ParticleSystem, Scenario, SimulationConfig};
+
+const TRAP: ForceField = ForceField::Harmonic { stiffness: 1.0, centre: (0.0, 0.0, 0.0) };
+
+fn config(integrator: Integrator, dt: f32, force: ForceField) -> SimulationConfig {
+    SimulationConfig {
+        bounds: (1.0e4, 1.0e4, 0.0),
+        dynamics: Dynamics::Integrated { integrator, dt, force },
+        ..SimulationConfig::default()
+    }
+}
+
+fn energy(particles: &[Particle], force: &ForceField) -> f64 {
+    particles.iter().map(|p| (p.kinetic_energy() + force.potential_energy(p)) as f64).sum()
+}
+
+// Total energy after every step of one particle released from rest at x = 1 in a unit harmonic
+// trap (period 2 pi)
+fn trap_energies(integrator: Integrator, dt: f32, steps: usize) -> Vec<f64> {
+    let config = config(integrator, dt, TRAP);
+    let motion_models = motion_models_for(&config.species);
+    let mut particles = vec![Particle::new(1.0, 0.0, 0).with_velocity(0.0, 1.0).with_mass(2.0)];
+    let mut energies = vec![energy(&particles, &TRAP)];
+    for step in 0..steps {
+        thread_main(&mut particles, &config, &motion_models, 0, step..step + 1, 0);
+        energies.push(energy(&particles, &TRAP));
+    }
+    energies
+}
+
+fn max_relative_error(energies: &[f64]) -> f64 {
+    energies.iter().map(|e| (e - energies[0]).abs() / energies[0]).fold(0.0, f64::max)
+}
+
+// About 16 orbits
+const DT: f32 = 0.05;
+const STEPS: usize = 2000;
+
+#[test]
+fn explicit_euler_energy_grows_every_step() {
+    let energies = trap_energies(Integrator::ExplicitEuler, DT, STEPS);
+    assert!(energies.windows(2).all(|pair| pair[1] > pair[0]));
+    // Each step multiplies the energy by 1 + k dt^2 / m, so it runs away exponentially
+    let expected = (1.0 + 0.5 * (DT as f64).powi(2)).powi(STEPS as i32);
+    let growth = energies[STEPS] / energies[0];
+    assert!((growth / expected - 1.0).abs() < 0.01, "grew by {} expected {}", growth, expected);
+}
+
+#[test]
+fn semi_implicit_euler_energy_stays_bounded() {
+    let energies = trap_energies(Integrator::SemiImplicitEuler, DT, STEPS);
+    // Oscillates with an amplitude of order dt, but doesn't drift
+    let error = max_relative_error(&energies);
+    assert!(error < DT as f64, "max error {}", error);
+    let early = max_relative_error(&energies[..STEPS / 4]);
+    assert!(error < early * 1.1, "error grew from {} to {}", early, error);
+}
+
+#[test]
+fn velocity_verlet_energy_error_is_second_order() {
+    let coarse = max_relative_error(&trap_energies(Integrator::VelocityVerlet, DT, STEPS));
+    let fine = max_relative_error(&trap_energies(Integrator::VelocityVerlet, DT / 2.0, STEPS * 2));
+    assert!(coarse < (DT as f64).powi(2), "max error {}", coarse);
+    // Halving dt should quarter the error
+    let ratio = coarse / fine;
+    assert!((3.0..5.0).contains(&ratio), "error ratio {}", ratio);
+
+    // And beats semi-implicit Euler at the same step
+    assert!(coarse < max_relative_error(&trap_energies(Integrator::SemiImplicitEuler, DT, STEPS)) / 10.0);
+}
+
+#[test]
+fn velocity_verlet_is_exact_under_gravity() {
+    let gravity = ForceField::Gravity { acceleration: (0.0, -9.81, 0.0) };
+    let config = config(Integrator::VelocityVerlet, 0.01, gravity);
+    let motion_models = motion_models_for(&config.species);
+    let mut particles: Vec<Particle> = vec![Particle::new(0.0, 0.0, 0).with_velocity(3.0, 20.0)];
+    thread_main(&mut particles, &config, &motion_models, 0, 0..100, 0);
+    // One second of projectile motion
+    assert!((particles[0].x - 3.0).abs() < 1e-4);
+    assert!((particles[0].y - (20.0 - 0.5 * 9.81)).abs() < 1e-3, "y {}", particles[0].y);
+    assert!((particles[0].vy - (20.0 - 9.81)).abs() < 1e-3);
+}
+
+// Integrated runs go through the same pools & ignore the thread split, like the random walk
+#[test]
+fn integrated_runs_match_for_any_thread_count() {
+    let run = |thread_count| {
+        let mut particle_system = ParticleSystem::new(SimulationConfig {
+            particle_count: 300,
+            bounds: (20.0, 20.0, 0.0),
+            initial_speed: 1.0,
+            num_iterations: 200,
+            thread_count,
+            log_collisions: false,
+            seed: Some(17),
+            ..config(Integrator::SemiImplicitEuler, 0.02, TRAP)
+        });
+        particle_system.spawn_particles();
+        particle_system.move_particles_loop();
+        assert!(particle_system.potential_energy() > 0.0);
+        particle_system.particles.iter().map(|p| (p.x.to_bits(), p.y.to_bits(), p.vx.to_bits(), p.vy.to_bits())).collect::<Vec<_>>()
+    };
+    assert_eq!(run(1), run(6));
+}
+
+#[test]
+fn scenarios_select_the_integrated_mode() {
+    let text = "iterations = 1\n[particles]\ncount = 10\nradius = 0.1\n[bounds]\nx = 5.0\ny = 5.0\n[threads]\ntotal = 2\n[dynamics]\nmode = \"integrated\"\nintegrator = \"velocity_verlet\"\ndt = 0.005\nforce = { harmonic = { stiffness = 2.0, centre = [0.0, 1.0] } }\n";
+    let scenario = Scenario::from_toml(text).unwrap();
+    assert_eq!(scenario.config().dynamics, Dynamics::Integrated {
+        integrator: Integrator::VelocityVerlet,
+        dt: 0.005,
+        force: ForceField::Harmonic { stiffness: 2.0, centre: (0.0, 1.0, 0.0) }
+    });
+    assert!(Scenario::from_toml(&text.replace("dt = 0.005", "dt = 0.0")).is_err());
+    // Random walk unless asked
+    assert_eq!(Scenario::from_toml(&text[..text.find("[dynamics]").unwrap()]).unwrap().config().dynamics, Dynamics::RandomWalk);
+}
@@ -0,0 +1,96 @@
+// AtomicPositions under loom, which runs each case in every interleaving (& every choice of which
+// store a relaxed load sees) the memory model allows. Only built with the loom cfg:
+//
+//     RUSTFLAGS="--cfg loom" cargo test --release -p particle_system --test loom
+//
+// Every write below keeps x == y == z, so a read where they differ saw half of two writes.
+#![cfg(loom)]
+
+use loom::sync::Arc;
+use loom::thread;
+use particle_system::{AtomicPositions, Particle, Scalar, ThreeD};
+
+// Readers spin while a write is in progress, so with unbounded preemption the number of
+// interleavings never ends; 3 covers a write being interrupted at any point
+fn model(f: impl Fn() + Sync + Send + 'static) {
+    let mut builder = loom::model::Builder::new();
+    builder.preemption_bound = Some(3);
+    builder.check(f);
+}
+
+fn positions<T: Scalar>(count: usize) -> Arc<AtomicPositions<T, ThreeD>> {
+    let particles: Vec<Particle<T, ThreeD>> = (0..count).map(|id| Particle::new_3d(T::ZERO, T::ZERO, T::ZERO, id)).collect();
+    Arc::new(AtomicPositions::from_particles(&particles))
+}
+
+fn assert_not_torn<T: Scalar>((x, y, z): (T, T, T)) -> T {
+    assert!(x == y && y == z, "torn position ({}, {}, {})", x, y, z);
+    x
+}
+
+#[test]
+fn a_read_during_a_write_sees_the_old_or_the_new_position() {
+    model(|| {
+        let positions = positions::<f32>(1);
+        let writer = {
+            let positions = Arc::clone(&positions);
+            thread::spawn(move || positions.write(0, (1.0, 1.0, 1.0)))
+        };
+        let x = assert_not_torn(positions.read(0));
+        assert!(x == 0.0 || x == 1.0);
+        writer.join().unwrap();
+        assert_eq!(positions.read(0), (1.0, 1.0, 1.0));
+    });
+}
+
+#[test]
+fn reads_during_repeated_writes_are_never_torn_and_never_go_back() {
+    model(|| {
+        let positions = positions::<f32>(1);
+        let writer = {
+            let positions = Arc::clone(&positions);
+            thread::spawn(move || {
+                positions.write(0, (1.0, 1.0, 1.0));
+                positions.write(0, (2.0, 2.0, 2.0));
+            })
+        };
+        let first = assert_not_torn(positions.read(0));
+        let second = assert_not_torn(positions.read(0));
+        assert!(second >= first, "read {} after {}", second, first);
+        writer.join().unwrap();
+    });
+}
+
+#[test]
+fn double_precision_positions_are_never_torn() {
+    model(|| {
+        let positions = positions::<f64>(1);
+        let writer = {
+            let positions = Arc::clone(&positions);
+            thread::spawn(move || positions.write(0, (0.1, 0.1, 0.1)))
+        };
+        let x = assert_not_torn(positions.read(0));
+        assert!(x == 0.0 || x == 0.1);
+        writer.join().unwrap();
+    });
+}
+
+// Two movement threads, each writing its own particle, as with one chunk each
+#[test]
+fn writers_of_different_particles_dont_disturb_each_other() {
+    model(|| {
+        let positions = positions::<f32>(2);
+        let writers: Vec<_> = (0..2).map(|i| {
+            let positions = Arc::clone(&positions);
+            thread::spawn(move || positions.write(i, (i as f32 + 1.0, i as f32 + 1.0, i as f32 + 1.0)))
+        }).collect();
+        for i in 0..2 {
+            let x = assert_not_torn(positions.read(i));
+            assert!(x == 0.0 || x == i as f32 + 1.0);
+        }
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        assert_eq!((positions.read(0), positions.read(1)), ((1.0, 1.0, 1.0), (2.0, 2.0, 2.0)));
+    });
+}
@@ -0,0 +1,124 @@
+use particle_system::{MovementModel, Particle, ParticleSystem, Scenario, SimulationConfig, Species, VectorFieldDrift};
+
+// Domain large enough that nobody reaches the walls, so clamping never gets in the way
+fn spawned(species: Vec<Species>, particle_count: usize, thread_count: usize) -> ParticleSystem {
+    let config = SimulationConfig {
+        particle_count,
+        bounds: (1.0e4, 1.0e4, 0.0),
+        species,
+        thread_count,
+        log_collisions: false,
+        seed: Some(5),
+        ..SimulationConfig::default()
+    };
+    let mut particle_system = ParticleSystem::new(config);
+    particle_system.spawn_particles();
+    particle_system
+}
+
+// The few particles that started within a step or two of the wall & were clamped
+fn on_wall(particle: &Particle) -> bool {
+    particle.x.abs() == 5000.0 || particle.y.abs() == 5000.0
+}
+
+fn one_species(movement: MovementModel) -> Vec<Species> {
+    vec![Species { weight: 1.0, movement }]
+}
+
+fn positions(particle_system: &ParticleSystem) -> Vec<(f32, f32)> {
+    particle_system.particles.iter().map(|p| (p.x, p.y)).collect()
+}
+
+fn moved(particle_system: &mut ParticleSystem, steps: usize) -> Vec<(f32, f32)> {
+    let start = positions(particle_system);
+    particle_system.config.num_iterations = steps;
+    particle_system.move_particles_loop();
+    particle_system.particles.iter().zip(start).map(|(p, s)| (p.x - s.0, p.y - s.1)).collect()
+}
+
+#[test]
+fn brownian_mean_squared_displacement_grows_as_4dt() {
+    let diffusion = 0.3;
+    let steps = 50;
+    let mut particle_system = spawned(one_species(MovementModel::Brownian { diffusion }), 4000, 4);
+    let displacements = moved(&mut particle_system, steps);
+    let msd = displacements.iter().map(|d| (d.0 * d.0 + d.1 * d.1) as f64).sum::<f64>() / displacements.len() as f64;
+    let expected = 4.0 * diffusion as f64 * steps as f64;
+    assert!((msd - expected).abs() < 0.05 * expected, "msd {} expected {}", msd, expected);
+}
+
+#[test]
+fn drift_moves_every_particle_by_the_same_vector() {
+    let mut particle_system = spawned(one_species(MovementModel::Drift { velocity: (0.5, -0.25, 0.0) }), 100, 3);
+    let displacements = moved(&mut particle_system, 8);
+    for (_, d) in particle_system.particles.iter().zip(displacements).filter(|(p, _)| !on_wall(p)) {
+        assert!((d.0 - 4.0).abs() < 1e-2 && (d.1 + 2.0).abs() < 1e-2, "{:?}", d);
+    }
+}
+
+#[test]
+fn levy_steps_are_heavy_tailed_and_capped() {
+    let (alpha, min_step, max_step) = (1.5, 0.1, 50.0);
+    let mut particle_system = spawned(one_species(MovementModel::Levy { alpha, min_step, max_step }), 20000, 4);
+    let displacements = moved(&mut particle_system, 1);
+    let lengths: Vec<f32> = particle_system.particles.iter().zip(displacements).filter(|(p, _)| !on_wall(p)).map(|(_, d)| (d.0 * d.0 + d.1 * d.1).sqrt()).collect();
+    // Positions are up to 5000 from the origin, so allow for f32 rounding there
+    assert!(lengths.iter().all(|&l| l >= min_step - 1e-3 && l <= max_step + 1e-3));
+    // P(l > 10 min_step) = 10^-alpha, about 3%; a Gaussian of similar scale would never get there
+    let long = lengths.iter().filter(|&&l| l > 10.0 * min_step).count() as f32 / lengths.len() as f32;
+    assert!((long - 10f32.powf(-alpha)).abs() < 0.01, "fraction of long jumps {}", long);
+}
+
+#[test]
+fn vector_field_drift_follows_the_field() {
+    let mut particle_system = spawned(Vec::from([Species::default()]), 200, 4);
+    // Contract towards the origin by 10% a step
+    particle_system.set_motion_model(0, Box::new(VectorFieldDrift::new(|x: f32, y: f32, _z: f32| (-0.1 * x, -0.1 * y, 0.0))));
+    assert_eq!(particle_system.motion_model(0).name(), "vector_field");
+    let start = positions(&particle_system);
+    moved(&mut particle_system, 3);
+    for (p, s) in particle_system.particles.iter().zip(start) {
+        let expected = (s.0 * 0.729, s.1 * 0.729);
+        assert!((p.x - expected.0).abs() < 1e-2 && (p.y - expected.1).abs() < 1e-2, "{:?} {:?}", (p.x, p.y), expected);
+    }
+}
+
+#[test]
+fn each_species_moves_by_its_own_model_for_any_thread_count() {
+    let species = vec![
+        Species { weight: 3.0, movement: MovementModel::Drift { velocity: (1.0, 0.0, 0.0) } },
+        Species { weight: 1.0, movement: MovementModel::Brownian { diffusion: 0.5 } }
+    ];
+    let run = |thread_count| {
+        let mut particle_system = spawned(species.clone(), 2000, thread_count);
+        let displacements = moved(&mut particle_system, 4);
+        (particle_system, displacements)
+    };
+    let (particle_system, displacements) = run(1);
+
+    let drifting = particle_system.particles.iter().filter(|p| p.species == 0).count() as f32 / 2000.0;
+    assert!((drifting - 0.75).abs() < 0.03, "species 0 share {}", drifting);
+    for (particle, d) in particle_system.particles.iter().zip(&displacements) {
+        if particle.species == 0 && !on_wall(particle) {
+            assert!((d.0 - 4.0).abs() < 1e-2 && d.1 == 0.0);
+        }
+    }
+    assert!(particle_system.particles.iter().zip(&displacements).any(|(p, d)| p.species == 1 && d.1 != 0.0));
+
+    assert_eq!(run(7).1, displacements);
+}
+
+#[test]
+fn scenarios_choose_movement_per_run_or_per_species() {
+    let base = "iterations = 1\n[particles]\ncount = 10\nradius = 0.1\n[bounds]\nx = 5.0\ny = 5.0\n[threads]\ntotal = 2\n";
+    let per_run = Scenario::from_toml(&format!("movement = {{ brownian = {{ diffusion = 0.2 }} }}\n{}", base)).unwrap();
+    assert_eq!(per_run.config().species, one_species(MovementModel::Brownian { diffusion: 0.2 }));
+
+    let per_species = Scenario::from_toml(&format!("{}[[species]]\nweight = 2.0\nmovement = \"random_walk\"\n[[species]]\nmovement = {{ drift = {{ velocity = [0.1, 0.0] }} }}\n", base)).unwrap();
+    assert_eq!(per_species.config().species, vec![
+        Species { weight: 2.0, movement: MovementModel::RandomWalk },
+        Species { weight: 1.0, movement: MovementModel::Drift { velocity: (0.1, 0.0, 0.0) } }
+    ]);
+
+    assert!(Scenario::from_toml(&format!("movement = {{ levy = {{ alpha = 3.0, min_step = 0.1, max_step = 1.0 }} }}\n{}", base)).is_err());
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use particle_system::partition::{chunk_len, pair_count, pairs_in_rows, partition_pairs};
+use particle_system::{Particle, ParticleSystem, RadiusDistribution, SimulationConfig};
+
+// Brute-force oracle: every unordered pair, once
+fn all_pairs(particle_count: usize) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    for i in 0..particle_count {
+        for j in 0..particle_count {
+            if i < j {
+                pairs.push((i, j));
+            }
+        }
+    }
+    pairs
+}
+
+#[test]
+fn every_pair_is_covered_exactly_once() {
+    for particle_count in 0..=40 {
+        for thread_count in 1..=50 {
+            let mut seen: HashMap<(usize, usize), usize> = HashMap::new();
+            for rows in partition_pairs(particle_count, thread_count) {
+                for pair in pairs_in_rows(rows, particle_count) {
+                    *seen.entry(pair).or_default() += 1;
+                }
+            }
+
+            let expected = all_pairs(particle_count);
+            assert_eq!(seen.len(), expected.len(), "n={} t={}", particle_count, thread_count);
+            for pair in expected {
+                assert_eq!(seen.get(&pair), Some(&1), "pair {:?} with n={} t={}", pair, particle_count, thread_count);
+            }
+        }
+    }
+}
+
+#[test]
+fn ranges_tile_the_rows_in_order() {
+    for (particle_count, thread_count) in [(0, 4), (1, 12), (5, 64), (100, 12), (101, 12), (1000, 7)] {
+        let ranges = partition_pairs(particle_count, thread_count);
+        assert!(ranges.len() <= thread_count);
+        let mut next = 0;
+        for rows in &ranges {
+            assert_eq!(rows.start, next);
+            assert!(!rows.is_empty());
+            next = rows.end;
+        }
+        assert_eq!(next, particle_count);
+    }
+}
+
+#[test]
+fn ranges_are_balanced_by_pair_count() {
+    let particle_count = 1000;
+    let thread_count = 12;
+    let ranges = partition_pairs(particle_count, thread_count);
+    assert_eq!(ranges.len(), thread_count);
+
+    let fair_share = pair_count(particle_count) / thread_count;
+    for rows in ranges {
+        let pairs = pairs_in_rows(rows, particle_count).count();
+        // Each range may overshoot its share by at most one row
+        assert!(pairs.abs_diff(fair_share) <= particle_count, "{} pairs vs {} share", pairs, fair_share);
+    }
+}
+
+#[test]
+fn chunk_len_is_never_zero() {
+    assert_eq!(chunk_len(0, 12), 1);
+    assert_eq!(chunk_len(5, 12), 1);
+    assert_eq!(chunk_len(100, 12), 9);
+    assert_eq!(chunk_len(100, 0), 100);
+}
+
+// Collision counts from the threaded path must match a brute-force count over the same positions
+#[test]
+fn collide_particles_matches_brute_force_for_any_thread_count() {
+    for (particle_count, thread_count) in [(1, 1), (2, 12), (13, 12), (100, 12), (101, 7), (64, 64), (10, 100)] {
+        let config = SimulationConfig {
+            particle_count,
+            bounds: (3.0, 3.0, 0.0),
+            particle_radius: RadiusDistribution::Fixed(0.25),
+            thread_count,
+            log_collisions: false,
+            seed: Some(particle_count as u64),
+            ..SimulationConfig::default()
+        };
+        let mut particle_system = ParticleSystem::new(config.clone());
+        particle_system.spawn_particles();
+        particle_system.collide_particles();
+
+        let particles: &[Particle] = &particle_system.particles;
+        let expected = all_pairs(particle_count).into_iter().filter(|&(i, j)| particles[i].collide(&particles[j])).count();
+        assert_eq!(particle_system.collision_count(), expected, "n={} t={}", particle_count, thread_count);
+    }
+}
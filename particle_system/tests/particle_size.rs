@@ -0,0 +1,82 @@
+use particle_system::{broad_phase_for, BroadPhaseKind, Particle, ParticleSystem, RadiusDistribution, SimulationConfig};
+
+fn spawned(particle_radius: RadiusDistribution, particle_count: usize) -> ParticleSystem {
+    let config = SimulationConfig {
+        particle_count,
+        bounds: (20.0, 20.0, 0.0),
+        particle_radius,
+        num_iterations: 10,
+        thread_count: 4,
+        movement_thread_count: 1,
+        log_collisions: false,
+        seed: Some(31),
+        ..SimulationConfig::default()
+    };
+    let mut particle_system = ParticleSystem::new(config);
+    particle_system.spawn_particles();
+    particle_system
+}
+
+fn radii(particle_system: &ParticleSystem) -> Vec<f32> {
+    particle_system.particles.iter().map(|p| p.radius).collect()
+}
+
+#[test]
+fn contact_distance_is_the_sum_of_the_radii() {
+    let small = Particle::new(0.0, 0.0, 0).with_radius(0.1);
+    let large = Particle::new(0.0, 0.0, 1).with_radius(0.5);
+    assert!(small.collide(&Particle { x: 0.6, ..large }));
+    assert!(!small.collide(&Particle { x: 0.61, ..large }));
+    assert!(!small.collide(&Particle { x: 0.21, ..small }));
+    // Symmetric
+    assert!(Particle { x: 0.6, ..large }.collide(&small));
+}
+
+#[test]
+fn spawned_sizes_follow_the_distribution() {
+    assert!(radii(&spawned(RadiusDistribution::Fixed(0.3), 100)).iter().all(|&r| r == 0.3));
+
+    let uniform = radii(&spawned(RadiusDistribution::Uniform { min: 0.1, max: 0.4 }, 2000));
+    assert!(uniform.iter().all(|&r| (0.1..=0.4).contains(&r)));
+    let mean = uniform.iter().sum::<f32>() / uniform.len() as f32;
+    assert!((mean - 0.25).abs() < 0.01, "uniform mean {}", mean);
+
+    let mut log_normal = radii(&spawned(RadiusDistribution::LogNormal { median: 0.2, sigma: 0.5 }, 2000));
+    assert!(log_normal.iter().all(|&r| r > 0.0));
+    log_normal.sort_by(f32::total_cmp);
+    let median = log_normal[log_normal.len() / 2];
+    assert!((median - 0.2).abs() < 0.02, "log-normal median {}", median);
+
+    let bidisperse = radii(&spawned(RadiusDistribution::Bidisperse { small: 0.1, large: 0.5, large_fraction: 0.25 }, 2000));
+    assert!(bidisperse.iter().all(|&r| r == 0.1 || r == 0.5));
+    let large = bidisperse.iter().filter(|&&r| r == 0.5).count() as f32 / bidisperse.len() as f32;
+    assert!((large - 0.25).abs() < 0.03, "large fraction {}", large);
+}
+
+// Sizes come from the spawn stream after the position, so changing the distribution never moves anyone
+#[test]
+fn sizes_do_not_change_positions() {
+    let fixed = spawned(RadiusDistribution::Fixed(0.125), 50);
+    let mixed = spawned(RadiusDistribution::LogNormal { median: 0.1, sigma: 1.0 }, 50);
+    for (a, b) in fixed.particles.iter().zip(&mixed.particles) {
+        assert_eq!((a.x, a.y), (b.x, b.y));
+    }
+}
+
+// A few large particles among many small ones; every broad phase has to size itself from the
+// largest to find the same collisions as brute force
+#[test]
+fn broad_phases_agree_on_a_polydisperse_mixture() {
+    let run = |broad_phase| {
+        let mut particle_system = spawned(RadiusDistribution::Bidisperse { small: 0.05, large: 0.8, large_fraction: 0.05 }, 800);
+        particle_system.config.broad_phase = broad_phase;
+        particle_system.set_broad_phase(broad_phase_for(&particle_system.config));
+        particle_system.move_and_collide_particles();
+        particle_system.collision_count()
+    };
+    let expected = run(BroadPhaseKind::BruteForce);
+    assert!(expected > 0);
+    for broad_phase in [BroadPhaseKind::Grid, BroadPhaseKind::Quadtree, BroadPhaseKind::SweepAndPrune] {
+        assert_eq!(run(broad_phase), expected, "{:?}", broad_phase);
+    }
+}
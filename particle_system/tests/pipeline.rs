@@ -0,0 +1,99 @@
+use particle_system::{Boundary, BroadPhaseKind, ParticleSystem, RadiusDistribution, Scenario, SimulationConfig, StorageLayout, Strategy};
+
+fn config() -> SimulationConfig {
+    SimulationConfig {
+        particle_count: 300,
+        bounds: (14.0, 14.0, 0.0),
+        particle_radius: RadiusDistribution::Uniform { min: 0.1, max: 0.35 },
+        initial_speed: 0.2,
+        num_iterations: 30,
+        thread_count: 4,
+        movement_thread_count: 2,
+        log_collisions: false,
+        record_collisions: true,
+        seed: Some(404),
+        ..SimulationConfig::default()
+    }
+}
+
+// (id, x, y as bits) per particle, collisions, absorbed & (step, a, b) per recorded event
+type Outcome = (Vec<(usize, u32, u32)>, usize, usize, Vec<(usize, usize, usize)>);
+
+fn run(config: &SimulationConfig, pipelined: bool) -> Outcome {
+    let mut particle_system = ParticleSystem::new(config.clone());
+    particle_system.spawn_particles();
+    if pipelined {
+        let stats = particle_system.pipelined_move_and_collide_particles();
+        assert_eq!(stats.snapshots, config.num_iterations);
+    } else {
+        particle_system.move_and_collide_particles();
+    }
+    assert_eq!(particle_system.step, config.num_iterations);
+    (
+        particle_system.particles.iter().map(|p| (p.id, p.x.to_bits(), p.y.to_bits())).collect(),
+        particle_system.collision_count(),
+        particle_system.absorbed_count(),
+        particle_system.collision_events().iter().map(|event| (event.step, event.a, event.b)).collect()
+    )
+}
+
+#[test]
+fn pipelined_runs_match_move_and_collide() {
+    for broad_phase in [BroadPhaseKind::BruteForce, BroadPhaseKind::Grid, BroadPhaseKind::SweepAndPrune] {
+        for storage in [StorageLayout::ArrayOfStructs, StorageLayout::StructOfArrays] {
+            let config = SimulationConfig { broad_phase, storage, ..config() };
+            let expected = run(&config, false);
+            assert!(expected.1 > 0);
+            for pipeline_depth in [1, 3, 64] {
+                let config = SimulationConfig { pipeline_depth, ..config.clone() };
+                assert_eq!(run(&config, true), expected, "{:?} in {:?}, depth {}", broad_phase, storage, pipeline_depth);
+            }
+        }
+    }
+}
+
+#[test]
+fn pipelined_runs_match_with_absorbing_walls_and_fall_back_for_collision_response() {
+    let config = SimulationConfig { boundaries: (Boundary::Absorb, Boundary::Absorb, Boundary::Clamp), initial_speed: 0.6, broad_phase: BroadPhaseKind::Grid, ..config() };
+    let expected = run(&config, false);
+    assert!(expected.2 > 0, "nothing was absorbed");
+    assert_eq!(run(&config, true), expected);
+
+    // Can't be pipelined, so it runs move_and_collide_particles & reports no stalls
+    let config = SimulationConfig { collision_response: true, mass_range: (1.0, 2.0), ..config };
+    let mut particle_system = ParticleSystem::new(config.clone());
+    particle_system.spawn_particles();
+    let stats = particle_system.pipelined_move_and_collide_particles();
+    assert_eq!(stats.snapshots, 0);
+    assert_eq!(particle_system.collision_count(), run(&config, false).1);
+}
+
+#[test]
+fn stalls_show_which_stage_is_the_bottleneck() {
+    // A brute force check of 2000 particles is far slower than moving them, so movement fills the
+    // channel & spends most of the run waiting for collision checking
+    let config = SimulationConfig { particle_count: 2_000, bounds: (60.0, 60.0, 0.0), num_iterations: 12, pipeline_depth: 1, record_collisions: false, ..config() };
+    let mut particle_system = ParticleSystem::new(config);
+    particle_system.spawn_particles();
+    let stats = particle_system.pipelined_move_and_collide_particles();
+    assert!(stats.movement_stall > stats.collision_stall, "{:?}", stats);
+    assert_eq!(stats.bottleneck(), "collision");
+}
+
+#[test]
+fn scenarios_run_pipelined_and_report_stalls() {
+    let text = "iterations = 10\nstrategy = \"pipelined\"\nseed = 8\n[particles]\ncount = 60\nradius = 0.2\n[bounds]\nx = 6.0\ny = 6.0\n[threads]\ntotal = 3\nmovement = 1\npipeline_depth = 4\n";
+    let scenario = Scenario::from_toml(text).unwrap();
+    assert_eq!(scenario.strategy, Strategy::Pipelined);
+    assert_eq!(scenario.config().pipeline_depth, 4);
+    let summary = scenario.run().unwrap();
+    assert!(summary.movement_stall_ms.is_some() && summary.collision_stall_ms.is_some());
+
+    // Same collisions as move_and_collide
+    let move_and_collide = Scenario::from_toml(&text.replace("\"pipelined\"", "\"move_and_collide\"")).unwrap().run().unwrap();
+    assert_eq!(summary.collisions, move_and_collide.collisions);
+    assert_eq!(move_and_collide.movement_stall_ms, None);
+
+    assert!(Scenario::from_toml(&text.replace("pipeline_depth = 4", "pipeline_depth = 0")).is_err());
+    assert!(Scenario::from_toml(&text.replace("movement = 1\n", "")).is_err());
+}
@@ -0,0 +1,126 @@
+use clap::Parser;
+use particle_system::{Boundary, BroadPhaseKind, Cli, Dimension, Dynamics, ForceField, Integrator, MovementModel, Particle, ParticleSystem, Precision, RadiusDistribution, Scalar, Scenario, SimulationConfig, Species, ThreeD, TwoD};
+
+fn spawned<T: Scalar, D: Dimension>(config: &SimulationConfig) -> ParticleSystem<T, D> {
+    let mut particle_system = ParticleSystem::with_config(SimulationConfig { log_collisions: false, ..config.clone() });
+    particle_system.spawn_particles();
+    particle_system
+}
+
+// Randomness is drawn in f32 for both precisions, so the same seed gives the same run; only the
+// rounding differs
+#[test]
+fn f64_runs_follow_the_f32_run_with_the_same_seed() {
+    let config = SimulationConfig {
+        particle_count: 300,
+        bounds: (1.0e3, 1.0e3, 0.0),
+        species: vec![
+            Species { weight: 1.0, movement: MovementModel::RandomWalk },
+            Species { weight: 1.0, movement: MovementModel::Brownian { diffusion: 0.2 } }
+        ],
+        initial_speed: 0.5,
+        num_iterations: 200,
+        thread_count: 3,
+        seed: Some(21),
+        ..SimulationConfig::default()
+    };
+    let mut single = spawned::<f32, TwoD>(&config);
+    let mut double = spawned::<f64, TwoD>(&config);
+    for (a, b) in single.particles.iter().zip(&double.particles) {
+        assert_eq!((a.x as f64, a.y as f64, a.vx as f64, a.radius as f64), (b.x, b.y, b.vx, b.radius));
+        assert_eq!(a.species, b.species);
+    }
+
+    single.move_particles_loop();
+    double.move_particles_loop();
+    let largest_gap = single.particles.iter().zip(&double.particles).map(|(a, b)| (a.x as f64 - b.x).abs().max((a.y as f64 - b.y).abs())).fold(0.0, f64::max);
+    assert!(largest_gap > 0.0, "f64 should round differently");
+    assert!(largest_gap < 1e-2, "f32 & f64 runs drifted {} apart", largest_gap);
+}
+
+// 0.1 has no exact binary form, and adding it to a growing f32 position loses more of it every step
+#[test]
+fn f64_does_not_accumulate_rounding_drift() {
+    let steps = 100_000;
+    let drift = |precision: Precision| -> f64 {
+        let config = SimulationConfig {
+            bounds: (1.0e6, 1.0e6, 0.0),
+            species: vec![Species { weight: 1.0, movement: MovementModel::Drift { velocity: (0.1, 0.0, 0.0) } }],
+            num_iterations: steps,
+            thread_count: 1,
+            log_collisions: false,
+            seed: Some(1),
+            ..SimulationConfig::default()
+        };
+        fn run<T: Scalar>(config: SimulationConfig) -> f64 {
+            let mut particle_system = ParticleSystem::<T>::with_config(config);
+            particle_system.particles = vec![Particle::new(T::ZERO, T::ZERO, 0)];
+            particle_system.move_particles_loop();
+            particle_system.particles[0].x.to_f64()
+        }
+        match precision {
+            Precision::F32 => run::<f32>(config),
+            Precision::F64 => run::<f64>(config)
+        }
+    };
+    // The step both precisions actually take is 0.1 rounded to f32
+    let expected = steps as f64 * 0.1f32 as f64;
+    let (single, double) = (drift(Precision::F32), drift(Precision::F64));
+    assert!((double - expected).abs() < 1e-6, "f64 ended at {} instead of {}", double, expected);
+    assert!((single - expected).abs() > 1.0, "f32 ended at {}, expected visible drift from {}", single, expected);
+}
+
+#[test]
+fn f64_collisions_conserve_energy_to_double_precision() {
+    let config = SimulationConfig {
+        particle_count: 400,
+        bounds: (6.0, 6.0, 6.0),
+        boundaries: (Boundary::Reflect, Boundary::Reflect, Boundary::Reflect),
+        particle_radius: RadiusDistribution::Fixed(0.15),
+        initial_speed: 1.0,
+        mass_range: (1.0, 3.0),
+        dynamics: Dynamics::Integrated { integrator: Integrator::VelocityVerlet, dt: 0.05, force: ForceField::None },
+        collision_response: true,
+        broad_phase: BroadPhaseKind::Grid,
+        num_iterations: 60,
+        movement_thread_count: 1,
+        seed: Some(12),
+        ..SimulationConfig::default()
+    };
+    let run = |thread_count| {
+        let mut particle_system = spawned::<f64, ThreeD>(&SimulationConfig { thread_count, ..config.clone() });
+        let energy = particle_system.kinetic_energy();
+        particle_system.move_and_collide_particles();
+        assert!(particle_system.collision_count() > 0);
+        let energy_after = particle_system.kinetic_energy();
+        assert!((energy_after - energy).abs() < 1e-9 * energy, "kinetic energy {} -> {}", energy, energy_after);
+        particle_system.particles.iter().map(|p| (p.x.to_bits(), p.y.to_bits(), p.z.to_bits(), p.vx.to_bits())).collect::<Vec<_>>()
+    };
+    // Still independent of the thread count
+    assert_eq!(run(2), run(5));
+}
+
+#[test]
+fn precision_is_chosen_on_the_command_line_and_in_scenarios() {
+    let parse = |args: &[&str]| Cli::parse_from([&["colliding_particles"], args].concat()).apply(SimulationConfig::default()).precision;
+    assert_eq!(parse(&[]), Precision::F32);
+    assert_eq!(parse(&["--precision", "f64"]), Precision::F64);
+    assert!(Cli::try_parse_from(["colliding_particles", "--precision", "f16"]).is_err());
+
+    let path = std::env::temp_dir().join(format!("particle_system_precision_{}", std::process::id())).join("positions.csv");
+    let text = format!("iterations = 5\nprecision = \"f64\"\nseed = 3\n[particles]\ncount = 20\nradius = 0.1\n[bounds]\nx = 5.0\ny = 5.0\n[threads]\ntotal = 2\n[[output]]\nkind = \"positions\"\npath = {:?}\n", path);
+    let scenario = Scenario::from_toml(&text).unwrap();
+    assert_eq!(scenario.config().precision, Precision::F64);
+    let summary = scenario.run().unwrap();
+    assert_eq!(summary.precision, Precision::F64);
+
+    // Written at full double precision, and matching the system the scenario builds
+    let csv = std::fs::read_to_string(&path).unwrap();
+    let mut expected = scenario.build::<f64, TwoD>();
+    expected.move_particles_loop();
+    let first: Vec<f64> = csv.lines().nth(1).unwrap().split(',').skip(1).map(|value| value.parse().unwrap()).collect();
+    let particle = &expected.particles[0];
+    assert_eq!(first, vec![particle.x, particle.y, particle.z(), particle.radius]);
+    assert!(Scenario::from_toml(&text.replace("\"f64\"", "\"f16\"")).is_err());
+    let _ = std::fs::remove_dir_all(path.parent().unwrap());
+}
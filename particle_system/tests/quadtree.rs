@@ -0,0 +1,102 @@
+use std::collections::BTreeSet;
+use rand::Rng;
+use particle_system::{max_contact_distance, Aabb, BroadPhaseKind, Domain, Particle, ParticleRng, ParticleSystem, Quadtree, SimulationConfig};
+
+// A few tight clumps, the case the quadtree is meant for
+fn clustered(particle_count: usize, seed: u64) -> Vec<Particle> {
+    let centres = [(-3.0, -3.0), (2.5, 1.0), (4.0, -4.5)];
+    (0..particle_count).map(|i| {
+        let mut rng = ParticleRng::for_spawn(seed, i);
+        let centre = centres[i % centres.len()];
+        Particle::new(centre.0 + rng.random_range(-0.4..0.4), centre.1 + rng.random_range(-0.4..0.4), i).with_radius(0.05)
+    }).collect()
+}
+
+fn domain() -> Aabb {
+    Aabb { min: (-5.0, -5.0), max: (5.0, 5.0) }
+}
+
+fn candidate_collisions(tree: &Quadtree, particles: &[Particle]) -> BTreeSet<(usize, usize)> {
+    let contact_distance = max_contact_distance(particles);
+    let mut candidates = BTreeSet::new();
+    for i in 0..particles.len() {
+        tree.for_each_candidate(i, particles, contact_distance, &Domain::default(), |j| {
+            assert!(candidates.insert((i, j)), "pair ({}, {}) produced twice", i, j);
+        });
+    }
+    candidates.into_iter().filter(|&(i, j)| particles[i].collide(&particles[j])).collect()
+}
+
+fn brute_force_collisions(particles: &[Particle]) -> BTreeSet<(usize, usize)> {
+    let mut pairs = BTreeSet::new();
+    for i in 0..particles.len() {
+        for j in i + 1..particles.len() {
+            if particles[i].collide(&particles[j]) {
+                pairs.insert((i, j));
+            }
+        }
+    }
+    pairs
+}
+
+#[test]
+fn quadtree_finds_exactly_the_brute_force_collisions() {
+    for particle_count in [0, 1, 9, 300, 1000] {
+        let particles = clustered(particle_count, 3);
+        let tree = Quadtree::build(&particles, domain());
+        assert_eq!(candidate_collisions(&tree, &particles), brute_force_collisions(&particles), "n={}", particle_count);
+    }
+}
+
+#[test]
+fn incremental_update_matches_a_fresh_build() {
+    let mut particles = clustered(600, 11);
+    let mut tree = Quadtree::build(&particles, domain());
+    for step in 0..30 {
+        // Drift every clump towards the middle so particles cross leaves & leaves merge
+        for particle in particles.iter_mut() {
+            let mut rng = ParticleRng::for_step(11, particle.id, step);
+            particle.x = (particle.x * 0.9 + rng.random_range(-0.2..0.2)).clamp(-5.0, 5.0);
+            particle.y = (particle.y * 0.9 + rng.random_range(-0.2..0.2)).clamp(-5.0, 5.0);
+        }
+        tree.update(&particles);
+
+        let fresh = Quadtree::build(&particles, domain());
+        assert_eq!(candidate_collisions(&tree, &particles), candidate_collisions(&fresh, &particles), "step {}", step);
+    }
+}
+
+#[test]
+fn region_query_returns_the_particles_inside() {
+    let particles = clustered(500, 5);
+    let tree = Quadtree::build(&particles, domain());
+    for region in [Aabb { min: (-3.2, -3.5), max: (-2.9, -2.6) }, Aabb { min: (-5.0, -5.0), max: (5.0, 5.0) }, Aabb::around((0.0, 0.0), 1.0)] {
+        let mut found = Vec::new();
+        tree.query_region(&region, &particles, &mut found);
+        found.sort();
+        let expected: Vec<usize> = (0..particles.len()).filter(|&i| region.contains((particles[i].x, particles[i].y))).collect();
+        assert_eq!(found, expected);
+    }
+}
+
+#[test]
+fn quadtree_collision_count_matches_brute_force_after_moving() {
+    let run = |broad_phase| {
+        let config = SimulationConfig {
+            particle_count: 400,
+            bounds: (8.0, 8.0, 0.0),
+            num_iterations: 20,
+            thread_count: 5,
+            movement_thread_count: 2,
+            log_collisions: false,
+            broad_phase,
+            seed: Some(7),
+            ..SimulationConfig::default()
+        };
+        let mut particle_system = ParticleSystem::new(config);
+        particle_system.spawn_particles();
+        particle_system.move_and_collide_particles();
+        particle_system.collision_count()
+    };
+    assert_eq!(run(BroadPhaseKind::Quadtree), run(BroadPhaseKind::BruteForce));
+}
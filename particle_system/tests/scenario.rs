@@ -0,0 +1,106 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use particle_system::{Scenario, ScenarioError};
+
+const BASE: &str = "iterations = 5\n[particles]\ncount = 20\nradius = 0.1\n[bounds]\nx = 5.0\ny = 5.0\n[threads]\ntotal = 4\n";
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("particle_system_scenario_{}_{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// The field a scenario is rejected for. Anything other than an Invalid error fails the test
+fn invalid_field(text: &str) -> &'static str {
+    match Scenario::from_toml(text) {
+        Err(ScenarioError::Invalid { field, .. }) => field,
+        other => panic!("expected an invalid value, got {:?}", other)
+    }
+}
+
+#[test]
+fn every_shipped_scenario_loads() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../scenarios");
+    let mut loaded = Vec::new();
+    for entry in fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        if matches!(path.extension().and_then(|extension| extension.to_str()), Some("toml" | "json")) {
+            let scenario = Scenario::load(&path).unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+            assert_eq!(scenario.config().validate(), Ok(()), "{}", path.display());
+            loaded.push(path.file_name().unwrap().to_owned());
+        }
+    }
+    assert!(loaded.iter().any(|name| name == "move_and_collide.json"), "{:?}", loaded);
+    assert!(loaded.len() >= 5, "{:?}", loaded);
+}
+
+#[test]
+fn only_toml_and_json_files_are_read() {
+    // Rejected on the extension alone, before the file is looked for
+    let error = Scenario::load("scenarios/missing.yaml").unwrap_err();
+    assert!(matches!(&error, ScenarioError::UnsupportedFormat(path) if path == Path::new("scenarios/missing.yaml")), "{:?}", error);
+    assert!(matches!(Scenario::load("scenarios/no_extension"), Err(ScenarioError::UnsupportedFormat(_))));
+
+    let path = scratch_dir("missing").join("missing.toml");
+    match Scenario::load(&path) {
+        Err(ScenarioError::Io { path: error_path, source }) => {
+            assert_eq!(error_path, path);
+            assert_eq!(source.kind(), std::io::ErrorKind::NotFound);
+        }
+        other => panic!("expected an I/O error, got {:?}", other)
+    }
+}
+
+#[test]
+fn syntax_errors_are_reported_by_format() {
+    let dir = scratch_dir("syntax");
+    let toml_path = dir.join("broken.toml");
+    fs::write(&toml_path, "iterations = = 5\n").unwrap();
+    assert!(matches!(Scenario::load(&toml_path), Err(ScenarioError::Toml(_))));
+    let json_path = dir.join("broken.json");
+    fs::write(&json_path, "{ \"iterations\": 5,").unwrap();
+    assert!(matches!(Scenario::load(&json_path), Err(ScenarioError::Json(_))));
+
+    // Unknown keys & wrong types are parse errors too
+    assert!(matches!(Scenario::from_toml(&format!("{}colour = \"red\"\n", BASE)), Err(ScenarioError::Toml(_))));
+    assert!(matches!(Scenario::from_json("{ \"iterations\": \"five\" }"), Err(ScenarioError::Json(_))));
+    // A .json file is parsed as JSON even if it holds TOML
+    fs::write(&json_path, BASE).unwrap();
+    assert!(matches!(Scenario::load(&json_path), Err(ScenarioError::Json(_))));
+}
+
+#[test]
+fn invalid_values_name_the_field_they_are_in() {
+    assert!(Scenario::from_toml(BASE).is_ok());
+    let with = |from: &str, to: &str| {
+        assert!(BASE.contains(from), "{}", from);
+        BASE.replacen(from, to, 1)
+    };
+    assert_eq!(invalid_field(&with("count = 20", "count = 0")), "particles.count");
+    assert_eq!(invalid_field(&with("radius = 0.1", "radius = -0.1")), "particles.radius");
+    assert_eq!(invalid_field(&with("radius = 0.1", "radius = 0.1\ninitial_speed = -1.0")), "particles.initial_speed");
+    assert_eq!(invalid_field(&with("radius = 0.1", "radius = 0.1\nmass = [0.0, 1.0]")), "particles.mass");
+    assert_eq!(invalid_field(&with("x = 5.0", "x = 0.0")), "bounds.x");
+    assert_eq!(invalid_field(&with("y = 5.0", "y = -5.0")), "bounds.y");
+    assert_eq!(invalid_field(&with("y = 5.0", "y = 5.0\nz = -1.0")), "bounds.z");
+    assert_eq!(invalid_field(&format!("broad_phase = \"quadtree\"\n{}", with("y = 5.0", "y = 5.0\nz = 2.0"))), "broad_phase");
+    assert_eq!(invalid_field(&with("iterations = 5", "iterations = 0")), "iterations");
+    assert_eq!(invalid_field(&with("total = 4", "total = 0")), "threads.total");
+    assert_eq!(invalid_field(&with("total = 4", "total = 4\npipeline_depth = 0")), "threads.pipeline_depth");
+    assert_eq!(invalid_field(&format!("movement = {{ brownian = {{ diffusion = -1.0 }} }}\n{}", BASE)), "movement");
+    assert_eq!(invalid_field(&format!("dynamics = {{ mode = \"integrated\", integrator = \"velocity_verlet\", dt = 0.0 }}\n{}", BASE)), "dynamics");
+    assert_eq!(invalid_field(&format!("{}[[species]]\nweight = 0.0\n", BASE)), "species.weight");
+    assert_eq!(invalid_field(&format!("{}[[species]]\nmovement = {{ brownian = {{ diffusion = -1.0 }} }}\n", BASE)), "species.movement");
+
+    // Moving & colliding together needs a valid number of movement threads
+    let overlapped = format!("strategy = \"move_and_collide\"\n{}", BASE);
+    assert_eq!(invalid_field(&overlapped), "threads.movement");
+    assert_eq!(invalid_field(&overlapped.replace("total = 4", "total = 4\nmovement = 4")), "threads.movement");
+    assert_eq!(invalid_field(&overlapped.replace("total = 4", "total = 4\nmovement = 0")), "threads.movement");
+    assert!(Scenario::from_toml(&overlapped.replace("total = 4", "total = 4\nmovement = 3")).is_ok());
+
+    match Scenario::from_toml(&with("x = 5.0", "x = 0.0")) {
+        Err(error) => assert_eq!(error.to_string(), "invalid value for `bounds.x`: must be a positive number, got 0"),
+        Ok(_) => panic!("zero width accepted")
+    }
+}
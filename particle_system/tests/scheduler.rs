@@ -0,0 +1,141 @@
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use clap::Parser;
+use particle_system::partition::chunk_ranges;
+use particle_system::{BroadPhaseKind, Cli, ParticleSystem, RadiusDistribution, Scenario, SchedulerKind, SchedulerStats, SimulationConfig, TaskQueues, TASKS_PER_THREAD};
+
+fn config() -> SimulationConfig {
+    SimulationConfig {
+        particle_count: 400,
+        bounds: (12.0, 12.0, 0.0),
+        particle_radius: RadiusDistribution::Uniform { min: 0.1, max: 0.4 },
+        num_iterations: 12,
+        thread_count: 4,
+        movement_thread_count: 1,
+        log_collisions: false,
+        record_collisions: true,
+        seed: Some(1664),
+        ..SimulationConfig::default()
+    }
+}
+
+fn single_rows(count: usize) -> Vec<std::ops::Range<usize>> {
+    (0..count).map(|row| row..row + 1).collect()
+}
+
+#[test]
+fn a_thread_out_of_work_steals_from_the_back_of_the_other_queues() {
+    let queues = TaskQueues::new(single_rows(8), 2, SchedulerKind::WorkStealing);
+    // Thread 1 running before thread 0 has started: its own tasks in order, then all of thread
+    // 0's, last first
+    let mut ran = Vec::new();
+    queues.work(1, |task| { ran.push(task.start); 0 });
+    assert_eq!(ran, [4, 5, 6, 7, 3, 2, 1, 0]);
+    queues.work(0, |_| panic!("nothing should be left"));
+    let stats = queues.stats();
+    assert_eq!((stats[0].tasks, stats[1].tasks, stats[1].stolen), (0, 8, 4));
+
+    // A static schedule never steals
+    let queues = TaskQueues::new(single_rows(8), 2, SchedulerKind::Static);
+    let mut ran = Vec::new();
+    queues.work(1, |task| { ran.push(task.start); 0 });
+    queues.work(0, |task| { ran.push(task.start); 0 });
+    assert_eq!(ran, [4, 5, 6, 7, 0, 1, 2, 3]);
+    assert!(queues.stats().iter().all(|stats| stats.tasks == 4 && stats.stolen == 0));
+}
+
+#[test]
+fn every_task_runs_exactly_once_however_the_threads_race() {
+    for workers in [1, 3, 7, 40] {
+        let queues = TaskQueues::new(single_rows(301), workers, SchedulerKind::WorkStealing);
+        let ran = Mutex::new(Vec::new());
+        thread::scope(|scope| {
+            for worker in 0..workers {
+                let (queues, ran) = (&queues, &ran);
+                scope.spawn(move || queues.work(worker, |task| { ran.lock().unwrap().push(task.start); 0 }));
+            }
+        });
+        let mut ran = ran.into_inner().unwrap();
+        ran.sort_unstable();
+        assert_eq!(ran, (0..301).collect::<Vec<_>>(), "{} threads", workers);
+        assert_eq!(queues.stats().iter().map(|stats| stats.tasks).sum::<usize>(), 301);
+    }
+}
+
+// Busy time over brute force rows, with each row costing as long as it has pairs to test. Each
+// task sleeps rather than spins, so its cost is the same however many cores are free.
+fn triangular_busy(kind: SchedulerKind, workers: usize) -> SchedulerStats {
+    const ROWS: usize = 64;
+    let tasks = chunk_ranges(ROWS, TaskQueues::task_count(workers, kind));
+    let queues = TaskQueues::new(tasks, workers, kind);
+    thread::scope(|scope| {
+        for worker in 0..workers {
+            let queues = &queues;
+            scope.spawn(move || queues.work(worker, |task| {
+                let pairs: usize = task.map(|row| ROWS - row - 1).sum();
+                thread::sleep(Duration::from_micros(50 * pairs as u64));
+                0
+            }));
+        }
+    });
+    let mut stats = SchedulerStats::default();
+    stats.add_pass(&queues.stats());
+    stats
+}
+
+#[test]
+fn work_stealing_evens_out_the_triangular_workload() {
+    // Cut into equal runs of rows, the first thread gets 7/16 of the pairs & the last 1/16
+    let fixed = triangular_busy(SchedulerKind::Static, 4);
+    assert!(fixed.imbalance() > 1.5, "static schedule: {}", fixed.busy_summary());
+
+    let stealing = triangular_busy(SchedulerKind::WorkStealing, 4);
+    assert!(stealing.imbalance() < 1.3, "work stealing: {}", stealing.busy_summary());
+    assert!(stealing.workers.iter().map(|stats| stats.stolen).sum::<usize>() > 0);
+}
+
+#[test]
+fn both_schedules_find_the_same_collisions_and_report_every_pass() {
+    let run = |scheduler, broad_phase| {
+        let mut particle_system = ParticleSystem::new(SimulationConfig { scheduler, broad_phase, ..config() });
+        particle_system.spawn_particles();
+        particle_system.move_and_collide_particles();
+        let events: Vec<_> = particle_system.collision_events().iter().map(|event| (event.step, event.a, event.b)).collect();
+        (particle_system.collision_count(), events, particle_system.scheduler_stats().clone())
+    };
+    let collision_threads = config().collision_thread_count();
+    for broad_phase in [BroadPhaseKind::BruteForce, BroadPhaseKind::Grid, BroadPhaseKind::SweepAndPrune] {
+        let (collisions, events, fixed) = run(SchedulerKind::Static, broad_phase);
+        assert!(collisions > 0);
+        assert_eq!(fixed.passes, config().num_iterations);
+        assert_eq!(fixed.workers.len(), collision_threads);
+        assert!(fixed.workers.iter().all(|stats| stats.tasks == fixed.passes && stats.stolen == 0), "{:?}", broad_phase);
+        // Every collision is put down to the thread that found it
+        assert_eq!(fixed.workers.iter().map(|stats| stats.collisions).sum::<usize>(), collisions, "{:?}", broad_phase);
+
+        let (stolen_collisions, stolen_events, stealing) = run(SchedulerKind::WorkStealing, broad_phase);
+        assert_eq!((stolen_collisions, &stolen_events), (collisions, &events), "{:?}", broad_phase);
+        assert_eq!(stealing.passes, config().num_iterations);
+        assert_eq!(stealing.workers.iter().map(|stats| stats.collisions).sum::<usize>(), collisions, "{:?}", broad_phase);
+        if broad_phase == BroadPhaseKind::BruteForce {
+            let tasks: usize = stealing.workers.iter().map(|stats| stats.tasks).sum();
+            assert_eq!(tasks, stealing.passes * collision_threads * TASKS_PER_THREAD);
+        }
+    }
+}
+
+#[test]
+fn the_scheduler_is_chosen_on_the_command_line_and_in_scenarios() {
+    let parse = |args: &[&str]| Cli::parse_from([&["colliding_particles"], args].concat()).apply(SimulationConfig::default()).scheduler;
+    assert_eq!(parse(&[]), SchedulerKind::WorkStealing);
+    assert_eq!(parse(&["--scheduler", "static"]), SchedulerKind::Static);
+
+    let text = "iterations = 5\nstrategy = \"move_and_collide\"\nscheduler = \"static\"\n[particles]\ncount = 60\nradius = 0.3\n[bounds]\nx = 5.0\ny = 5.0\n[threads]\ntotal = 4\nmovement = 1\n";
+    let scenario = Scenario::from_toml(text).unwrap();
+    assert_eq!(scenario.config().scheduler, SchedulerKind::Static);
+    // The summary has each collision thread's busy time
+    let summary = scenario.run().unwrap();
+    assert_eq!(summary.collision_busy_ms.len(), 3);
+    assert!(summary.collision_imbalance >= 1.0);
+}
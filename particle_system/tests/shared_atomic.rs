@@ -0,0 +1,199 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use clap::Parser;
+use particle_system::{AtomicPositions, Boundary, BroadPhaseKind, Cli, Dynamics, ForceField, Integrator, MovementModel, Particle, ParticleStore, ParticleSystem, RadiusDistribution, Scenario, SimulationConfig, Species, StorageLayout, ThreeD};
+
+fn config() -> SimulationConfig {
+    SimulationConfig {
+        particle_count: 250,
+        bounds: (12.0, 12.0, 0.0),
+        particle_radius: RadiusDistribution::Uniform { min: 0.1, max: 0.4 },
+        species: vec![
+            Species { weight: 1.0, movement: MovementModel::RandomWalk },
+            Species { weight: 1.0, movement: MovementModel::Brownian { diffusion: 0.3 } }
+        ],
+        initial_speed: 0.2,
+        num_iterations: 30,
+        thread_count: 4,
+        movement_thread_count: 2,
+        log_collisions: false,
+        record_collisions: true,
+        seed: Some(5150),
+        ..SimulationConfig::default()
+    }
+}
+
+// (id, x, y, vx & vy as bits) per particle, collisions, absorbed & (step, a, b) per recorded event
+type Outcome = (Vec<(usize, u32, u32, u32, u32)>, usize, usize, Vec<(usize, usize, usize)>);
+
+fn outcome(config: &SimulationConfig, storage: StorageLayout, run: fn(&mut ParticleSystem)) -> Outcome {
+    let mut particle_system = ParticleSystem::new(SimulationConfig { storage, ..config.clone() });
+    particle_system.spawn_particles();
+    run(&mut particle_system);
+    (
+        particle_system.particles.iter().map(|p| (p.id, p.x.to_bits(), p.y.to_bits(), p.vx.to_bits(), p.vy.to_bits())).collect(),
+        particle_system.collision_count(),
+        particle_system.absorbed_count(),
+        particle_system.collision_events().iter().map(|event| (event.step, event.a, event.b)).collect()
+    )
+}
+
+fn move_then_collide(particle_system: &mut ParticleSystem) {
+    particle_system.move_particles_loop();
+    particle_system.collide_particles();
+}
+
+fn move_and_collide(particle_system: &mut ParticleSystem) {
+    particle_system.move_and_collide_particles();
+}
+
+#[test]
+fn shared_atomic_runs_match_array_of_structs_when_nothing_overlaps() {
+    for broad_phase in [BroadPhaseKind::BruteForce, BroadPhaseKind::Grid, BroadPhaseKind::Quadtree, BroadPhaseKind::SweepAndPrune] {
+        for boundary in [Boundary::Clamp, Boundary::Periodic] {
+            let config = SimulationConfig { broad_phase, boundaries: (boundary, boundary, boundary), ..config() };
+            let expected = outcome(&config, StorageLayout::ArrayOfStructs, move_then_collide);
+            assert!(expected.1 > 0);
+            assert_eq!(outcome(&config, StorageLayout::SharedAtomic, move_then_collide), expected, "{:?} with {:?} walls", broad_phase, boundary);
+        }
+    }
+
+    // Collision response makes movement & collision take turns, so that matches exactly too
+    let config = SimulationConfig {
+        boundaries: (Boundary::Absorb, Boundary::Reflect, Boundary::Clamp),
+        dynamics: Dynamics::Integrated { integrator: Integrator::VelocityVerlet, dt: 0.1, force: ForceField::None },
+        initial_speed: 2.0,
+        mass_range: (1.0, 4.0),
+        collision_response: true,
+        broad_phase: BroadPhaseKind::Grid,
+        ..config()
+    };
+    let expected = outcome(&config, StorageLayout::ArrayOfStructs, move_and_collide);
+    assert!(expected.1 > 0 && expected.2 > 0);
+    assert_eq!(outcome(&config, StorageLayout::SharedAtomic, move_and_collide), expected);
+}
+
+#[test]
+fn moving_and_colliding_at_once_moves_every_particle_the_same() {
+    for boundary in [Boundary::Clamp, Boundary::Absorb] {
+        let config = SimulationConfig { broad_phase: BroadPhaseKind::Grid, boundaries: (boundary, boundary, boundary), ..config() };
+        let expected = outcome(&config, StorageLayout::ArrayOfStructs, move_and_collide);
+        let (particles, collisions, absorbed, events) = outcome(&config, StorageLayout::SharedAtomic, move_and_collide);
+        // Movement never depends on what the collision threads find, so it is exactly the same...
+        assert_eq!((&particles, absorbed), (&expected.0, expected.2), "{:?} walls", boundary);
+        // ...but which step each collision is seen in depends on how the threads interleave
+        assert!(collisions > 0 && collisions == events.len());
+        assert!(events.iter().all(|&(step, a, b)| (1..=config.num_iterations).contains(&step) && a < b));
+    }
+}
+
+// Every particle after each step (0 being as spawned), moving one step at a time with ArrayOfStructs storage
+fn positions_per_step(config: &SimulationConfig) -> Vec<Vec<Particle>> {
+    let mut particle_system = ParticleSystem::new(SimulationConfig { num_iterations: 1, ..config.clone() });
+    particle_system.spawn_particles();
+    let mut steps = vec![particle_system.particles.clone()];
+    for _ in 0..config.num_iterations {
+        particle_system.move_particles_loop();
+        steps.push(particle_system.particles.clone());
+    }
+    steps
+}
+
+#[test]
+fn moving_particles_collide_at_the_steps_array_of_structs_moves_them_to() {
+    for broad_phase in [BroadPhaseKind::BruteForce, BroadPhaseKind::Grid, BroadPhaseKind::Quadtree, BroadPhaseKind::SweepAndPrune] {
+        let config = SimulationConfig { broad_phase, ..config() };
+        let steps = positions_per_step(&config);
+        let mut particle_system = ParticleSystem::new(SimulationConfig { storage: StorageLayout::SharedAtomic, ..config.clone() });
+        particle_system.spawn_particles();
+        particle_system.move_and_collide_particles();
+
+        // The pass over step n sees each particle at step n or n + 1 (the last pass only at the last step)...
+        let last = config.num_iterations;
+        let seen = |step: usize| step..=(step + 1).min(last);
+        let found: HashSet<(usize, usize, usize)> = particle_system.collision_events().iter().map(|event| (event.step, event.a, event.b)).collect();
+        assert_eq!(found.len(), particle_system.collision_count(), "{:?}", broad_phase);
+        for event in particle_system.collision_events() {
+            let position = |id: usize, step: usize| steps[step][id].position();
+            assert!(seen(event.step).any(|step| position(event.a, step) == event.a_position), "{:?}: {:?}", broad_phase, event);
+            assert!(seen(event.step).any(|step| position(event.b, step) == event.b_position), "{:?}: {:?}", broad_phase, event);
+        }
+        // ...so a pair touching whichever of those it is seen at is always found, moving or not
+        let mut always_touching = 0;
+        for step in 1..=last {
+            for a in 0..config.particle_count {
+                for b in a + 1..config.particle_count {
+                    if seen(step).all(|at_a| seen(step).all(|at_b| steps[at_a][a].collide(&steps[at_b][b]))) {
+                        assert!(found.contains(&(step, a, b)), "{:?} missed {} & {} at step {}", broad_phase, a, b, step);
+                        always_touching += 1;
+                    }
+                }
+            }
+        }
+        assert!(always_touching > 0 && always_touching < found.len(), "{:?}: {} of {}", broad_phase, always_touching, found.len());
+    }
+}
+
+#[test]
+fn stationary_particles_give_the_same_collisions_while_moving_and_colliding_at_once() {
+    // Every step rewrites the same positions, under the collision threads' reads
+    let config = SimulationConfig {
+        species: vec![Species { weight: 1.0, movement: MovementModel::Drift { velocity: (0.0, 0.0, 0.0) } }],
+        initial_speed: 0.0,
+        particle_count: 600,
+        ..config()
+    };
+    for broad_phase in [BroadPhaseKind::BruteForce, BroadPhaseKind::Grid, BroadPhaseKind::SweepAndPrune] {
+        let config = SimulationConfig { broad_phase, ..config.clone() };
+        let expected = outcome(&config, StorageLayout::ArrayOfStructs, move_and_collide);
+        assert!(expected.1 > 0);
+        assert_eq!(outcome(&config, StorageLayout::SharedAtomic, move_and_collide), expected, "{:?}", broad_phase);
+    }
+}
+
+// The loom tests (tests/loom.rs) check every interleaving of tiny cases; this hammers the real
+// atomics with real threads
+#[test]
+fn reads_never_see_half_of_a_write() {
+    let particles: Vec<Particle<f32, ThreeD>> = (0..4).map(|id| Particle::new_3d(0.0, 0.0, 0.0, id).with_radius(0.5)).collect();
+    let positions = AtomicPositions::from_particles(&particles);
+    let done = AtomicBool::new(false);
+    thread::scope(|scope| {
+        for i in 0..positions.len() {
+            let (positions, done) = (&positions, &done);
+            scope.spawn(move || {
+                for k in 1..=100_000 {
+                    let value = k as f32;
+                    positions.write(i, (value, -value, value * 0.5));
+                }
+                done.store(true, Ordering::Relaxed);
+            });
+        }
+        for _ in 0..2 {
+            scope.spawn(|| {
+                let mut last = [0.0; 4];
+                while !done.load(Ordering::Relaxed) {
+                    for (i, last) in last.iter_mut().enumerate() {
+                        let (x, y, z) = positions.read(i);
+                        assert!(y == -x && z == x * 0.5, "torn position ({}, {}, {})", x, y, z);
+                        assert!(x >= *last, "went back from {} to {}", last, x);
+                        *last = x;
+                    }
+                }
+            });
+        }
+    });
+    assert_eq!(positions.position(3), (100_000.0, -100_000.0, 50_000.0));
+    assert_eq!(positions.particle(2).id, 2);
+    assert_eq!(positions.radius(1), 0.5);
+}
+
+#[test]
+fn shared_atomic_storage_is_chosen_on_the_command_line_and_in_scenarios() {
+    let parse = |args: &[&str]| Cli::parse_from([&["colliding_particles"], args].concat()).apply(SimulationConfig::default()).storage;
+    assert_eq!(parse(&["--storage", "shared-atomic"]), StorageLayout::SharedAtomic);
+
+    let text = "iterations = 5\nstorage = \"shared_atomic\"\n[particles]\ncount = 20\nradius = 0.1\n[bounds]\nx = 5.0\ny = 5.0\n[threads]\ntotal = 2\n";
+    assert_eq!(Scenario::from_toml(text).unwrap().config().storage, StorageLayout::SharedAtomic);
+}
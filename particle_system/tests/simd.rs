@@ -0,0 +1,156 @@
+use clap::Parser;
+use rand::Rng;
+use particle_system::{BroadPhaseKind, Cli, Dimension, Domain, Particle, ParticleColumns, ParticleRng, ParticleStore, ParticleSystem, RadiusDistribution, Scenario, SimdLevel, SimulationConfig, StorageLayout, ThreeD, TwoD};
+
+const LEVELS: [SimdLevel; 4] = [SimdLevel::Scalar, SimdLevel::Sse2, SimdLevel::Avx2, SimdLevel::Avx512];
+
+// A crowded box, so most rows have contacts, plus exactly touching pairs & a NaN that must never touch
+fn particles<D: Dimension>(count: usize, seed: u64) -> Vec<Particle<f32, D>> {
+    let mut particles: Vec<Particle<f32, D>> = (0..count).map(|i| {
+        let mut rng = ParticleRng::for_spawn(seed, i);
+        let (x, y) = (rng.random_range(-2.0..2.0), rng.random_range(-2.0..2.0));
+        let z = if D::AXES == 3 { rng.random_range(-2.0..2.0) } else { 0.0 };
+        Particle::at((x, y, z), i).with_radius(rng.random_range(0.05..0.5))
+    }).collect();
+    if count > 20 {
+        // 0.5 apart with radii 0.25 each: distance² & contact² are both exactly 0.25
+        particles[3] = Particle::at((1.0, 1.0, 0.0), 3).with_radius(0.25);
+        particles[19] = Particle::at((1.5, 1.0, 0.0), 19).with_radius(0.25);
+        particles[11].x = f32::NAN;
+    }
+    particles
+}
+
+// Every (i, j) the AoS scalar test finds, in order
+fn expected<D: Dimension>(particles: &[Particle<f32, D>], domain: &Domain) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    for i in 0..particles.len() {
+        particles.for_each_contact(i, i + 1..particles.len(), domain, |j| pairs.push((i, j)));
+    }
+    pairs
+}
+
+fn assert_every_level_finds_the_same_contacts<D: Dimension>() {
+    let domain = Domain { half: (2.0, 2.0, 2.0), ..Domain::default() };
+    // Lengths around each lane count, so full vectors & leftover tails are both covered
+    for count in [0, 1, 3, 4, 5, 8, 15, 16, 17, 33, 250] {
+        let particles = particles::<D>(count, count as u64);
+        let expected = expected(&particles, &domain);
+        if count > 20 {
+            assert!(expected.contains(&(3, 19)), "exactly touching pair missed by the scalar test");
+            assert!(!expected.iter().any(|&(i, j)| i == 11 || j == 11));
+        }
+        let mut columns = ParticleColumns::from_particles(&particles);
+        for level in LEVELS {
+            columns.simd = level;
+            let mut found = Vec::new();
+            for i in 0..count {
+                columns.for_each_contact(i, i + 1..count, &domain, |j| found.push((i, j)));
+            }
+            assert_eq!(found, expected, "{:?} kernel, n={}, {}D", level, count, D::AXES);
+        }
+    }
+}
+
+#[test]
+fn every_level_finds_the_same_contacts_as_the_scalar_test() {
+    assert_every_level_finds_the_same_contacts::<TwoD>();
+    assert_every_level_finds_the_same_contacts::<ThreeD>();
+}
+
+#[test]
+fn every_level_filters_gathered_candidates_like_the_scalar_test() {
+    let domain = Domain { half: (2.0, 2.0, 2.0), ..Domain::default() };
+    let particles = particles::<ThreeD>(120, 5);
+    let mut columns = ParticleColumns::from_particles(&particles);
+    // Unordered candidate lists of every length up to 40, including indices below i
+    let mut rng = ParticleRng::for_spawn(9, 0);
+    for i in 0..particles.len() {
+        let candidates: Vec<usize> = (0..i % 41).map(|_| rng.random_range(0..particles.len())).collect();
+        let mut expected = Vec::new();
+        particles.for_each_later_contact(i, &candidates, &domain, |j| expected.push(j));
+        for level in LEVELS {
+            columns.simd = level;
+            let mut found = Vec::new();
+            columns.for_each_later_contact(i, &candidates, &domain, |j| found.push(j));
+            assert_eq!(found, expected, "{:?} kernel, row {}", level, i);
+        }
+    }
+}
+
+#[test]
+fn runs_are_bit_identical_at_every_level() {
+    let outcome = |broad_phase, storage, simd| {
+        let mut particle_system = ParticleSystem::new(SimulationConfig {
+            particle_count: 400,
+            bounds: (15.0, 15.0, 0.0),
+            particle_radius: RadiusDistribution::Uniform { min: 0.1, max: 0.4 },
+            collision_response: true,
+            initial_speed: 0.3,
+            num_iterations: 30,
+            thread_count: 3,
+            movement_thread_count: 1,
+            log_collisions: false,
+            record_collisions: true,
+            broad_phase,
+            storage,
+            simd,
+            seed: Some(808),
+            ..SimulationConfig::default()
+        });
+        particle_system.spawn_particles();
+        particle_system.move_and_collide_particles();
+        let events: Vec<_> = particle_system.collision_events().iter().map(|event| (event.step, event.a, event.b)).collect();
+        let positions: Vec<_> = particle_system.particles.iter().map(|p| (p.x.to_bits(), p.y.to_bits(), p.vx.to_bits(), p.vy.to_bits())).collect();
+        (particle_system.collision_count(), events, positions)
+    };
+    for broad_phase in [BroadPhaseKind::BruteForce, BroadPhaseKind::Grid] {
+        let reference = outcome(broad_phase, StorageLayout::ArrayOfStructs, SimdLevel::Scalar);
+        assert!(reference.0 > 0);
+        for level in LEVELS {
+            assert_eq!(outcome(broad_phase, StorageLayout::StructOfArrays, level), reference, "{:?} with {:?} kernel", broad_phase, level);
+        }
+    }
+}
+
+#[test]
+fn levels_are_capped_at_what_the_cpu_supports() {
+    let detected = SimdLevel::detect();
+    assert_eq!(SimdLevel::Avx512.supported(), detected);
+    assert_eq!(SimdLevel::Scalar.supported(), SimdLevel::Scalar);
+    assert_eq!(SimdLevel::default(), detected);
+    assert_eq!(LEVELS.map(SimdLevel::lanes), [1, 4, 8, 16]);
+    #[cfg(target_arch = "x86_64")]
+    assert!(detected >= SimdLevel::Sse2, "SSE2 is part of x86_64");
+}
+
+#[test]
+fn simd_level_is_chosen_on_the_command_line_and_in_scenarios() {
+    let parse = |args: &[&str]| Cli::parse_from([&["colliding_particles"], args].concat()).apply(SimulationConfig::default()).simd;
+    assert_eq!(parse(&[]), SimdLevel::detect());
+    assert_eq!(parse(&["--simd", "scalar"]), SimdLevel::Scalar);
+    assert_eq!(parse(&["--simd", "avx512"]), SimdLevel::Avx512);
+    assert!(Cli::try_parse_from(["colliding_particles", "--simd", "neon"]).is_err());
+
+    let text = "iterations = 5\nsimd = \"sse2\"\n[particles]\ncount = 20\nradius = 0.1\n[bounds]\nx = 5.0\ny = 5.0\n[threads]\ntotal = 2\n";
+    assert_eq!(Scenario::from_toml(text).unwrap().config().simd, SimdLevel::Sse2);
+    assert_eq!(Scenario::from_toml(&text.replace("simd = \"sse2\"\n", "")).unwrap().config().simd, SimdLevel::detect());
+}
+
+#[test]
+fn asking_for_a_simd_level_selects_the_layout_it_runs_on() {
+    let parse = |args: &[&str]| Cli::parse_from([&["colliding_particles"], args].concat()).apply(SimulationConfig::default()).storage;
+    // Left alone, the default layout (which the kernels don't run on) stays
+    assert_eq!(parse(&[]), StorageLayout::ArrayOfStructs);
+    assert_eq!(parse(&["--simd", "scalar"]), StorageLayout::ArrayOfStructs);
+    assert_eq!(parse(&["--simd", "sse2"]), StorageLayout::StructOfArrays);
+    // An explicit layout still wins
+    assert_eq!(parse(&["--simd", "avx2", "--storage", "shared-atomic"]), StorageLayout::SharedAtomic);
+
+    let text = "iterations = 5\nsimd = \"sse2\"\n[particles]\ncount = 20\nradius = 0.1\n[bounds]\nx = 5.0\ny = 5.0\n[threads]\ntotal = 2\n";
+    let storage = |text: &str| Scenario::from_toml(text).unwrap().config().storage;
+    assert_eq!(storage(text), StorageLayout::StructOfArrays);
+    assert_eq!(storage(&text.replace("simd = \"sse2\"", "simd = \"scalar\"")), StorageLayout::ArrayOfStructs);
+    assert_eq!(storage(&text.replace("simd = \"sse2\"", "storage = \"array_of_structs\"\nsimd = \"sse2\"")), StorageLayout::ArrayOfStructs);
+    assert_eq!(storage(&text.replace("simd = \"sse2\"\n", "")), StorageLayout::ArrayOfStructs);
+}
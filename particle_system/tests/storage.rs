@@ -0,0 +1,123 @@
+use clap::Parser;
+use particle_system::{Boundary, BroadPhaseKind, Cli, Dimension, Dynamics, ForceField, Integrator, MovementModel, Particle, ParticleColumns, ParticleSystem, RadiusDistribution, Scenario, SimulationConfig, Species, StorageLayout, ThreeD, TwoD};
+
+fn config() -> SimulationConfig {
+    SimulationConfig {
+        particle_count: 250,
+        bounds: (12.0, 12.0, 0.0),
+        particle_radius: RadiusDistribution::Uniform { min: 0.1, max: 0.4 },
+        species: vec![
+            Species { weight: 1.0, movement: MovementModel::RandomWalk },
+            Species { weight: 1.0, movement: MovementModel::Brownian { diffusion: 0.3 } }
+        ],
+        initial_speed: 0.2,
+        num_iterations: 40,
+        thread_count: 4,
+        movement_thread_count: 2,
+        log_collisions: false,
+        record_collisions: true,
+        seed: Some(77),
+        ..SimulationConfig::default()
+    }
+}
+
+// (id, x, y, vx & vy as bits) per particle, collisions, absorbed, recorded events
+type Outcome = (Vec<(usize, u32, u32, u32, u32)>, usize, usize, usize);
+
+// What running `run` on a freshly spawned system in the given layout leaves behind
+fn outcome<D: Dimension>(config: &SimulationConfig, storage: StorageLayout, run: fn(&mut ParticleSystem<f32, D>)) -> Outcome {
+    let mut particle_system = ParticleSystem::with_config(SimulationConfig { storage, ..config.clone() });
+    particle_system.spawn_particles();
+    run(&mut particle_system);
+    let particles = particle_system.particles.iter().map(|p| (p.id, p.x.to_bits(), p.y.to_bits(), p.vx.to_bits(), p.vy.to_bits())).collect();
+    (particles, particle_system.collision_count(), particle_system.absorbed_count(), particle_system.collision_events().len())
+}
+
+fn assert_same_in_both_layouts<D: Dimension>(config: &SimulationConfig, case: &str) {
+    let runs: [fn(&mut ParticleSystem<f32, D>); 2] = [
+        |particle_system| {
+            particle_system.move_particles_loop();
+            particle_system.collide_particles();
+        },
+        |particle_system| particle_system.move_and_collide_particles()
+    ];
+    for run in runs {
+        let structs = outcome(config, StorageLayout::ArrayOfStructs, run);
+        assert!(structs.1 > 0, "{}: no collisions to compare", case);
+        assert_eq!(outcome(config, StorageLayout::StructOfArrays, run), structs, "{}: struct of arrays run diverged", case);
+    }
+}
+
+#[test]
+fn struct_of_arrays_runs_match_array_of_structs_for_every_broad_phase() {
+    for broad_phase in [BroadPhaseKind::BruteForce, BroadPhaseKind::Grid, BroadPhaseKind::Quadtree, BroadPhaseKind::SweepAndPrune] {
+        for boundary in [Boundary::Clamp, Boundary::Periodic] {
+            let config = SimulationConfig { broad_phase, boundaries: (boundary, boundary, boundary), ..config() };
+            assert_same_in_both_layouts::<TwoD>(&config, &format!("{:?} with {:?} walls", broad_phase, boundary));
+        }
+    }
+}
+
+#[test]
+fn struct_of_arrays_runs_match_with_absorbing_walls_and_collision_response() {
+    let config = SimulationConfig {
+        boundaries: (Boundary::Absorb, Boundary::Reflect, Boundary::Clamp),
+        dynamics: Dynamics::Integrated { integrator: Integrator::VelocityVerlet, dt: 0.1, force: ForceField::None },
+        initial_speed: 2.0,
+        mass_range: (1.0, 4.0),
+        collision_response: true,
+        broad_phase: BroadPhaseKind::Grid,
+        ..config()
+    };
+    let (particles, _, absorbed, _) = outcome::<TwoD>(&config, StorageLayout::StructOfArrays, |particle_system| particle_system.move_and_collide_particles());
+    assert!(absorbed > 0 && particles.len() + absorbed == config.particle_count);
+    assert_same_in_both_layouts::<TwoD>(&config, "absorbing walls with collision response");
+
+    let config = SimulationConfig { bounds: (8.0, 8.0, 8.0), ..config };
+    assert_same_in_both_layouts::<ThreeD>(&config, "3D");
+}
+
+fn fields(particles: &[Particle<f32, ThreeD>]) -> Vec<(usize, [f32; 8], usize)> {
+    particles.iter().map(|p| (p.id, [p.x, p.y, p.z, p.vx, p.vy, p.vz, p.mass, p.radius], p.species)).collect()
+}
+
+#[test]
+fn columns_round_trip_and_retain_in_order() {
+    let particles: Vec<Particle<f32, ThreeD>> = (0..10).map(|i| {
+        let mut particle = Particle::new_3d(i as f32, -(i as f32), 0.5 * i as f32, i).with_radius(0.1 + 0.01 * i as f32);
+        (particle.vx, particle.vy, particle.mass, particle.species) = (1.0, 2.0, 3.0, i % 2);
+        particle
+    }).collect();
+    let mut columns = ParticleColumns::from_particles(&particles);
+    assert_eq!(columns.len(), 10);
+    assert_eq!(columns.y[3], -3.0);
+    assert_eq!(fields(&[columns.get(7)]), fields(&particles[7..8]));
+
+    let mut stored = Vec::new();
+    columns.store(&mut stored);
+    assert_eq!(fields(&stored), fields(&particles));
+
+    columns.retain(|particle| particle.id % 3 != 0);
+    let kept: Vec<Particle<f32, ThreeD>> = particles.iter().copied().filter(|particle| particle.id % 3 != 0).collect();
+    columns.store(&mut stored);
+    assert_eq!(fields(&stored), fields(&kept));
+    assert_eq!(columns.id, vec![1, 2, 4, 5, 7, 8]);
+
+    // Chunks cover every row once, in order
+    let chunks = columns.chunks_mut(4);
+    assert_eq!(chunks.iter().map(|chunk| chunk.len()).collect::<Vec<_>>(), vec![4, 2]);
+    assert_eq!(fields(&[chunks[1].get(1)]), fields(&kept[5..6]));
+}
+
+#[test]
+fn storage_is_chosen_on_the_command_line_and_in_scenarios() {
+    let parse = |args: &[&str]| Cli::parse_from([&["colliding_particles"], args].concat()).apply(SimulationConfig::default()).storage;
+    assert_eq!(parse(&[]), StorageLayout::ArrayOfStructs);
+    assert_eq!(parse(&["--storage", "struct-of-arrays"]), StorageLayout::StructOfArrays);
+    assert!(Cli::try_parse_from(["colliding_particles", "--storage", "columns"]).is_err());
+
+    let text = "iterations = 5\nstorage = \"struct_of_arrays\"\n[particles]\ncount = 20\nradius = 0.1\n[bounds]\nx = 5.0\ny = 5.0\n[threads]\ntotal = 2\n";
+    assert_eq!(Scenario::from_toml(text).unwrap().config().storage, StorageLayout::StructOfArrays);
+    assert_eq!(Scenario::from_toml(&text.replace("storage = \"struct_of_arrays\"\n", "")).unwrap().config().storage, StorageLayout::ArrayOfStructs);
+    assert!(Scenario::from_toml(&text.replace("struct_of_arrays", "columns")).is_err());
+}
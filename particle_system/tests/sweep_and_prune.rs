@@ -0,0 +1,74 @@
+use std::collections::BTreeSet;
+use particle_system::{broad_phase_for, BroadPhaseKind, Particle, ParticleSystem, SimulationConfig, SweepAndPrune};
+
+fn spawned(particle_count: usize, bounds: f32, seed: u64) -> ParticleSystem {
+    let config = SimulationConfig {
+        particle_count,
+        bounds: (bounds, bounds, 0.0),
+        num_iterations: 1,
+        thread_count: 4,
+        log_collisions: false,
+        seed: Some(seed),
+        ..SimulationConfig::default()
+    };
+    let mut particle_system = ParticleSystem::new(config);
+    particle_system.spawn_particles();
+    particle_system
+}
+
+fn collisions_from_pairs(sweep_and_prune: &SweepAndPrune, particles: &[Particle]) -> BTreeSet<(usize, usize)> {
+    let mut pairs = Vec::new();
+    sweep_and_prune.overlapping_pairs(&mut pairs);
+    let unique: BTreeSet<_> = pairs.iter().map(|&(i, j)| (i as usize, j as usize)).collect();
+    assert_eq!(unique.len(), pairs.len(), "a pair was produced twice");
+    assert!(unique.iter().all(|&(i, j)| i < j));
+    unique.into_iter().filter(|&(i, j)| particles[i].collide(&particles[j])).collect()
+}
+
+fn brute_force_collisions(particles: &[Particle]) -> BTreeSet<(usize, usize)> {
+    let mut pairs = BTreeSet::new();
+    for i in 0..particles.len() {
+        for j in i + 1..particles.len() {
+            if particles[i].collide(&particles[j]) {
+                pairs.insert((i, j));
+            }
+        }
+    }
+    pairs
+}
+
+#[test]
+fn sweep_and_prune_finds_exactly_the_brute_force_collisions() {
+    for (particle_count, bounds) in [(0, 10.0), (1, 10.0), (2, 0.1), (300, 10.0), (800, 6.0)] {
+        let particle_system = spawned(particle_count, bounds, 21);
+        let particles = &particle_system.particles;
+        let sweep_and_prune = SweepAndPrune::build(particles);
+        assert_eq!(collisions_from_pairs(&sweep_and_prune, particles), brute_force_collisions(particles));
+    }
+}
+
+#[test]
+fn insertion_sort_repair_tracks_moving_particles() {
+    let mut particle_system = spawned(500, 8.0, 3);
+    let mut sweep_and_prune = SweepAndPrune::build(&particle_system.particles);
+    for _ in 0..25 {
+        particle_system.move_particles_loop();
+        sweep_and_prune.update(&particle_system.particles);
+        let particles = &particle_system.particles;
+        assert_eq!(collisions_from_pairs(&sweep_and_prune, particles), brute_force_collisions(particles));
+    }
+}
+
+#[test]
+fn sweep_and_prune_collision_count_matches_brute_force_on_the_same_seed() {
+    let run = |broad_phase| {
+        let mut particle_system = spawned(400, 8.0, 7);
+        particle_system.config.broad_phase = broad_phase;
+        particle_system.set_broad_phase(broad_phase_for(&particle_system.config));
+        particle_system.config.num_iterations = 20;
+        particle_system.config.movement_thread_count = 2;
+        particle_system.move_and_collide_particles();
+        particle_system.collision_count()
+    };
+    assert_eq!(run(BroadPhaseKind::SweepAndPrune), run(BroadPhaseKind::BruteForce));
+}
@@ -0,0 +1,160 @@
+use std::mem::size_of;
+use clap::Parser;
+use particle_system::{Boundary, BroadPhaseKind, Cli, Dimension, Domain, Dynamics, ForceField, Integrator, MovementModel, Particle, ParticleSystem, RadiusDistribution, Scenario, SimulationConfig, Species, ThreeD, TwoD};
+
+fn spawned<D: Dimension>(config: SimulationConfig) -> ParticleSystem<f32, D> {
+    let mut particle_system = ParticleSystem::with_config(SimulationConfig { log_collisions: false, ..config });
+    particle_system.spawn_particles();
+    particle_system
+}
+
+#[test]
+fn spheres_collide_on_their_3d_distance() {
+    let a = Particle::new_3d(0.0, 0.0, 0.0, 0);
+    // Right on top of each other in x & y, so only z keeps them apart
+    assert!(a.collide(&Particle::new_3d(0.0, 0.0, 0.25, 1)));
+    assert!(!a.collide(&Particle::new_3d(0.0, 0.0, 0.26, 1)));
+    assert!(!a.collide(&Particle::new_3d(0.15, 0.15, 0.15, 1)));
+
+    let periodic = Domain { half: (5.0, 5.0, 5.0), boundaries: (Boundary::Clamp, Boundary::Clamp, Boundary::Periodic) };
+    let (top, bottom) = (Particle::new_3d(0.0, 0.0, 4.95, 0), Particle::new_3d(0.0, 0.0, -4.95, 1));
+    assert!(!top.collide(&bottom));
+    assert!(top.collide_in(&bottom, &periodic));
+}
+
+#[test]
+fn flat_particles_have_no_depth() {
+    // No z or vz to store or move
+    assert_eq!(size_of::<Particle>() + 2 * size_of::<f32>(), size_of::<Particle<f32, ThreeD>>());
+    assert_eq!(size_of::<Particle<f64>>() + 2 * size_of::<f64>(), size_of::<Particle<f64, ThreeD>>());
+    let particle = Particle::<f32, TwoD>::at((1.0, 2.0, 3.0), 0);
+    assert_eq!(particle.position(), (1.0, 2.0, 0.0));
+
+    let flat = Domain { half: (5.0, 5.0, 0.0), boundaries: (Boundary::Clamp, Boundary::Clamp, Boundary::Periodic) };
+    assert_eq!(flat.dimensions(), 2);
+    let mut particle = Particle::new_3d(1.0, 2.0, 3.0, 0).with_velocity_3d(1.0, 1.0, 1.0);
+    flat.apply(&mut particle);
+    assert_eq!((particle.x, particle.y, particle.z, particle.vz), (1.0, 2.0, 0.0, 0.0));
+
+    // 3D motion can't leak into a 2D run either
+    let mut particle_system = spawned::<TwoD>(SimulationConfig {
+        particle_count: 200,
+        initial_speed: 0.5,
+        species: vec![Species { weight: 1.0, movement: MovementModel::Drift { velocity: (0.0, 0.1, 0.1) } }],
+        num_iterations: 5,
+        thread_count: 3,
+        seed: Some(4),
+        ..SimulationConfig::default()
+    });
+    assert!(particle_system.particles.iter().all(|p| p.z() == 0.0 && p.vz() == 0.0));
+    particle_system.move_particles_loop();
+    assert!(particle_system.particles.iter().all(|p| p.z() == 0.0 && p.vz() == 0.0));
+}
+
+// The config's bounds & the system's type have to agree on the dimension
+#[test]
+#[should_panic(expected = "don't match a 2D system")]
+fn a_flat_system_refuses_a_deep_box() {
+    ParticleSystem::<f32, TwoD>::with_config(SimulationConfig { bounds: (10.0, 10.0, 4.0), ..SimulationConfig::default() });
+}
+
+#[test]
+#[should_panic(expected = "can't be used in a 3D domain")]
+fn quadtree_is_refused_in_3d() {
+    ParticleSystem::<f32, ThreeD>::with_config(SimulationConfig { bounds: (10.0, 10.0, 4.0), broad_phase: BroadPhaseKind::Quadtree, ..SimulationConfig::default() });
+}
+
+#[test]
+fn spawning_fills_the_box_with_isotropic_velocities() {
+    let particle_system = spawned::<ThreeD>(SimulationConfig {
+        particle_count: 6000,
+        bounds: (10.0, 10.0, 4.0),
+        initial_speed: 2.0,
+        seed: Some(8),
+        ..SimulationConfig::default()
+    });
+    let particles = &particle_system.particles;
+    assert!(particles.iter().all(|p| p.z.abs() <= 2.0));
+    assert!(particles.iter().any(|p| p.z > 1.5) && particles.iter().any(|p| p.z < -1.5));
+    assert!(particles.iter().all(|p| (p.vx * p.vx + p.vy * p.vy + p.vz * p.vz).sqrt() <= 2.0 + 1e-5));
+
+    // Every axis gets the same share of the speed
+    let mean_square = |component: fn(&Particle<f32, ThreeD>) -> f32| particles.iter().map(|p| component(p).powi(2) as f64).sum::<f64>() / particles.len() as f64;
+    let (x, z) = (mean_square(|p| p.vx), mean_square(|p| p.vz));
+    assert!((z / x - 1.0).abs() < 0.1, "mean vx^2 {} vz^2 {}", x, z);
+}
+
+#[test]
+fn brownian_mean_squared_displacement_grows_as_6dt_in_3d() {
+    let diffusion = 0.3;
+    let steps = 50;
+    let mut particle_system = spawned::<ThreeD>(SimulationConfig {
+        particle_count: 4000,
+        bounds: (1.0e4, 1.0e4, 1.0e4),
+        species: vec![Species { weight: 1.0, movement: MovementModel::Brownian { diffusion } }],
+        num_iterations: steps,
+        thread_count: 4,
+        seed: Some(5),
+        ..SimulationConfig::default()
+    });
+    let start: Vec<(f32, f32, f32)> = particle_system.particles.iter().map(|p| (p.x, p.y, p.z)).collect();
+    particle_system.move_particles_loop();
+    let msd = particle_system.particles.iter().zip(start).map(|(p, s)| {
+        let d = (p.x - s.0, p.y - s.1, p.z - s.2);
+        (d.0 * d.0 + d.1 * d.1 + d.2 * d.2) as f64
+    }).sum::<f64>() / 4000.0;
+    let expected = 6.0 * diffusion as f64 * steps as f64;
+    assert!((msd - expected).abs() < 0.05 * expected, "msd {} expected {}", msd, expected);
+}
+
+// An ideal-ish gas of hard spheres in a box with mirror walls: the walls & collisions are both
+// elastic, so the kinetic energy stays put, and nobody escapes
+#[test]
+fn gas_in_a_reflecting_box_conserves_energy() {
+    let run = |thread_count| {
+        let mut particle_system = spawned::<ThreeD>(SimulationConfig {
+            particle_count: 400,
+            bounds: (6.0, 6.0, 6.0),
+            boundaries: (Boundary::Reflect, Boundary::Reflect, Boundary::Reflect),
+            particle_radius: RadiusDistribution::Fixed(0.15),
+            initial_speed: 1.0,
+            mass_range: (1.0, 3.0),
+            dynamics: Dynamics::Integrated { integrator: Integrator::VelocityVerlet, dt: 0.05, force: ForceField::None },
+            collision_response: true,
+            broad_phase: BroadPhaseKind::Grid,
+            num_iterations: 60,
+            thread_count,
+            movement_thread_count: 1,
+            seed: Some(12),
+            ..SimulationConfig::default()
+        });
+        let energy = particle_system.kinetic_energy();
+        particle_system.move_and_collide_particles();
+        assert!(particle_system.collision_count() > 0);
+        assert!(particle_system.particles.iter().all(|p| p.x.abs() <= 3.0 && p.y.abs() <= 3.0 && p.z.abs() <= 3.0));
+        let energy_after = particle_system.kinetic_energy();
+        assert!((energy_after - energy).abs() < 1e-3 * energy, "kinetic energy {} -> {}", energy, energy_after);
+        particle_system.particles.iter().map(|p| (p.x.to_bits(), p.y.to_bits(), p.z.to_bits(), p.vz.to_bits())).collect::<Vec<_>>()
+    };
+    assert_eq!(run(2), run(5));
+}
+
+#[test]
+fn depth_is_set_on_the_command_line_and_in_scenarios() {
+    let config = Cli::parse_from(["colliding_particles", "--bounds-z", "5", "--boundary-z", "periodic"]).apply(SimulationConfig::default());
+    assert_eq!(config.bounds, (10.0, 10.0, 5.0));
+    assert_eq!(config.boundaries, (Boundary::Clamp, Boundary::Clamp, Boundary::Periodic));
+    assert!(config.is_3d() && !SimulationConfig::default().is_3d());
+
+    let text = "iterations = 1\n[particles]\ncount = 10\nradius = 0.1\n[bounds]\nx = 5.0\ny = 5.0\nz = 4.0\nboundary = [\"reflect\", \"reflect\", \"periodic\"]\n[threads]\ntotal = 2\n[dynamics]\nmode = \"integrated\"\nintegrator = \"velocity_verlet\"\ndt = 0.01\nforce = { gravity = { acceleration = [0.0, 0.0, -9.81] } }\n";
+    let config = Scenario::from_toml(text).unwrap().config();
+    assert_eq!(config.bounds, (5.0, 5.0, 4.0));
+    assert_eq!(config.boundaries, (Boundary::Reflect, Boundary::Reflect, Boundary::Periodic));
+    assert_eq!(config.dynamics.force(), ForceField::Gravity { acceleration: (0.0, 0.0, -9.81) });
+
+    // 2D vectors still load, with z = 0
+    let flat = Scenario::from_toml(&text.replace("[0.0, 0.0, -9.81]", "[0.0, -9.81]")).unwrap();
+    assert_eq!(flat.config().dynamics.force(), ForceField::Gravity { acceleration: (0.0, -9.81, 0.0) });
+    assert!(Scenario::from_toml(&text.replace("[0.0, 0.0, -9.81]", "[0.0, 0.0, -9.81, 1.0]")).is_err());
+    assert!(Scenario::from_toml(&text.replace("z = 4.0", "z = -1.0")).is_err());
+}
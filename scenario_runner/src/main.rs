@@ -0,0 +1,36 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+use clap::Parser;
+use particle_system::Scenario;
+
+#[derive(Debug, Parser)]
+#[command(about = "Run a particle simulation described by a TOML or JSON scenario file")]
+struct Args {
+    /// Scenario file(s) to run, in order
+    #[arg(required = true)]
+    scenarios: Vec<PathBuf>
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    for path in &args.scenarios {
+        // Load & validate before running anything, so a bad file fails fast
+        let scenario = match Scenario::load(path) {
+            Ok(scenario) => scenario,
+            Err(error) => {
+                eprintln!("error: {}: {}", path.display(), error);
+                return ExitCode::FAILURE;
+            }
+        };
+
+        println!("Running scenario {} ({})", scenario.name, path.display());
+        match scenario.run() {
+            Ok(summary) => println!("Scenario {} finished: {} collisions in {} ms (collision thread imbalance {:.2})", summary.name, summary.collisions, summary.duration_ms, summary.collision_imbalance),
+            Err(error) => {
+                eprintln!("error: {}: {}", path.display(), error);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    ExitCode::SUCCESS
+}